@@ -0,0 +1,26 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use emp::decode::decode;
+use emp::encode::{encode_with, EncodeOptions};
+
+mod sample;
+
+fn bench_decode(c: &mut Criterion) {
+    let val = sample::sample();
+    let mut group = c.benchmark_group("decode");
+
+    for (name, options) in [
+        ("default", EncodeOptions::default()),
+        ("speed", EncodeOptions::speed()),
+        ("size", EncodeOptions::size()),
+    ] {
+        let bytes = encode_with(&val, &options);
+
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(name, |b| b.iter(|| decode(black_box(&bytes))));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use emp::encode::{encode, encode_into, encoded_len};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod sample;
+
+// Counts every allocation so the benchmarks can check that `encode_into` does not make any.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let val = sample::sample();
+    let mut buffer = Vec::with_capacity(encoded_len(&val));
+
+    // Encoding into a buffer which is already large enough should not allocate at all.
+    let owned = allocations(|| {
+        black_box(encode(val.clone()));
+    });
+    let into = allocations(|| {
+        buffer.clear();
+        encode_into(&val, &mut buffer);
+    });
+    assert_eq!(into, 0, "encode_into allocated {} times", into);
+    assert!(owned > 0);
+
+    c.bench_function("encode", |b| b.iter(|| encode(black_box(val.clone()))));
+    c.bench_function("encode_into", |b| {
+        b.iter(|| {
+            buffer.clear();
+            encode_into(black_box(&val), &mut buffer);
+        })
+    });
+    c.bench_function("encoded_len", |b| b.iter(|| encoded_len(black_box(&val))));
+}
+
+criterion_group!(benches, bench_encode);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+use emp::value::Value;
+use std::collections::HashMap;
+
+// A save file with a few hundred players, each with some stats and an inventory.
+pub fn sample() -> Value {
+    let mut players = vec![];
+
+    for i in 0..256 {
+        let mut player = HashMap::new();
+        player.insert("name".to_owned(), Value::String(format!("player-{}", i)));
+        player.insert("score".to_owned(), Value::Int32(i * 97));
+        player.insert("health".to_owned(), Value::Float(i as f32 / 3.0));
+        player.insert("online".to_owned(), Value::Boolean(i % 2 == 0));
+        player.insert(
+            "inventory".to_owned(),
+            Value::Array((0..8).map(|j| Value::Int16((i * j) as i16)).collect()),
+        );
+        players.push(Value::Object(player));
+    }
+
+    let mut save = HashMap::new();
+    save.insert("version".to_owned(), Value::Int8(3));
+    save.insert("players".to_owned(), Value::Array(players));
+
+    Value::Object(save)
+}
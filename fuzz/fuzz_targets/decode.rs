@@ -0,0 +1,25 @@
+#![no_main]
+
+use emp::decode::borrowed::decode_borrowed;
+use emp::decode::parallel::decode_parallel;
+use emp::decode::push::PushDecoder;
+use emp::decode::stream::Decoder;
+use emp::decode::validate::validate;
+use emp::decode::decode;
+use libfuzzer_sys::fuzz_target;
+
+// Every decoder has to return `Ok` or `Err` for any input, and the ones without a depth limit have to agree with
+// `decode` on which.
+fuzz_target!(|data: &[u8]| {
+    let ok = decode(data).is_ok();
+
+    assert_eq!(validate(data).is_ok(), ok);
+    assert_eq!(decode_parallel(data).is_ok(), ok);
+
+    let _ = decode_borrowed(data);
+    let _ = Decoder::new(data).decode();
+
+    let mut push = PushDecoder::new();
+    push.feed(data);
+    let _ = push.next_value();
+});
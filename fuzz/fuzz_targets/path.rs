@@ -0,0 +1,13 @@
+#![no_main]
+
+use emp::value::path::from_str;
+use libfuzzer_sys::fuzz_target;
+
+// Every path which parses has to parse back to itself from its text form.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(path) = from_str(text) {
+            assert_eq!(from_str(&path.to_string()).unwrap(), path);
+        }
+    }
+});
@@ -6,6 +6,16 @@ pub enum ArgumentOptions {
     WriteToFile(String, String),
     FromJSON(String),
     ToJSON(String),
+    #[cfg(feature = "encryption")]
+    Encrypt(String, String),
+    #[cfg(feature = "encryption")]
+    Decrypt(String, String),
+    #[cfg(feature = "signatures")]
+    Sign(String, String),
+    #[cfg(feature = "signatures")]
+    Verify(String, String),
+    #[cfg(feature = "signatures")]
+    PublicKey(String, String),
     Version,
     Help,
 }
@@ -15,34 +25,79 @@ pub fn resolve_arguments(_a: env::Args) -> ArgumentOptions {
     match args.nth(1) {
         Some(a) => match &a[..] {
             "-r" | "--read" => {
-                if let Some(file) = args.nth(0) {
+                if let Some(file) = args.next() {
                     return ArgumentOptions::ReadFromFile(file);
                 }
-                return ArgumentOptions::Help;
+                ArgumentOptions::Help
             }
             "-w" | "--write" => {
-                if let Some(file) = args.nth(0) {
-                    if let Some(val) = args.nth(0) {
+                if let Some(file) = args.next() {
+                    if let Some(val) = args.next() {
                         return ArgumentOptions::WriteToFile(file, val);
                     }
                 }
-                return ArgumentOptions::Help;
+                ArgumentOptions::Help
             }
             "-fj" | "--from_json" => {
-                if let Some(json) = args.nth(0) {
+                if let Some(json) = args.next() {
                     return ArgumentOptions::FromJSON(json);
                 }
-                return ArgumentOptions::Help;
+                ArgumentOptions::Help
             }
             "-tj" | "--to_json" => {
-                if let Some(emp) = args.nth(0) {
+                if let Some(emp) = args.next() {
                     return ArgumentOptions::ToJSON(emp);
                 }
-                return ArgumentOptions::Help;
+                ArgumentOptions::Help
             }
-            "-v" | "--version" => return ArgumentOptions::Version,
-            _ => return ArgumentOptions::Help,
+            #[cfg(feature = "encryption")]
+            "-e" | "--encrypt" => {
+                if let Some(file) = args.next() {
+                    if let Some(key) = args.next() {
+                        return ArgumentOptions::Encrypt(file, key);
+                    }
+                }
+                ArgumentOptions::Help
+            }
+            #[cfg(feature = "encryption")]
+            "-d" | "--decrypt" => {
+                if let Some(file) = args.next() {
+                    if let Some(key) = args.next() {
+                        return ArgumentOptions::Decrypt(file, key);
+                    }
+                }
+                ArgumentOptions::Help
+            }
+            #[cfg(feature = "signatures")]
+            "-s" | "--sign" => {
+                if let Some(file) = args.next() {
+                    if let Some(key) = args.next() {
+                        return ArgumentOptions::Sign(file, key);
+                    }
+                }
+                ArgumentOptions::Help
+            }
+            #[cfg(feature = "signatures")]
+            "-vs" | "--verify" => {
+                if let Some(file) = args.next() {
+                    if let Some(key) = args.next() {
+                        return ArgumentOptions::Verify(file, key);
+                    }
+                }
+                ArgumentOptions::Help
+            }
+            #[cfg(feature = "signatures")]
+            "-pk" | "--public_key" => {
+                if let Some(key) = args.next() {
+                    if let Some(file) = args.next() {
+                        return ArgumentOptions::PublicKey(key, file);
+                    }
+                }
+                ArgumentOptions::Help
+            }
+            "-v" | "--version" => ArgumentOptions::Version,
+            _ => ArgumentOptions::Help,
         },
-        None => return ArgumentOptions::Help,
+        None => ArgumentOptions::Help,
     }
 }
@@ -0,0 +1,143 @@
+//! Sending Values over async byte streams with tokio
+
+use crate::decode::push::PushDecoder;
+use crate::decode::DecodeLimits;
+use crate::encode::{encode_into_with, EncodeOptions};
+use crate::errors::{CodecError, DecodeError, Location};
+use crate::value::path::Path;
+use crate::value::Value;
+use bytes::BytesMut;
+use std::vec::Vec;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A `tokio_util::codec::Encoder` and `tokio_util::codec::Decoder` for
+/// `emp::value::Value`s, for use with `tokio_util::codec::Framed` on a
+/// `TcpStream` or any other async byte stream.
+///
+/// Every frame is a whole piece of EMP bytecode, the same as what
+/// `emp::encode::encode_with` returns, and values are sent one after another
+/// with nothing in between. Decoding uses an `emp::decode::push::PushDecoder`,
+/// so each byte is only decoded once however the stream splits it up.
+///
+/// Frames longer than the maximum frame size are refused with
+/// `emp::errors::CodecError::FrameSizeError`, both when encoding and as soon
+/// as that many bytes of a frame have been received, so a peer can not make
+/// the decoder hold more than that much data. Values are decoded with
+/// `emp::decode::DecodeLimits::default()` unless the codec is created with
+/// `Codec::with_limits`, so a deeply nested frame is refused before it can
+/// overflow the stack when it is dropped.
+pub struct Codec {
+    options: EncodeOptions,
+    max_frame_size: usize,
+    decoder: PushDecoder,
+    // How many bytes of the frame being decoded have been passed to `decoder`.
+    frame: usize,
+    buffer: Vec<u8>,
+}
+
+impl Codec {
+    /// Creates a codec which encodes with `EncodeOptions::default()` and
+    /// refuses frames longer than `max_frame_size` bytes.
+    pub fn new(max_frame_size: usize) -> Codec {
+        Codec::with_options(max_frame_size, EncodeOptions::default())
+    }
+
+    /// Creates a codec which encodes with the given `EncodeOptions` and
+    /// refuses frames longer than `max_frame_size` bytes.
+    pub fn with_options(max_frame_size: usize, options: EncodeOptions) -> Codec {
+        Codec::with_limits(max_frame_size, options, DecodeLimits::default())
+    }
+
+    /// Creates a codec which encodes with the given `EncodeOptions`, refuses
+    /// frames longer than `max_frame_size` bytes and decodes each value
+    /// within the given `limits`.
+    pub fn with_limits(
+        max_frame_size: usize,
+        options: EncodeOptions,
+        limits: DecodeLimits,
+    ) -> Codec {
+        Codec {
+            options,
+            max_frame_size,
+            decoder: PushDecoder::with_limits(limits),
+            frame: 0,
+            buffer: vec![],
+        }
+    }
+
+    /// Returns the length of the longest frame the codec accepts.
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+}
+
+impl Decoder for Codec {
+    type Item = Value;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Value>, CodecError> {
+        if !src.is_empty() {
+            self.frame += src.len();
+            self.decoder.feed(src);
+            src.clear();
+        }
+
+        let val = match self.decoder.next_value() {
+            Ok(v) => v,
+            Err(e) => return Err(CodecError::DecodeError(e)),
+        };
+
+        // Anything left over after a value belongs to the next frame.
+        let len = match val {
+            Some(_) => self.frame - self.decoder.buffered(),
+            None => self.frame,
+        };
+        if len > self.max_frame_size {
+            return Err(CodecError::FrameSizeError(len));
+        }
+        if val.is_some() {
+            self.frame = self.decoder.buffered();
+        }
+
+        Ok(val)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Value>, CodecError> {
+        match self.decode(src)? {
+            Some(val) => Ok(Some(val)),
+            None if self.frame == 0 => Ok(None),
+            None => {
+                let location = Location {
+                    offset: self.decoder.position() + self.decoder.buffered() as u64,
+                    path: Path::default(),
+                };
+                let e = DecodeError::LocatedError(Box::new(DecodeError::EOFError), location);
+                Err(CodecError::DecodeError(e))
+            }
+        }
+    }
+}
+
+impl Encoder<&Value> for Codec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: &Value, dst: &mut BytesMut) -> Result<(), CodecError> {
+        self.buffer.clear();
+        encode_into_with(item, &self.options, &mut self.buffer);
+
+        if self.buffer.len() > self.max_frame_size {
+            return Err(CodecError::FrameSizeError(self.buffer.len()));
+        }
+
+        dst.extend_from_slice(&self.buffer);
+        Ok(())
+    }
+}
+
+impl Encoder<Value> for Codec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Value, dst: &mut BytesMut) -> Result<(), CodecError> {
+        self.encode(&item, dst)
+    }
+}
@@ -6,6 +6,7 @@ pub const ARRAY_START: u8 = 0b00000010;
 pub const ARRAY_END: u8 = 0b00000011;
 pub const STRING: u8 = 0b00000100;
 pub const NULL: u8 = 0b00000101;
+pub const STRING_REFERENCE: u8 = 0b00000110;
 pub const BIT: u8 = 0b00000111;
 pub const BOOLEAN: u8 = 0b00001000;
 pub const INT_32: u8 = 0b00001001;
@@ -14,6 +15,22 @@ pub const DOUBLE: u8 = 0b00001011;
 pub const INT_64: u8 = 0b00001100;
 pub const INT_16: u8 = 0b00001101;
 pub const INT_8: u8 = 0b00001110;
+pub const EXTENSION: u8 = 0b00001111;
+
+// Extension identifiers, stored in the top 4 bits of an `EXTENSION` byte
+pub const EXTENSION_STRING_TABLE: u8 = 0b0000;
+pub const EXTENSION_FEATURES: u8 = 0b0001;
+pub const EXTENSION_MAP: u8 = 0b0010;
+pub const EXTENSION_METADATA: u8 = 0b0011;
+pub const EXTENSION_SCHEMA: u8 = 0b0100;
+pub const EXTENSION_ENCRYPTED: u8 = 0b0101;
+pub const EXTENSION_SIGNED: u8 = 0b0110;
+
+// Flags stored in the byte following an `EXTENSION_FEATURES` byte
+pub const FEATURE_STRING_TABLE: u8 = 0b00000001;
+pub const FEATURE_METADATA: u8 = 0b00000010;
+pub const FEATURE_SCHEMA: u8 = 0b00000100;
+pub const FEATURES: u8 = FEATURE_STRING_TABLE | FEATURE_METADATA | FEATURE_SCHEMA;
 
 pub const EMP_QUOTE: char = '"';
 pub const EMP_OPEN_BRACE: char = '[';
@@ -1,381 +1,845 @@
 //! Decoding EMP Bytecode
 
 use crate::constants;
-use crate::errors::DecodeError;
+use crate::errors::{DecodeError, Location};
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::schema::Schema;
 use crate::value::Value;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+pub mod borrowed;
+#[cfg(feature = "encryption")]
+pub mod decrypt;
+pub mod events;
 pub mod json;
-#[macro_use]
-mod macros;
+pub mod lazy;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod push;
+pub(crate) mod skip;
+pub mod stream;
+pub mod validate;
+#[cfg(feature = "signatures")]
+pub mod verify;
+
+// How deeply containers can be nested by default in the decoders which recurse for each container, which leaves room on
+// the stack of a thread even in debug builds.
+const DEFAULT_DEPTH: usize = 128;
+
+#[derive(Clone, Debug)]
+/// Limits on what decoding a value can use up, for data from untrusted
+/// sources.
+///
+/// `emp::decode::decode` and the other decoders which keep containers on a
+/// stack of their own have no limits unless they are given some.
+/// `DecodeLimits::default()` only limits containers to being nested 128
+/// levels deep, which is what the stream and borrowed decoders use as they
+/// recurse once for each container. `DecodeLimits::none()` turns every limit
+/// off. Going over a limit stops the decoder with one of the `Limit`
+/// variants of `emp::errors::DecodeError`.
+pub struct DecodeLimits {
+    /// How many containers can be nested inside of each other.
+    pub depth: usize,
+    /// How many values can be decoded, including object and map keys and
+    /// the header before the data.
+    pub elements: usize,
+    /// The length of the longest string in bytes.
+    pub string_length: usize,
+    /// Roughly how many bytes the decoded values can take up in memory,
+    /// counting the size of a `Value` for every value along with the bytes
+    /// of every string. Strings from the string table count every time they
+    /// are used, as each use is a copy.
+    pub allocation: usize,
+}
 
-fn decode_bit(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0xf != constants::BIT {
-        return Ok((None, &bytes[1..]));
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            depth: DEFAULT_DEPTH,
+            ..DecodeLimits::none()
+        }
     }
-
-    return Ok((Some(Value::Bit(bytes[0] >> 4 == 1)), &bytes[1..]));
 }
 
-fn decode_bool(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0xf != constants::BOOLEAN {
-        return Ok((None, &bytes[1..]));
+impl DecodeLimits {
+    /// Limits which allow anything.
+    pub fn none() -> Self {
+        DecodeLimits {
+            depth: usize::MAX,
+            elements: usize::MAX,
+            string_length: usize::MAX,
+            allocation: usize::MAX,
+        }
     }
-
-    return Ok((Some(Value::Boolean(bytes[0] >> 4 == 1)), &bytes[1..]));
 }
 
-fn decode_array(mut bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    let mut emp_array: Vec<Value> = vec![];
+// Everything needed to decode the data after its header, along with how much of its limits the data has used up so
+// far. The counts are shared by the header and the data, and by every thread decoding the data.
+struct State {
+    strings: Vec<String>,
+    limits: DecodeLimits,
+    elements: AtomicUsize,
+    allocation: AtomicUsize,
+}
 
-    if bytes[0] & 0xf != constants::ARRAY_START {
-        return Ok((None, &bytes[1..]));
+impl Default for State {
+    fn default() -> Self {
+        State::new(DecodeLimits::none())
     }
+}
 
-    let left = bytes[0] >> 4;
+impl State {
+    fn new(limits: DecodeLimits) -> State {
+        State {
+            strings: vec![],
+            limits,
+            elements: AtomicUsize::new(0),
+            allocation: AtomicUsize::new(0),
+        }
+    }
 
-    bytes = &bytes[1..];
+    // Counts the value about to be decoded.
+    fn value(&self) -> Result<(), DecodeError> {
+        if self.elements.fetch_add(1, Ordering::Relaxed) >= self.limits.elements {
+            return Err(DecodeError::ElementLimitError(self.limits.elements));
+        }
 
-    if left == 0 {
-        while bytes.len() != 0 {
-            if bytes[0] == constants::ARRAY_END {
-                return Ok((Some(Value::Array(emp_array)), &bytes[1..]));
-            }
+        self.allocate(std::mem::size_of::<Value>())
+    }
 
-            let val_tokens = decode(bytes);
+    // Counts a string of `len` bytes, before it is copied out of the data.
+    fn string(&self, len: usize) -> Result<(), DecodeError> {
+        self.string_length(len)?;
+        self.allocate(len)
+    }
 
-            match val_tokens {
-                Ok((val, b)) => {
-                    bytes = b;
-                    emp_array.push(val);
-                }
-                Err(e) => return Err(e),
-            }
+    // Checks the length of a string which is not copied.
+    fn string_length(&self, len: usize) -> Result<(), DecodeError> {
+        if len > self.limits.string_length {
+            return Err(DecodeError::StringLengthLimitError(
+                self.limits.string_length,
+            ));
         }
-    } else {
-        for _ in 0..left {
-            if bytes.len() == 0 {
-                return Err(DecodeError::EOFError);
-            }
 
-            let val_tokens = decode(bytes);
+        Ok(())
+    }
 
-            match val_tokens {
-                Ok((val, b)) => {
-                    bytes = b;
-                    emp_array.push(val);
-                }
-                Err(e) => return Err(e),
-            }
+    fn allocate(&self, len: usize) -> Result<(), DecodeError> {
+        let total = self
+            .allocation
+            .fetch_add(len, Ordering::Relaxed)
+            .saturating_add(len);
+        if total > self.limits.allocation {
+            return Err(DecodeError::AllocationLimitError(self.limits.allocation));
         }
-        return Ok((Some(Value::Array(emp_array)), &bytes));
+
+        Ok(())
+    }
+
+    // How much the data has used up so far, to go back to with `State::reset`.
+    #[cfg(feature = "parallel")]
+    fn usage(&self) -> (usize, usize) {
+        (
+            self.elements.load(Ordering::Relaxed),
+            self.allocation.load(Ordering::Relaxed),
+        )
     }
 
-    return Err(DecodeError::EOFError);
+    #[cfg(feature = "parallel")]
+    fn reset(&self, (elements, allocation): (usize, usize)) {
+        self.elements.store(elements, Ordering::Relaxed);
+        self.allocation.store(allocation, Ordering::Relaxed);
+    }
 }
 
-fn decode_object(mut bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    let mut emp_object: HashMap<String, Value> = HashMap::new();
+// Adds where an error happened to it, unless it already has a location. An `UnexpectedByteError` is placed at the
+// byte it holds. Decoders working on a slice count `offset` as the number of bytes left after the error until
+// `located` turns it around, decoders reading a stream count it from the start.
+fn at(error: DecodeError, offset: u64, path: Path) -> DecodeError {
+    let offset = match error {
+        DecodeError::LocatedError(..) => return error,
+        DecodeError::UnexpectedByteError(_, o) => o,
+        _ => offset,
+    };
+
+    DecodeError::LocatedError(Box::new(error), Location { offset, path })
+}
 
-    if bytes[0] & 0x0F != constants::DICTIONARY_START {
-        return Ok((None, &bytes[1..]));
+// Adds `segments` to the front of the path of an error from inside of a container, for decoders which recurse and
+// for values found by following a path.
+fn within(error: DecodeError, segments: &[Segment]) -> DecodeError {
+    match error {
+        DecodeError::LocatedError(e, mut l) => {
+            l.path.segments.splice(0..0, segments.iter().cloned());
+            DecodeError::LocatedError(e, l)
+        }
+        e => e,
     }
+}
 
-    let left = bytes[0] >> 4;
+// Turns the location of an error from decoding `bytes` into an offset from the start of `bytes`, for the decoders
+// which count the bytes left after an error. Running out of data is always at the end of it.
+fn located<T>(bytes: &[u8], result: Result<T, DecodeError>) -> Result<T, DecodeError> {
+    let (error, left, path) = match result {
+        Ok(val) => return Ok(val),
+        Err(DecodeError::LocatedError(e, l)) => (*e, l.offset, l.path),
+        Err(e) => (e, bytes.len() as u64, Path::default()),
+    };
+
+    let left = match error {
+        DecodeError::EOFError => 0,
+        DecodeError::UnexpectedByteError(_, l) => l,
+        _ => left,
+    };
+    let offset = (bytes.len() as u64).saturating_sub(left);
+
+    let error = match error {
+        DecodeError::UnexpectedByteError(b, _) => DecodeError::UnexpectedByteError(b, offset),
+        e => e,
+    };
+
+    Err(DecodeError::LocatedError(
+        Box::new(error),
+        Location { offset, path },
+    ))
+}
 
-    bytes = &bytes[1..];
+// A container which is being decoded, along with everything decoded into it so far. `len` is the number of values
+// (or pairs) it holds, 0 when it ends with a closing tag instead. `start` is the number of bytes left from its first
+// byte, for errors about the whole container.
+enum Frame {
+    Array {
+        items: Vec<Value>,
+        len: usize,
+        start: usize,
+    },
+    Object {
+        object: HashMap<String, Value>,
+        key: Option<String>,
+        count: usize,
+        len: usize,
+        start: usize,
+    },
+    Map {
+        pairs: Vec<(Value, Value)>,
+        key: Option<Value>,
+        len: usize,
+        start: usize,
+    },
+}
 
-    if left == 0 {
-        let mut current_key: Option<String> = None;
-        while bytes.len() != 0 {
-            if bytes[0] == constants::DICTIONARY_END {
-                if let Some(k) = current_key {
-                    return Err(DecodeError::UnmatchedKeyError(k));
-                }
-                return Ok((Some(Value::Object(emp_object)), &bytes[1..]));
-            }
+impl Frame {
+    fn len(&self) -> usize {
+        match self {
+            Frame::Array { len, .. } | Frame::Object { len, .. } | Frame::Map { len, .. } => *len,
+        }
+    }
 
-            let val_tokens = decode(bytes);
-
-            match val_tokens {
-                Ok((val, b)) => {
-                    bytes = b;
-                    if let Some(c) = current_key.clone() {
-                        emp_object.insert(c.clone(), val);
-                        current_key = None;
-                    } else {
-                        if let Value::String(s) = val {
-                            let c = s;
-                            current_key = Some(c);
-                        }
-                    }
-                }
-                Err(e) => return Err(e),
+    fn start(&self) -> usize {
+        match self {
+            Frame::Array { start, .. } | Frame::Object { start, .. } | Frame::Map { start, .. } => {
+                *start
             }
         }
-    } else {
-        for _ in 0..left {
-            let key;
-
-            let val_tokens = decode(bytes);
-
-            match val_tokens {
-                Ok((val, b)) => {
-                    bytes = b;
-                    if let Value::String(s) = val {
-                        key = s;
-                    } else {
-                        return Err(DecodeError::InvalidKeyError(val));
-                    }
-                }
-                Err(e) => return Err(e),
-            }
-
-            let val_tokens = decode(bytes);
+    }
 
-            match val_tokens {
-                Ok((val, b)) => {
-                    bytes = b;
-                    emp_object.insert(key, val);
-                }
-                Err(e) => return Err(e),
+    // Whether every value in the container has been decoded, `bytes` starts after the last of them.
+    fn done(&self, bytes: &[u8]) -> bool {
+        let next = bytes.first().copied();
+
+        match self {
+            Frame::Array { len: 0, .. } => next == Some(constants::ARRAY_END),
+            Frame::Array { items, len, .. } => items.len() == *len,
+            Frame::Object { len: 0, .. } => next == Some(constants::DICTIONARY_END),
+            Frame::Object { count, len, .. } => count == len,
+            // A map waiting for the value of a key reads the closing tag as that value, which is an error.
+            Frame::Map { key, len: 0, .. } => {
+                key.is_none() && next == Some(constants::DICTIONARY_END)
             }
+            Frame::Map { pairs, len, .. } => pairs.len() == *len,
         }
-        return Ok((Some(Value::Object(emp_object)), &bytes));
+    }
+}
+
+// The path to the value being decoded inside of the containers on `stack`, which stops at a container waiting for a
+// key.
+fn stack_path(stack: &[Frame]) -> Path {
+    let mut segments = vec![];
+
+    for frame in stack {
+        let segment = match frame {
+            Frame::Array { items, .. } => Segment::Index(items.len()),
+            Frame::Object { key: Some(k), .. } => Segment::Key(k.to_owned()),
+            Frame::Map { key: Some(k), .. } => key_segment(k),
+            _ => break,
+        };
+        segments.push(segment);
     }
 
-    return Err(DecodeError::EOFError);
+    Path { segments }
 }
 
-fn decode_string(mut bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    let mut emp_string: Vec<u8> = vec![];
+fn decode_bit(bytes: &[u8]) -> (Value, &[u8]) {
+    (Value::Bit(bytes[0] >> 4 == 1), &bytes[1..])
+}
+
+fn decode_bool(bytes: &[u8]) -> (Value, &[u8]) {
+    (Value::Boolean(bytes[0] >> 4 == 1), &bytes[1..])
+}
+
+fn decode_string<'a>(bytes: &'a [u8], state: &State) -> Result<(Value, &'a [u8]), DecodeError> {
+    let left = (bytes[0] >> 4) as usize;
+
+    let (string, rest) = if left != 0 {
+        if bytes.len() < left + 1 {
+            return Err(DecodeError::EOFError);
+        }
+        (&bytes[1..left + 1], &bytes[left + 1..])
+    } else {
+        match bytes[1..].iter().position(|b| *b == constants::STRING) {
+            Some(end) => (&bytes[1..end + 1], &bytes[end + 2..]),
+            None => return Err(DecodeError::EOFError),
+        }
+    };
+
+    state.string(string.len())?;
 
-    if bytes[0] & 0xf != constants::STRING {
-        return Ok((None, &bytes[1..]));
+    match std::str::from_utf8(string) {
+        Ok(s) => Ok((Value::String(s.to_owned()), rest)),
+        Err(e) => Err(DecodeError::StringDecodeError(e)),
     }
+}
 
-    let left = bytes[0] >> 4;
+fn decode_string_reference<'a>(
+    bytes: &'a [u8],
+    state: &State,
+) -> Result<(Value, &'a [u8]), DecodeError> {
+    let index;
+    let mut rest = &bytes[1..];
 
-    bytes = &bytes[1..];
+    if bytes[0] >> 4 != 0 {
+        index = (bytes[0] >> 4) as u64 - 1;
+    } else {
+        if rest.is_empty() {
+            return Err(DecodeError::EOFError);
+        }
+        if rest[0] & 0x0F != constants::INT_32 {
+            return Err(DecodeError::UnexpectedByteError(rest[0], rest.len() as u64));
+        }
 
-    if left == 0 {
-        while bytes.len() != 0 {
-            if bytes[0] == constants::STRING {
-                let s = match std::str::from_utf8(&emp_string) {
-                    Ok(s) => s,
-                    Err(e) => return Err(DecodeError::StringDecodeError(e)),
-                };
-                return Ok((Some(Value::String(s.to_owned())), &bytes[1..]));
+        match decode_int32(rest)? {
+            (Value::Int32(i), b) if i >= 0 => {
+                index = i as u64;
+                rest = b;
             }
+            _ => return Err(DecodeError::UnexpectedByteError(rest[0], rest.len() as u64)),
+        }
+    }
 
-            emp_string.push(bytes[0]);
-            bytes = &bytes[1..];
+    match state.strings.get(index as usize) {
+        Some(s) => {
+            state.string(s.len())?;
+            Ok((Value::String(s.to_owned()), rest))
         }
-    } else {
-        for _ in 0..left {
-            if bytes.len() == 0 {
-                return Err(DecodeError::EOFError);
-            }
+        None => Err(DecodeError::StringReferenceError(index)),
+    }
+}
 
-            emp_string.push(bytes[0]);
-            bytes = &bytes[1..];
+// Reads the bytes of a number into an array of its full width, putting back the leading zero bytes which were trimmed
+// off. Also returns whether the number is negative.
+fn decode_number<const N: usize>(bytes: &[u8]) -> Result<([u8; N], bool, &[u8]), DecodeError> {
+    let trimmed = (bytes[0] >> 4 & 0b0111) as usize;
+
+    let len = match N.checked_sub(trimmed) {
+        Some(len) => len,
+        None => {
+            return Err(DecodeError::UnexpectedByteError(
+                bytes[0],
+                bytes.len() as u64,
+            ))
         }
+    };
 
-        let s = match std::str::from_utf8(&emp_string) {
-            Ok(s) => s,
-            Err(e) => return Err(DecodeError::StringDecodeError(e)),
-        };
-        return Ok((Some(Value::String(s.to_owned())), &bytes));
+    if bytes.len() < len + 1 {
+        return Err(DecodeError::EOFError);
     }
 
-    return Err(DecodeError::EOFError);
+    let mut number = [0; N];
+    number[trimmed..].copy_from_slice(&bytes[1..len + 1]);
+
+    Ok((number, bytes[0] >> 7 == 1, &bytes[len + 1..]))
 }
 
-fn decode_null(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] != constants::NULL {
-        return Ok((None, &bytes[1..]));
-    }
+fn decode_int64(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    let (number, negative, rest) = decode_number(bytes)?;
+    let i = i64::from_be_bytes(number);
 
-    return Ok((Some(Value::Null), &bytes[1..]));
+    Ok((
+        Value::Int64(if negative { i.wrapping_neg() } else { i }),
+        rest,
+    ))
 }
 
-fn decode_int64(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0x0F != constants::INT_64 {
-        return Ok((None, &bytes[1..]));
-    }
+fn decode_int32(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    let (number, negative, rest) = decode_number(bytes)?;
+    let i = i32::from_be_bytes(number);
+
+    Ok((
+        Value::Int32(if negative { i.wrapping_neg() } else { i }),
+        rest,
+    ))
+}
+
+fn decode_int16(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    let (number, negative, rest) = decode_number(bytes)?;
+    let i = i16::from_be_bytes(number);
+
+    Ok((
+        Value::Int16(if negative { i.wrapping_neg() } else { i }),
+        rest,
+    ))
+}
 
-    let back: usize = (bytes[0] >> 4).into();
+fn decode_int8(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    if bytes[0] >> 4 != 0 {
+        return Ok((Value::Int8((bytes[0] >> 4) as i8), &bytes[1..]));
+    }
 
-    if bytes.len() < 9 - (back & 0b0111) {
+    if bytes.len() < 2 {
         return Err(DecodeError::EOFError);
     }
 
-    return Ok((
-        Some(Value::Int64(
-            i64::from_be_bytes(
-                force_to_length(&bytes[1..9 - (back & 0b0111)], 8)
-                    .try_into()
-                    .expect("Slice with incorrect length"),
-            ) * if (back >> 3) == 0 { 1 } else { -1 },
-        )),
-        &bytes[9 - (back & 0b0111)..],
-    ));
+    Ok((Value::Int8(bytes[1] as i8), &bytes[2..]))
+}
+
+fn decode_float(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    let (number, negative, rest) = decode_number(bytes)?;
+    let f = f32::from_be_bytes(number);
+
+    Ok((Value::Float(if negative { -f } else { f }), rest))
+}
+
+fn decode_double(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    let (number, negative, rest) = decode_number(bytes)?;
+    let d = f64::from_be_bytes(number);
+
+    Ok((Value::Double(if negative { -d } else { d }), rest))
+}
+
+/// Decodes a slice of `u8`s into a `(emp::value::Value, &[u8])` tuple
+///
+/// The `&[u8]` in the tuple is for internal use and should be discorded.
+/// If the decoder encounters an error it will return a
+/// `emp::errors::DecodeError` error instead. Data from untrusted sources
+/// should be decoded with `decode_with_limits` instead, as dropping or
+/// printing a very deeply nested `emp::value::Value` overflows the stack.
+pub fn decode(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    decode_with_limits(bytes, &DecodeLimits::none())
+}
+
+/// Decodes a slice of `u8`s the same way as `emp::decode::decode`, stopping
+/// with an `emp::errors::DecodeError` as soon as the data goes over any of
+/// the `emp::decode::DecodeLimits`.
+pub fn decode_with_limits<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<(Value, &'a [u8]), DecodeError> {
+    located(bytes, decode_data(bytes, limits))
 }
 
-fn decode_int32(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0x0F != constants::INT_32 {
-        return Ok((None, &bytes[1..]));
+fn decode_data<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<(Value, &'a [u8]), DecodeError> {
+    let mut state = State::new(limits.clone());
+
+    let bytes = decode_envelope(bytes)?;
+    let bytes = decode_header(bytes, &mut state)?;
+
+    decode_value(bytes, &state)
+}
+
+/// Decodes only the value at `path` in EMP bytecode, such as
+/// `players[3].name`, skipping over everything else without decoding it.
+///
+/// Paths can be parsed with `emp::value::path::from_str`. If there is no
+/// value at `path` this returns `None`. Every key of the objects and maps on
+/// the way is checked the same as by `emp::decode::decode`, and a key which
+/// appears more than once gives the last of its values.
+pub fn decode_path(bytes: &[u8], path: &Path) -> Result<Option<Value>, DecodeError> {
+    located(bytes, decode_at(bytes, path))
+}
+
+fn decode_at(bytes: &[u8], path: &Path) -> Result<Option<Value>, DecodeError> {
+    let mut state = State::default();
+
+    let bytes = decode_envelope(bytes)?;
+    let bytes = decode_header(bytes, &mut state)?;
+
+    let bytes = match skip::find(bytes, path, &state)? {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+
+    match decode_value(bytes, &state) {
+        Ok((val, _)) => Ok(Some(val)),
+        Err(e) => Err(within(e, &path.segments)),
     }
-    let back: usize = (bytes[0] >> 4).into();
+}
 
-    if bytes.len() < 5 - (back & 0b0111) {
-        return Err(DecodeError::EOFError);
+// Skips over any signatures in front of the data, encrypted data has to be decrypted before it can be decoded. The
+// signatures are skipped in a loop, as data can hold more of them than the stack could recurse through.
+fn decode_envelope(mut bytes: &[u8]) -> Result<&[u8], DecodeError> {
+    while let Some(tag) = bytes.first() {
+        if *tag == constants::EXTENSION | constants::EXTENSION_ENCRYPTED << 4 {
+            return Err(DecodeError::EncryptedError);
+        }
+        if *tag != constants::EXTENSION | constants::EXTENSION_SIGNED << 4 {
+            break;
+        }
+        if bytes.len() < 65 {
+            return Err(DecodeError::EOFError);
+        }
+        bytes = &bytes[65..];
     }
-    return Ok((
-        Some(Value::Int32(
-            i32::from_be_bytes(
-                force_to_length(&bytes[1..5 - (back & 0b0111)], 4)
-                    .try_into()
-                    .expect("Slice with incorrect length"),
-            ) * if (back >> 3) == 0 { 1 } else { -1 },
-        )),
-        &bytes[5 - (back & 0b0111)..],
-    ));
+
+    Ok(bytes)
+}
+
+fn decode_header<'a>(bytes: &'a [u8], state: &mut State) -> Result<&'a [u8], DecodeError> {
+    let bytes = decode_sections(bytes, state)?;
+
+    decode_string_table(bytes, state)
 }
 
-fn decode_int16(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0x0F != constants::INT_16 {
-        return Ok((None, &bytes[1..]));
+// Reads past the features, metadata and schema at the start of the data, returning the bytes from the string table
+// onwards. The metadata and schema are decoded and thrown away rather than skipped, so that data with a malformed
+// header is never decoded.
+fn decode_sections<'a>(bytes: &'a [u8], state: &State) -> Result<&'a [u8], DecodeError> {
+    let (features, mut bytes) = decode_features(bytes)?;
+
+    if features & constants::FEATURE_METADATA != 0 {
+        bytes = decode_section(bytes, constants::EXTENSION_METADATA)?;
+        (_, bytes) = decode_value(bytes, state)?;
     }
 
-    let back: usize = (bytes[0] >> 4).into();
+    if features & constants::FEATURE_SCHEMA != 0 {
+        bytes = decode_section(bytes, constants::EXTENSION_SCHEMA)?;
+        (_, bytes) = decode_value(bytes, state)?;
+    }
 
-    if bytes.len() < 3 - (back & 0b0111) {
-        return Err(DecodeError::EOFError);
+    if features & constants::FEATURE_STRING_TABLE != 0 {
+        decode_section(bytes, constants::EXTENSION_STRING_TABLE)?;
     }
 
-    return Ok((
-        Some(Value::Int16(
-            i16::from_be_bytes(
-                force_to_length(&bytes[1..3 - (back & 0b0111)], 2)
-                    .try_into()
-                    .expect("Slice with incorrect length"),
-            ) * if (back >> 3) == 0 { 1 } else { -1 },
-        )),
-        &bytes[3 - (back & 0b0111)..],
-    ));
+    Ok(bytes)
 }
 
-fn decode_int8(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0x0F != constants::INT_8 {
-        return Ok((None, &bytes[1..]));
+// Reads the features header at the start of the data, if there is one.
+fn decode_features(bytes: &[u8]) -> Result<(u8, &[u8]), DecodeError> {
+    if bytes.is_empty() || bytes[0] != constants::EXTENSION | constants::EXTENSION_FEATURES << 4 {
+        return Ok((0, bytes));
     }
 
-    if bytes.len() < 2 && bytes[0] >> 4 & 0x0F == 0 {
+    if bytes.len() < 2 {
         return Err(DecodeError::EOFError);
     }
 
-    if bytes[0] >> 4 & 0x0F != 0 {
-        return Ok((
-            Some(Value::Int8(i8::from_be_bytes([bytes[0] >> 4]))),
-            &bytes[1..],
+    let features = bytes[1];
+    if features & !constants::FEATURES != 0 {
+        return Err(DecodeError::UnsupportedFeatureError(features));
+    }
+
+    Ok((features, &bytes[2..]))
+}
+
+// Checks that the data continues with the extension byte for the header section `id`, returning what follows it.
+fn decode_section(bytes: &[u8], id: u8) -> Result<&[u8], DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+    if bytes[0] != constants::EXTENSION | id << 4 {
+        return Err(DecodeError::UnexpectedByteError(
+            bytes[0],
+            bytes.len() as u64,
         ));
     }
 
-    return Ok((
-        Some(Value::Int8(i8::from_be_bytes([bytes[1]]))),
-        &bytes[2..],
-    ));
+    Ok(&bytes[1..])
 }
 
-fn decode_float(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0x0F != constants::FLOAT {
-        return Ok((None, &bytes[1..]));
+/// Decodes the schema stored at the start of EMP bytecode, without decoding
+/// the rest of the data.
+///
+/// If the data has no schema this returns `None`.
+pub fn decode_schema(bytes: &[u8]) -> Result<Option<Schema>, DecodeError> {
+    located(bytes, read_schema(bytes))
+}
+
+fn read_schema(bytes: &[u8]) -> Result<Option<Schema>, DecodeError> {
+    let state = State::default();
+    let (features, mut bytes) = decode_features(decode_envelope(bytes)?)?;
+
+    if features & constants::FEATURE_SCHEMA == 0 {
+        return Ok(None);
     }
 
-    let back: usize = (bytes[0] >> 4).into();
+    if features & constants::FEATURE_METADATA != 0 {
+        bytes = decode_section(bytes, constants::EXTENSION_METADATA)?;
+        (_, bytes) = decode_value(bytes, &state)?;
+    }
 
-    if bytes.len() < 5 - (back & 0b0111) {
-        return Err(DecodeError::EOFError);
+    let bytes = decode_section(bytes, constants::EXTENSION_SCHEMA)?;
+    let (val, _) = decode_value(bytes, &state)?;
+
+    match Schema::from_value(&val) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) => Err(DecodeError::SchemaError(e)),
     }
+}
 
-    return Ok((
-        Some(Value::Float(
-            f32::from_be_bytes(
-                force_to_length(&bytes[1..5 - (back & 0b0111)], 4)
-                    .try_into()
-                    .expect("Slice with incorrect length"),
-            ) * if (back >> 3) == 0 { 1.0 } else { -1.0 },
-        )),
-        &bytes[5 - (back & 0b0111)..],
-    ));
+/// Decodes the metadata stored at the start of EMP bytecode, without
+/// decoding the rest of the data.
+///
+/// If the data has no metadata this returns `None`.
+pub fn decode_metadata(bytes: &[u8]) -> Result<Option<HashMap<String, Value>>, DecodeError> {
+    located(bytes, read_metadata(bytes))
 }
 
-fn decode_double(bytes: &[u8]) -> Result<(Option<Value>, &[u8]), DecodeError> {
-    if bytes[0] & 0x0F != constants::DOUBLE {
-        return Ok((None, &bytes[1..]));
+fn read_metadata(bytes: &[u8]) -> Result<Option<HashMap<String, Value>>, DecodeError> {
+    let (features, bytes) = decode_features(decode_envelope(bytes)?)?;
+
+    if features & constants::FEATURE_METADATA == 0 {
+        return Ok(None);
     }
-    let back: usize = (bytes[0] >> 4).into();
 
-    if bytes.len() < 9 - (back & 0b0111) {
-        return Err(DecodeError::EOFError);
+    let bytes = decode_section(bytes, constants::EXTENSION_METADATA)?;
+
+    match decode_value(bytes, &State::default())? {
+        (Value::Object(o), _) => Ok(Some(o)),
+        (_, _) => Err(DecodeError::UnexpectedByteError(
+            bytes[0],
+            bytes.len() as u64,
+        )),
+    }
+}
+
+fn decode_string_table<'a>(bytes: &'a [u8], state: &mut State) -> Result<&'a [u8], DecodeError> {
+    if bytes.is_empty() || bytes[0] != constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4
+    {
+        return Ok(bytes);
     }
 
-    return Ok((
-        Some(Value::Double(
-            f64::from_be_bytes(
-                force_to_length(&bytes[1..9 - (back & 0b0111)], 8)
-                    .try_into()
-                    .expect("Slice with incorrect length"),
-            ) * if (back >> 3) == 0 { 1.0 } else { -1.0 },
+    match decode_value(&bytes[1..], state) {
+        Ok((Value::Array(a), b)) => {
+            for val in a {
+                match val {
+                    Value::String(s) => state.strings.push(s),
+                    _ => {
+                        return Err(DecodeError::UnexpectedByteError(
+                            bytes[1],
+                            (bytes.len() - 1) as u64,
+                        ))
+                    }
+                }
+            }
+            Ok(b)
+        }
+        Ok(_) => Err(DecodeError::UnexpectedByteError(
+            bytes[1],
+            (bytes.len() - 1) as u64,
         )),
-        &bytes[9 - (back & 0b0111)..],
-    ));
+        Err(e) => Err(e),
+    }
 }
 
-/// Decodes a slice of `u8`s into a `(emp::value::Value, &[u8])` tuple
-///
-/// The `&[u8]` in the tuple is for internal use and should be discorded.
-/// If the decoder encounters an error it will return a
-/// `emp::errors::DecodeError` error instead.
-pub fn decode(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
-    try_decode!(decode_bit, bytes);
-    try_decode!(decode_bool, bytes);
-    try_decode!(decode_array, bytes);
-    try_decode!(decode_string, bytes);
-    try_decode!(decode_null, bytes);
-    try_decode!(decode_int32, bytes);
-    try_decode!(decode_object, bytes);
-    try_decode!(decode_float, bytes);
-    try_decode!(decode_double, bytes);
-    try_decode!(decode_int64, bytes);
-    try_decode!(decode_int16, bytes);
-    try_decode!(decode_int8, bytes);
-
-    return Err(DecodeError::UnexpectedByteError(
-        bytes[0],
-        bytes.len() as u64,
-    ));
+// Decodes the value at the start of `bytes`, going straight to the routine for its identifier. Containers are kept on
+// a stack of their own instead of recursing, so deeply nested data can not overflow the call stack.
+fn decode_value<'a>(bytes: &'a [u8], state: &State) -> Result<(Value, &'a [u8]), DecodeError> {
+    decode_nested(bytes, state, 0)
 }
 
-/// Decodes a lice of `u8`s into an `emp::value::Value`.
-///
-/// If it encounters an error it will instead return a `emp::value::Value::Null`
-pub fn decode_safe(val: &[u8]) -> Value {
-    match decode(val) {
-        Ok((c, _)) => return c,
-        Err(_) => return Value::Null,
+// Decodes the value at the start of `bytes` the same way as `decode_value`, for a value which is already inside of
+// `depth` containers.
+fn decode_nested<'a>(
+    mut bytes: &'a [u8],
+    state: &State,
+    depth: usize,
+) -> Result<(Value, &'a [u8]), DecodeError> {
+    let mut stack: Vec<Frame> = vec![];
+
+    match decode_frames(&mut bytes, &mut stack, state, depth) {
+        Ok(val) => Ok((val, bytes)),
+        Err(e) => Err(at(e, bytes.len() as u64, stack_path(&stack))),
     }
 }
 
-fn force_to_length(arr: &[u8], n: u64) -> Vec<u8> {
-    let mut zero: Vec<u8> = Vec::new();
+// Does the work of `decode_nested`, leaving `bytes` at the start of the value an error is in along with the
+// containers it is inside of on `stack`.
+fn decode_frames(
+    bytes: &mut &[u8],
+    stack: &mut Vec<Frame>,
+    state: &State,
+    depth: usize,
+) -> Result<Value, DecodeError> {
+    loop {
+        let finished = match stack.last() {
+            Some(frame) => frame.done(bytes),
+            None => false,
+        };
+
+        // Where the value starts, which is where an error about it being a key is.
+        let mut start = bytes.len();
+        let (val, rest) = if finished {
+            let rest = if stack.last().map(Frame::len) == Some(0) {
+                &bytes[1..]
+            } else {
+                *bytes
+            };
+            start = stack.last().map_or(start, Frame::start);
+
+            let val = match stack.pop() {
+                Some(Frame::Array { items, .. }) => Value::Array(items),
+                Some(Frame::Object {
+                    key: Some(k),
+                    len: 0,
+                    ..
+                }) => return Err(DecodeError::UnmatchedKeyError(k)),
+                Some(Frame::Object { object, .. }) => Value::Object(object),
+                Some(Frame::Map { pairs, .. }) => Value::Map(pairs),
+                None => Value::Null,
+            };
+            (val, rest)
+        } else {
+            if bytes.is_empty() {
+                return Err(DecodeError::EOFError);
+            }
+
+            let tag = bytes[0];
+            let len = (tag >> 4) as usize;
+
+            state.value()?;
+            let container = tag & 0x0F == constants::ARRAY_START
+                || tag & 0x0F == constants::DICTIONARY_START
+                || tag == constants::EXTENSION | constants::EXTENSION_MAP << 4;
+            if container && depth + stack.len() >= state.limits.depth {
+                return Err(DecodeError::DepthLimitError(state.limits.depth));
+            }
 
-    while zero.len() + arr.len() < n as usize {
-        zero.push(0);
+            match tag & 0x0F {
+                constants::ARRAY_START => {
+                    stack.push(Frame::Array {
+                        items: Vec::with_capacity(len),
+                        len,
+                        start: bytes.len(),
+                    });
+                    *bytes = &bytes[1..];
+                    continue;
+                }
+                constants::DICTIONARY_START => {
+                    stack.push(Frame::Object {
+                        object: HashMap::with_capacity(len),
+                        key: None,
+                        count: 0,
+                        len,
+                        start: bytes.len(),
+                    });
+                    *bytes = &bytes[1..];
+                    continue;
+                }
+                constants::EXTENSION
+                    if tag == constants::EXTENSION | constants::EXTENSION_MAP << 4 =>
+                {
+                    if bytes.len() < 2 {
+                        return Err(DecodeError::EOFError);
+                    }
+                    if bytes[1] & 0x0F != constants::DICTIONARY_START {
+                        return Err(DecodeError::UnexpectedByteError(
+                            bytes[1],
+                            (bytes.len() - 1) as u64,
+                        ));
+                    }
+
+                    let len = (bytes[1] >> 4) as usize;
+                    stack.push(Frame::Map {
+                        pairs: Vec::with_capacity(len),
+                        key: None,
+                        len,
+                        start: bytes.len(),
+                    });
+                    *bytes = &bytes[2..];
+                    continue;
+                }
+                constants::STRING => decode_string(bytes, state)?,
+                constants::STRING_REFERENCE => decode_string_reference(bytes, state)?,
+                constants::NULL if tag == constants::NULL => (Value::Null, &bytes[1..]),
+                constants::BIT => decode_bit(bytes),
+                constants::BOOLEAN => decode_bool(bytes),
+                constants::INT_32 => decode_int32(bytes)?,
+                constants::FLOAT => decode_float(bytes)?,
+                constants::DOUBLE => decode_double(bytes)?,
+                constants::INT_64 => decode_int64(bytes)?,
+                constants::INT_16 => decode_int16(bytes)?,
+                constants::INT_8 => decode_int8(bytes)?,
+                _ => return Err(DecodeError::UnexpectedByteError(tag, bytes.len() as u64)),
+            }
+        };
+
+        // Hands the finished value to the container it is in, the data is done once the outermost value is.
+        let invalid = match stack.last_mut() {
+            None => {
+                *bytes = rest;
+                return Ok(val);
+            }
+            Some(Frame::Array { items, .. }) => {
+                items.push(val);
+                None
+            }
+            Some(Frame::Object {
+                object, key, count, ..
+            }) => match (key.take(), val) {
+                (Some(k), val) => {
+                    object.insert(k, val);
+                    *count += 1;
+                    None
+                }
+                (None, Value::String(s)) => {
+                    *key = Some(s);
+                    None
+                }
+                (None, val) => Some(val),
+            },
+            Some(Frame::Map { pairs, key, .. }) => match key.take() {
+                Some(k) => {
+                    pairs.push((k, val));
+                    None
+                }
+                None if val.is_scalar() => {
+                    *key = Some(val);
+                    None
+                }
+                None => Some(val),
+            },
+        };
+
+        // A key which is not allowed is placed at its first byte, even if it is a container which has been decoded.
+        if let Some(val) = invalid {
+            let e = DecodeError::InvalidKeyError(val);
+            return Err(at(e, start as u64, stack_path(stack)));
+        }
+        *bytes = rest;
     }
+}
 
-    let out = [zero, Vec::from(arr)].concat();
-    return out;
+/// Decodes a lice of `u8`s into an `emp::value::Value`.
+///
+/// If it encounters an error it will instead return a `emp::value::Value::Null`
+pub fn decode_safe(val: &[u8]) -> Value {
+    match decode(val) {
+        Ok((c, _)) => c,
+        Err(_) => Value::Null,
+    }
 }
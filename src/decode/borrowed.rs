@@ -0,0 +1,372 @@
+//! Decoding EMP Bytecode without copying out of it
+
+use crate::constants;
+use crate::decode::{
+    at, decode_envelope, decode_int32, decode_sections, decode_value, located, within,
+    DecodeLimits, State,
+};
+use crate::errors::DecodeError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A value which borrows its strings from the bytecode it was decoded from.
+///
+/// Objects keep their fields in the order they are stored, and are turned
+/// into a `HashMap` by `ValueRef::to_value`. Every other variant matches the
+/// `Value` variant with the same name.
+pub enum ValueRef<'a> {
+    Null,
+    Object(Vec<(&'a str, ValueRef<'a>)>),
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    Array(Vec<ValueRef<'a>>),
+    String(&'a str),
+    Bit(bool),
+    Boolean(bool),
+    Int32(i32),
+    Float(f32),
+    Double(f64),
+    Int64(i64),
+    Int16(i16),
+    Int8(i8),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Copies the value into an owned `emp::value::Value`.
+    pub fn to_value(&self) -> Value {
+        match self {
+            ValueRef::Null => Value::Null,
+            ValueRef::Object(o) => {
+                let mut object = HashMap::new();
+                for (k, v) in o {
+                    object.insert(k.to_string(), v.to_value());
+                }
+                Value::Object(object)
+            }
+            ValueRef::Map(m) => Value::Map(
+                m.iter()
+                    .map(|(k, v)| (k.to_value(), v.to_value()))
+                    .collect(),
+            ),
+            ValueRef::Array(a) => Value::Array(a.iter().map(|v| v.to_value()).collect()),
+            ValueRef::String(s) => Value::String(s.to_string()),
+            ValueRef::Bit(b) => Value::Bit(*b),
+            ValueRef::Boolean(b) => Value::Boolean(*b),
+            ValueRef::Int32(i) => Value::Int32(*i),
+            ValueRef::Float(f) => Value::Float(*f),
+            ValueRef::Double(d) => Value::Double(*d),
+            ValueRef::Int64(i) => Value::Int64(*i),
+            ValueRef::Int16(i) => Value::Int16(*i),
+            ValueRef::Int8(i) => Value::Int8(*i),
+        }
+    }
+
+    fn is_scalar(&self) -> bool {
+        !matches!(
+            self,
+            ValueRef::Null | ValueRef::Object(_) | ValueRef::Map(_) | ValueRef::Array(_)
+        )
+    }
+}
+
+type Pairs<'a> = Vec<(ValueRef<'a>, ValueRef<'a>)>;
+
+impl<'a> From<ValueRef<'a>> for Value {
+    fn from(val: ValueRef<'a>) -> Value {
+        val.to_value()
+    }
+}
+
+/// Decodes a slice of `u8`s into an `emp::decode::borrowed::ValueRef` which
+/// borrows every string from `bytes` instead of copying it.
+///
+/// If the decoder encounters an error it will return a
+/// `emp::errors::DecodeError` instead. This uses
+/// `emp::decode::DecodeLimits::default()`.
+pub fn decode_borrowed(bytes: &[u8]) -> Result<ValueRef<'_>, DecodeError> {
+    decode_borrowed_with_limits(bytes, &DecodeLimits::default())
+}
+
+/// Decodes a slice of `u8`s the same way as
+/// `emp::decode::borrowed::decode_borrowed`, stopping with an
+/// `emp::errors::DecodeError` as soon as the data goes over any of the
+/// `emp::decode::DecodeLimits`.
+///
+/// Strings are not copied, so they only count towards the allocation limit
+/// through the size of the `ValueRef` holding them.
+pub fn decode_borrowed_with_limits<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<ValueRef<'a>, DecodeError> {
+    located(bytes, decode_data(bytes, limits))
+}
+
+fn decode_data<'a>(bytes: &'a [u8], limits: &DecodeLimits) -> Result<ValueRef<'a>, DecodeError> {
+    let state = State::new(limits.clone());
+
+    let bytes = decode_sections(decode_envelope(bytes)?, &state)?;
+    let (strings, bytes) = decode_string_table(bytes, &state)?;
+
+    let (val, _) = decode_value_ref(bytes, &strings, &state, 0)?;
+    Ok(val)
+}
+
+fn decode_string_table<'a>(
+    bytes: &'a [u8],
+    state: &State,
+) -> Result<(Vec<&'a str>, &'a [u8]), DecodeError> {
+    let mut strings = vec![];
+
+    if bytes.is_empty() || bytes[0] != constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4
+    {
+        return Ok((strings, bytes));
+    }
+
+    let (table, rest) = decode_value_ref(&bytes[1..], &[], state, 0)?;
+    let table = match table {
+        ValueRef::Array(a) => a,
+        _ => {
+            return Err(DecodeError::UnexpectedByteError(
+                bytes[1],
+                (bytes.len() - 1) as u64,
+            ))
+        }
+    };
+
+    for val in table {
+        match val {
+            ValueRef::String(s) => strings.push(s),
+            _ => {
+                return Err(DecodeError::UnexpectedByteError(
+                    bytes[1],
+                    (bytes.len() - 1) as u64,
+                ))
+            }
+        }
+    }
+
+    Ok((strings, rest))
+}
+
+// Decodes the value at the start of `bytes`, which is inside of `depth` containers.
+fn decode_value_ref<'a>(
+    bytes: &'a [u8],
+    strings: &[&'a str],
+    state: &State,
+    depth: usize,
+) -> Result<(ValueRef<'a>, &'a [u8]), DecodeError> {
+    match decode_ref(bytes, strings, state, depth) {
+        Ok(val) => Ok(val),
+        Err(e) => Err(at(e, bytes.len() as u64, Path::default())),
+    }
+}
+
+fn decode_ref<'a>(
+    bytes: &'a [u8],
+    strings: &[&'a str],
+    state: &State,
+    depth: usize,
+) -> Result<(ValueRef<'a>, &'a [u8]), DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+
+    let left = (bytes[0] >> 4) as usize;
+    let container = bytes[0] & 0x0F == constants::ARRAY_START
+        || bytes[0] & 0x0F == constants::DICTIONARY_START
+        || bytes[0] == constants::EXTENSION | constants::EXTENSION_MAP << 4;
+    if container && depth >= state.limits.depth {
+        return Err(DecodeError::DepthLimitError(state.limits.depth));
+    }
+
+    match bytes[0] & 0x0F {
+        constants::STRING => {
+            state.value()?;
+            return decode_str(bytes, state);
+        }
+        constants::STRING_REFERENCE => {
+            state.value()?;
+            let (index, rest) = if left != 0 {
+                (left - 1, &bytes[1..])
+            } else {
+                let rest = &bytes[1..];
+                if rest.is_empty() {
+                    return Err(DecodeError::EOFError);
+                }
+                if rest[0] & 0x0F != constants::INT_32 {
+                    return Err(DecodeError::UnexpectedByteError(rest[0], rest.len() as u64));
+                }
+
+                match decode_int32(rest)? {
+                    (Value::Int32(i), rest) if i >= 0 => (i as usize, rest),
+                    _ => {
+                        return Err(DecodeError::UnexpectedByteError(
+                            bytes[0],
+                            bytes.len() as u64,
+                        ))
+                    }
+                }
+            };
+
+            match strings.get(index) {
+                Some(s) => return Ok((ValueRef::String(s), rest)),
+                None => return Err(DecodeError::StringReferenceError(index as u64)),
+            }
+        }
+        constants::ARRAY_START => {
+            state.value()?;
+            let mut array = vec![];
+            let mut rest = &bytes[1..];
+
+            while left == 0 || array.len() < left {
+                if rest.is_empty() {
+                    return Err(DecodeError::EOFError);
+                }
+                if left == 0 && rest[0] == constants::ARRAY_END {
+                    rest = &rest[1..];
+                    break;
+                }
+
+                let (val, r) = match decode_value_ref(rest, strings, state, depth + 1) {
+                    Ok(v) => v,
+                    Err(e) => return Err(within(e, &[Segment::Index(array.len())])),
+                };
+                array.push(val);
+                rest = r;
+            }
+
+            return Ok((ValueRef::Array(array), rest));
+        }
+        constants::DICTIONARY_START => {
+            state.value()?;
+            let (pairs, rest) = decode_pairs(bytes, true, strings, state, depth + 1)?;
+
+            let mut object = Vec::with_capacity(pairs.len());
+            for (key, val) in pairs {
+                match key {
+                    ValueRef::String(s) => object.push((s, val)),
+                    _ => return Err(DecodeError::InvalidKeyError(key.to_value())),
+                }
+            }
+
+            return Ok((ValueRef::Object(object), rest));
+        }
+        constants::EXTENSION
+            if bytes[0] == constants::EXTENSION | constants::EXTENSION_MAP << 4 =>
+        {
+            if bytes.len() < 2 {
+                return Err(DecodeError::EOFError);
+            }
+            if bytes[1] & 0x0F != constants::DICTIONARY_START {
+                return Err(DecodeError::UnexpectedByteError(
+                    bytes[1],
+                    (bytes.len() - 1) as u64,
+                ));
+            }
+
+            state.value()?;
+            let (pairs, rest) = decode_pairs(&bytes[1..], false, strings, state, depth + 1)?;
+            return Ok((ValueRef::Map(pairs), rest));
+        }
+        _ => {}
+    }
+
+    // Everything else has nothing to borrow, so it is decoded the same way as `emp::decode::decode` does.
+    let (val, rest) = decode_value(bytes, state)?;
+    let val = match val {
+        Value::Null => ValueRef::Null,
+        Value::Bit(b) => ValueRef::Bit(b),
+        Value::Boolean(b) => ValueRef::Boolean(b),
+        Value::Int32(i) => ValueRef::Int32(i),
+        Value::Float(f) => ValueRef::Float(f),
+        Value::Double(d) => ValueRef::Double(d),
+        Value::Int64(i) => ValueRef::Int64(i),
+        Value::Int16(i) => ValueRef::Int16(i),
+        Value::Int8(i) => ValueRef::Int8(i),
+        _ => {
+            return Err(DecodeError::UnexpectedByteError(
+                bytes[0],
+                bytes.len() as u64,
+            ))
+        }
+    };
+
+    Ok((val, rest))
+}
+
+// Decodes the pairs of the dictionary starting at `bytes`, checking each key as soon as it is decoded the same way as
+// `emp::decode::decode` does. Keys of an `object` have to be strings, and keys of a map have to be scalars. The pairs
+// are inside of `depth` containers.
+fn decode_pairs<'a>(
+    bytes: &'a [u8],
+    object: bool,
+    strings: &[&'a str],
+    state: &State,
+    depth: usize,
+) -> Result<(Pairs<'a>, &'a [u8]), DecodeError> {
+    let left = (bytes[0] >> 4) as usize;
+    let mut rest = &bytes[1..];
+    let mut pairs = vec![];
+
+    while left == 0 || pairs.len() < left {
+        if rest.is_empty() {
+            return Err(DecodeError::EOFError);
+        }
+        if left == 0 && rest[0] == constants::DICTIONARY_END {
+            rest = &rest[1..];
+            break;
+        }
+
+        let (key, r) = decode_value_ref(rest, strings, state, depth)?;
+        let valid = match key {
+            ValueRef::String(_) => true,
+            _ => !object && key.is_scalar(),
+        };
+        if !valid {
+            let e = DecodeError::InvalidKeyError(key.to_value());
+            return Err(at(e, rest.len() as u64, Path::default()));
+        }
+
+        // A map reads the closing tag as the value of the key, which is an error of its own.
+        if let (ValueRef::String(s), true, 0) = (&key, object, left) {
+            if r.first() == Some(&constants::DICTIONARY_END) {
+                let e = DecodeError::UnmatchedKeyError((*s).to_owned());
+                return Err(at(e, r.len() as u64, Path::default()));
+            }
+        }
+
+        let (val, r) = match decode_value_ref(r, strings, state, depth) {
+            Ok(v) => v,
+            Err(e) => return Err(within(e, &[key_segment(&key.to_value())])),
+        };
+        pairs.push((key, val));
+        rest = r;
+    }
+
+    Ok((pairs, rest))
+}
+
+fn decode_str<'a>(bytes: &'a [u8], state: &State) -> Result<(ValueRef<'a>, &'a [u8]), DecodeError> {
+    let left = (bytes[0] >> 4) as usize;
+
+    let (string, rest) = if left != 0 {
+        if bytes.len() < left + 1 {
+            return Err(DecodeError::EOFError);
+        }
+        (&bytes[1..left + 1], &bytes[left + 1..])
+    } else {
+        match bytes[1..].iter().position(|b| *b == constants::STRING) {
+            Some(end) => (&bytes[1..end + 1], &bytes[end + 2..]),
+            None => return Err(DecodeError::EOFError),
+        }
+    };
+
+    state.string_length(string.len())?;
+
+    match std::str::from_utf8(string) {
+        Ok(s) => Ok((ValueRef::String(s), rest)),
+        Err(e) => Err(DecodeError::StringDecodeError(e)),
+    }
+}
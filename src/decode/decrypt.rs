@@ -0,0 +1,62 @@
+//! Decrypting encrypted EMP Bytecode
+
+use crate::constants;
+use crate::decode;
+use crate::errors::DecodeError;
+use crate::value::Value;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::vec::Vec;
+
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+/// Decrypts data created by `emp::encode::encrypt::encrypt` with the same
+/// 32 byte `key`, giving back the EMP bytecode.
+///
+/// If the key is wrong or the data has been changed in any way it returns
+/// `emp::errors::DecodeError::AuthenticationError`.
+pub fn decrypt(bytes: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, DecodeError> {
+    decode::located(bytes, open(bytes, key))
+}
+
+fn open(bytes: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, DecodeError> {
+    let identifier = constants::EXTENSION | constants::EXTENSION_ENCRYPTED << 4;
+
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+    if bytes[0] != identifier {
+        return Err(DecodeError::UnexpectedByteError(
+            bytes[0],
+            bytes.len() as u64,
+        ));
+    }
+    if bytes.len() < 1 + NONCE_LENGTH + TAG_LENGTH {
+        return Err(DecodeError::EOFError);
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+
+    match cipher.decrypt(
+        Nonce::from_slice(&bytes[1..1 + NONCE_LENGTH]),
+        Payload {
+            msg: &bytes[1 + NONCE_LENGTH..],
+            aad: &[identifier],
+        },
+    ) {
+        Ok(b) => Ok(b),
+        Err(_) => Err(DecodeError::AuthenticationError),
+    }
+}
+
+/// Decrypts data created by `emp::encode::encrypt::encode_encrypted` with the
+/// same 32 byte `key` and decodes it into an `emp::value::Value`.
+pub fn decode_encrypted(bytes: &[u8], key: &[u8; 32]) -> Result<Value, DecodeError> {
+    let decrypted = decrypt(bytes, key)?;
+
+    match decode::decode(&decrypted) {
+        Ok((v, _)) => Ok(v),
+        Err(e) => Err(e),
+    }
+}
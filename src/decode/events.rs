@@ -0,0 +1,291 @@
+//! Reading EMP Bytecode as a series of events
+
+use crate::constants;
+use crate::decode::{
+    at, decode_envelope, decode_header, decode_value, located, skip, within, State,
+};
+use crate::errors::DecodeError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A single piece of EMP data read by an `emp::decode::events::EventReader`.
+///
+/// Objects are made up of a `Key` followed by the events of its value for
+/// every field, while maps give the events of the key and then the value for
+/// every pair. Every other variant matches the `Value` variant with the same
+/// name.
+pub enum Event {
+    StartObject,
+    EndObject,
+    StartMap,
+    EndMap,
+    StartArray,
+    EndArray,
+    Key(String),
+    Null,
+    Bit(bool),
+    Boolean(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+}
+
+// A container which has been started but not ended.
+struct Container {
+    end: Event,
+    // The byte which ends the container, only used when `len` is 0.
+    tag: u8,
+    // How many values the container holds (keys included), 0 when it ends with a closing tag instead.
+    len: usize,
+    count: usize,
+    object: bool,
+    // The key of the value being read, for containers holding pairs.
+    key: Option<Segment>,
+}
+
+/// Reads EMP bytecode one event at a time without building any `Value`s,
+/// which is useful for picking a few fields out of a large amount of data.
+///
+/// The reader is an `Iterator` over each `Event` along with the offset of
+/// the byte it starts at. Values which are not needed can be stepped over
+/// with `EventReader::skip_value` without reading any of their events.
+pub struct EventReader<'a> {
+    bytes: &'a [u8],
+    rest: &'a [u8],
+    state: State,
+    stack: Vec<Container>,
+    done: bool,
+}
+
+impl<'a> EventReader<'a> {
+    /// Creates a reader over `bytes`, reading the header of the data right
+    /// away.
+    pub fn new(bytes: &'a [u8]) -> Result<EventReader<'a>, DecodeError> {
+        let mut state = State::default();
+        let header = decode_envelope(bytes).and_then(|b| decode_header(b, &mut state));
+        let rest = located(bytes, header)?;
+
+        Ok(EventReader {
+            bytes,
+            rest,
+            state,
+            stack: vec![],
+            done: false,
+        })
+    }
+
+    /// Returns the offset of the next byte which will be read.
+    pub fn offset(&self) -> usize {
+        self.bytes.len() - self.rest.len()
+    }
+
+    /// Skips over the next value, without producing any of its events.
+    ///
+    /// If the next event ends a container this does nothing.
+    pub fn skip_value(&mut self) -> Result<(), DecodeError> {
+        if self.done || self.at_end() {
+            return Ok(());
+        }
+
+        let rest = skip::skip_value(self.rest);
+        self.rest = self.locate(rest, self.rest.len())?;
+        self.finish_value();
+
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<(Event, usize), DecodeError> {
+        let left = self.rest.len();
+        let event = self.read_event();
+        self.locate(event, left)
+    }
+
+    // Adds where in the data an error happened to it, `left` being the number of bytes left from the start of the
+    // value it is in.
+    fn locate<T>(&self, result: Result<T, DecodeError>, left: usize) -> Result<T, DecodeError> {
+        match result {
+            Ok(val) => Ok(val),
+            Err(e) => {
+                let e = at(e, left as u64, Path::default());
+                located(self.bytes, Err(within(e, &self.path().segments)))
+            }
+        }
+    }
+
+    // The path to the next value, which stops at a container waiting for a key.
+    fn path(&self) -> Path {
+        let mut segments = vec![];
+
+        for c in &self.stack {
+            let segment = match &c.key {
+                _ if c.end == Event::EndArray => Segment::Index(c.count),
+                Some(k) if c.count % 2 == 1 => k.clone(),
+                _ => break,
+            };
+            segments.push(segment);
+        }
+
+        Path { segments }
+    }
+
+    fn read_event(&mut self) -> Result<(Event, usize), DecodeError> {
+        let offset = self.offset();
+
+        if self.at_end() {
+            // A map reads the closing tag as the value of the key before it, so the error is inside of that key.
+            if let Some(c) = self.stack.last() {
+                if !c.object && c.end != Event::EndArray && c.count % 2 == 1 {
+                    return Err(DecodeError::UnexpectedByteError(
+                        self.rest[0],
+                        self.rest.len() as u64,
+                    ));
+                }
+            }
+            if let Some(c) = self.stack.pop() {
+                if let (true, Some(Segment::Key(k))) = (c.object && c.count % 2 == 1, &c.key) {
+                    return Err(DecodeError::UnmatchedKeyError(k.to_owned()));
+                }
+                if c.len == 0 {
+                    self.rest = &self.rest[1..];
+                }
+                self.finish_value();
+                return Ok((c.end, offset));
+            }
+        }
+
+        if self.rest.is_empty() {
+            return Err(DecodeError::EOFError);
+        }
+
+        let tag = self.rest[0];
+        let left = (tag >> 4) as usize;
+        // Keys of objects have to be strings, while keys of maps can be any value except a container.
+        let (key, object_key) = match self.stack.last() {
+            Some(c) if c.end != Event::EndArray && c.count % 2 == 0 => (true, c.object),
+            _ => (false, false),
+        };
+
+        let container = match tag & 0x0F {
+            constants::ARRAY_START => Some((
+                Event::StartArray,
+                Event::EndArray,
+                constants::ARRAY_END,
+                left,
+            )),
+            constants::DICTIONARY_START => Some((
+                Event::StartObject,
+                Event::EndObject,
+                constants::DICTIONARY_END,
+                left * 2,
+            )),
+            constants::EXTENSION if tag == constants::EXTENSION | constants::EXTENSION_MAP << 4 => {
+                if self.rest.len() < 2 {
+                    return Err(DecodeError::EOFError);
+                }
+                if self.rest[1] & 0x0F != constants::DICTIONARY_START {
+                    return Err(DecodeError::UnexpectedByteError(
+                        self.rest[1],
+                        (self.rest.len() - 1) as u64,
+                    ));
+                }
+                self.rest = &self.rest[1..];
+                Some((
+                    Event::StartMap,
+                    Event::EndMap,
+                    constants::DICTIONARY_END,
+                    (self.rest[0] >> 4) as usize * 2,
+                ))
+            }
+            _ => None,
+        };
+
+        if let Some((start, end, end_tag, len)) = container {
+            if key {
+                return Err(DecodeError::UnexpectedByteError(
+                    tag,
+                    self.rest.len() as u64,
+                ));
+            }
+
+            self.rest = &self.rest[1..];
+            self.stack.push(Container {
+                object: start == Event::StartObject,
+                end,
+                tag: end_tag,
+                len,
+                count: 0,
+                key: None,
+            });
+            return Ok((start, offset));
+        }
+
+        let left = self.rest.len();
+        let (val, rest) = decode_value(self.rest, &self.state)?;
+        let segment = if key { Some(key_segment(&val)) } else { None };
+
+        let event = match val {
+            Value::String(s) if object_key => Event::Key(s),
+            _ if object_key => return Err(DecodeError::InvalidKeyError(val)),
+            _ if key && !val.is_scalar() => return Err(DecodeError::InvalidKeyError(val)),
+            Value::Null => Event::Null,
+            Value::Bit(b) => Event::Bit(b),
+            Value::Boolean(b) => Event::Boolean(b),
+            Value::Int8(i) => Event::Int8(i),
+            Value::Int16(i) => Event::Int16(i),
+            Value::Int32(i) => Event::Int32(i),
+            Value::Int64(i) => Event::Int64(i),
+            Value::Float(f) => Event::Float(f),
+            Value::Double(d) => Event::Double(d),
+            Value::String(s) => Event::String(s),
+            _ => return Err(DecodeError::UnexpectedByteError(tag, left as u64)),
+        };
+
+        self.rest = rest;
+        self.finish_value();
+        if let (Some(c), Some(s)) = (self.stack.last_mut(), segment) {
+            c.key = Some(s);
+        }
+
+        Ok((event, offset))
+    }
+
+    // Whether the next event is the end of the innermost container.
+    fn at_end(&self) -> bool {
+        match self.stack.last() {
+            Some(c) if c.len == 0 => !self.rest.is_empty() && self.rest[0] == c.tag,
+            Some(c) => c.count == c.len,
+            None => false,
+        }
+    }
+
+    // Counts a finished value towards the container it is in, the data is done once the outermost value is.
+    fn finish_value(&mut self) {
+        match self.stack.last_mut() {
+            Some(c) => c.count += 1,
+            None => self.done = true,
+        }
+    }
+}
+
+impl<'a> Iterator for EventReader<'a> {
+    type Item = Result<(Event, usize), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read();
+        if result.is_err() {
+            self.done = true;
+        }
+
+        Some(result)
+    }
+}
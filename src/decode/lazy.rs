@@ -0,0 +1,273 @@
+//! Decoding only the parts of EMP Bytecode which are used
+
+use crate::constants;
+use crate::decode::{
+    at, decode_envelope, decode_header, decode_value, located, skip, within, State,
+};
+use crate::errors::DecodeError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::vec::Vec;
+
+// The kind of container a `LazyValue` is, which decides what its keys can be.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Array,
+    Object,
+    Map,
+}
+
+/// A value inside of EMP bytecode which is only decoded when it is used.
+///
+/// Indexing into a container with `LazyValue::get` or `LazyValue::index`
+/// finds where each of its children starts the first time, without decoding
+/// them, and keeps that around so later lookups are quick. The children are
+/// `LazyValue`s as well, so `get("player")` followed by `get("inventory")`
+/// only decodes the keys along the way. `LazyValue::value` decodes the whole
+/// value and keeps it as well.
+///
+/// Keys are checked the same as by `emp::decode::decode` when the children
+/// are found, and a key which appears more than once gives the last of its
+/// values.
+pub struct LazyValue<'a> {
+    // All of the data the value is in, for working out where errors are.
+    data: &'a [u8],
+    // The bytes starting at this value, which may carry on past the end of it.
+    bytes: &'a [u8],
+    path: Path,
+    state: Rc<State>,
+    children: OnceCell<Vec<(Option<Value>, LazyValue<'a>)>>,
+    value: OnceCell<Value>,
+}
+
+impl<'a> LazyValue<'a> {
+    /// Creates a lazy value over EMP bytecode, only reading the header of the
+    /// data right away.
+    pub fn new(bytes: &'a [u8]) -> Result<LazyValue<'a>, DecodeError> {
+        let mut state = State::default();
+        let data = bytes;
+        let header = decode_envelope(data).and_then(|b| decode_header(b, &mut state));
+        let bytes = located(data, header)?;
+
+        if bytes.is_empty() {
+            return located(data, Err(DecodeError::EOFError));
+        }
+
+        Ok(LazyValue::child(
+            data,
+            bytes,
+            Path::default(),
+            Rc::new(state),
+        ))
+    }
+
+    fn child(data: &'a [u8], bytes: &'a [u8], path: Path, state: Rc<State>) -> LazyValue<'a> {
+        LazyValue {
+            data,
+            bytes,
+            path,
+            state,
+            children: OnceCell::new(),
+            value: OnceCell::new(),
+        }
+    }
+
+    /// Returns the value of the field `key` if this is an object, or the value
+    /// for the string key `key` if this is a map.
+    pub fn get(&self, key: &str) -> Result<Option<&LazyValue<'a>>, DecodeError> {
+        self.lookup(&Segment::Key(key.to_owned()))
+    }
+
+    /// Returns the value at `index` if this is an array, or the value for the
+    /// integer key `index` if this is a map.
+    pub fn index(&self, index: usize) -> Result<Option<&LazyValue<'a>>, DecodeError> {
+        self.lookup(&Segment::Index(index))
+    }
+
+    /// Returns the child `segment` points to, if there is one.
+    pub fn lookup(&self, segment: &Segment) -> Result<Option<&LazyValue<'a>>, DecodeError> {
+        let children = self.children()?;
+
+        if self.bytes[0] & 0x0F == constants::ARRAY_START {
+            return match segment {
+                Segment::Index(i) => Ok(children.get(*i).map(|(_, c)| c)),
+                Segment::Key(_) => Ok(None),
+            };
+        }
+
+        // A key which appears more than once in a map is the last of them, the same as in an object.
+        for (key, child) in children.iter().rev() {
+            let found = match (key, segment) {
+                (Some(Value::String(s)), Segment::Key(k)) => s == k,
+                (Some(Value::Int8(n)), Segment::Index(i)) => *n as i64 == *i as i64,
+                (Some(Value::Int16(n)), Segment::Index(i)) => *n as i64 == *i as i64,
+                (Some(Value::Int32(n)), Segment::Index(i)) => *n as i64 == *i as i64,
+                (Some(Value::Int64(n)), Segment::Index(i)) => *n == *i as i64,
+                _ => false,
+            };
+            if found {
+                return Ok(Some(child));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the number of values in an array, or the number of pairs in
+    /// an object or map. Every other value has a length of 0.
+    pub fn len(&self) -> Result<usize, DecodeError> {
+        Ok(self.children()?.len())
+    }
+
+    /// Returns whether this is an empty container, or not a container at all.
+    pub fn is_empty(&self) -> Result<bool, DecodeError> {
+        Ok(self.children()?.len() == 0)
+    }
+
+    /// Returns the keys of an object or map, in the order they are stored.
+    pub fn keys(&self) -> Result<Vec<&Value>, DecodeError> {
+        Ok(self
+            .children()?
+            .iter()
+            .filter_map(|(k, _)| k.as_ref())
+            .collect())
+    }
+
+    /// Decodes the whole value, keeping it so it is only decoded once.
+    pub fn value(&self) -> Result<&Value, DecodeError> {
+        if let Some(v) = self.value.get() {
+            return Ok(v);
+        }
+
+        let (val, _) = self.locate(decode_value(self.bytes, &self.state))?;
+        Ok(self.value.get_or_init(|| val))
+    }
+
+    // Adds where in the data an error from inside of this value happened to it.
+    fn locate<T>(&self, result: Result<T, DecodeError>) -> Result<T, DecodeError> {
+        match result {
+            Ok(val) => Ok(val),
+            Err(e) => {
+                let e = at(e, self.bytes.len() as u64, Path::default());
+                located(self.data, Err(within(e, &self.path.segments)))
+            }
+        }
+    }
+
+    // Returns where every child of a container starts, finding them the first time they are needed.
+    fn children(&self) -> Result<&Vec<(Option<Value>, LazyValue<'a>)>, DecodeError> {
+        if let Some(c) = self.children.get() {
+            return Ok(c);
+        }
+
+        let children = self.locate(self.find_children())?;
+        Ok(self.children.get_or_init(|| children))
+    }
+
+    fn find_children(&self) -> Result<Vec<(Option<Value>, LazyValue<'a>)>, DecodeError> {
+        let tag = self.bytes[0];
+        let mut children = vec![];
+
+        // Maps have their dictionary identifier after the extension byte, so their children start one byte later.
+        let (end, kind, start) = match tag & 0x0F {
+            constants::ARRAY_START => (constants::ARRAY_END, Kind::Array, 1),
+            constants::DICTIONARY_START => (constants::DICTIONARY_END, Kind::Object, 1),
+            constants::EXTENSION if tag == constants::EXTENSION | constants::EXTENSION_MAP << 4 => {
+                if self.bytes.len() < 2 {
+                    return Err(DecodeError::EOFError);
+                }
+                if self.bytes[1] & 0x0F != constants::DICTIONARY_START {
+                    return Err(DecodeError::UnexpectedByteError(
+                        self.bytes[1],
+                        (self.bytes.len() - 1) as u64,
+                    ));
+                }
+                (constants::DICTIONARY_END, Kind::Map, 2)
+            }
+            _ => return Ok(children),
+        };
+
+        let left = (self.bytes[start - 1] >> 4) as usize;
+        let mut rest = &self.bytes[start..];
+        let mut count = 0;
+        // Where each field of an object is in `children`, so a field with the same key as an earlier one replaces
+        // it, the same as when the object is decoded.
+        let mut fields: HashMap<String, usize> = HashMap::new();
+
+        while left == 0 || count < left {
+            if rest.is_empty() {
+                return Err(DecodeError::EOFError);
+            }
+            if left == 0 && rest[0] == end {
+                break;
+            }
+
+            let mut key = None;
+            let mut segment = Segment::Index(count);
+            if kind != Kind::Array {
+                let (k, r) = match self.decode_key(rest, kind) {
+                    Ok(k) => k,
+                    Err(e) => return Err(at(e, rest.len() as u64, Path::default())),
+                };
+                if r.is_empty() {
+                    return Err(DecodeError::EOFError);
+                }
+                if let (Value::String(s), Kind::Object, 0) = (&k, kind, left) {
+                    if r[0] == end {
+                        let e = DecodeError::UnmatchedKeyError(s.to_owned());
+                        return Err(at(e, r.len() as u64, Path::default()));
+                    }
+                }
+                segment = key_segment(&k);
+                key = Some(k);
+                rest = r;
+            }
+
+            let next = match skip::skip_value(rest) {
+                Ok(r) => r,
+                Err(e) => {
+                    let e = at(e, rest.len() as u64, Path::default());
+                    return Err(within(e, &[segment]));
+                }
+            };
+
+            let path = self.path.join(segment);
+            let child = LazyValue::child(self.data, rest, path, self.state.clone());
+            let field = match &key {
+                Some(Value::String(s)) if kind == Kind::Object => fields.get(s).copied(),
+                _ => None,
+            };
+            match field {
+                Some(i) => children[i] = (key, child),
+                None => {
+                    if let (Some(Value::String(s)), Kind::Object) = (&key, kind) {
+                        fields.insert(s.to_owned(), children.len());
+                    }
+                    children.push((key, child));
+                }
+            }
+            rest = next;
+            count += 1;
+        }
+
+        Ok(children)
+    }
+
+    // Decodes the key of the next pair, which has to be a string in an object and a scalar in a map.
+    fn decode_key<'b>(
+        &self,
+        bytes: &'b [u8],
+        kind: Kind,
+    ) -> Result<(Value, &'b [u8]), DecodeError> {
+        let (key, rest) = decode_value(bytes, &self.state)?;
+
+        match (&key, kind) {
+            (Value::String(_), _) => Ok((key, rest)),
+            (_, Kind::Map) if key.is_scalar() => Ok((key, rest)),
+            _ => Err(DecodeError::InvalidKeyError(key)),
+        }
+    }
+}
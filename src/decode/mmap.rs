@@ -0,0 +1,64 @@
+//! Decoding EMP Bytecode straight from a memory-mapped file
+
+use crate::decode::borrowed::{decode_borrowed, ValueRef};
+use crate::decode::lazy::LazyValue;
+use crate::errors::DecodeError;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+/// An EMP file mapped into memory.
+///
+/// The operating system only loads the pages of the file which are read, so
+/// only the parts of a large file which are actually used take up memory.
+/// `MappedFile::lazy` and `MappedFile::borrowed` decode the file without
+/// copying it, while `MappedFile::bytes` gives the whole file as a `&[u8]`
+/// for any of the other decoders.
+///
+/// The file should not be changed while it is mapped, as the bytes would
+/// change underneath any values borrowed from it.
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    /// Maps the file at `path` into memory.
+    ///
+    /// If the file can not be opened or mapped this returns a
+    /// `emp::errors::DecodeError::IOError`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedFile, DecodeError> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(DecodeError::IOError(e)),
+        };
+
+        // Mapping is only unsafe if the file is changed while it is mapped, which is documented above.
+        match unsafe { Mmap::map(&file) } {
+            Ok(map) => Ok(MappedFile { map }),
+            Err(e) => Err(DecodeError::IOError(e)),
+        }
+    }
+
+    /// Returns the bytes of the file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Returns the file as an `emp::decode::lazy::LazyValue`, which only
+    /// reads the parts of the file which are used.
+    pub fn lazy(&self) -> Result<LazyValue<'_>, DecodeError> {
+        LazyValue::new(&self.map)
+    }
+
+    /// Decodes the file into an `emp::decode::borrowed::ValueRef` which
+    /// borrows every string from the file instead of copying it.
+    pub fn borrowed(&self) -> Result<ValueRef<'_>, DecodeError> {
+        decode_borrowed(&self.map)
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.map
+    }
+}
@@ -0,0 +1,218 @@
+//! Decoding large EMP Bytecode on several threads
+
+use crate::constants;
+use crate::decode::{
+    decode_envelope, decode_header, decode_nested, decode_value, located, skip, DecodeLimits, State,
+};
+use crate::errors::DecodeError;
+use crate::value::Value;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::vec::Vec;
+
+// Containers taking up fewer bytes than this are decoded on the thread that reaches them.
+const MIN_BYTES: usize = 16 * 1024;
+
+// Containers nested deeper than this are decoded on the thread that reaches them, so the spans found for the data
+// are never nested deeper than this however deeply the data is, and can be dropped without overflowing the stack.
+const MAX_SPLIT_DEPTH: usize = 64;
+
+/// Decodes a slice of `u8`s into a `(emp::value::Value, &[u8])` tuple,
+/// splitting large containers between threads.
+///
+/// The children of every array, object and map taking up enough bytes are
+/// found in a single pass over the data, then decoded on the rayon thread
+/// pool, so the result is the same as `emp::decode::decode`. If the data is
+/// invalid it is decoded again in order, which returns the same
+/// `emp::errors::DecodeError` as `decode` would. Like `decode` it has no
+/// limits, data from untrusted sources should be decoded with
+/// `decode_parallel_with_limits` instead.
+pub fn decode_parallel(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    decode_parallel_with_limits(bytes, &DecodeLimits::none())
+}
+
+/// Decodes a slice of `u8`s the same way as
+/// `emp::decode::parallel::decode_parallel`, stopping with an
+/// `emp::errors::DecodeError` as soon as the data goes over any of the
+/// `emp::decode::DecodeLimits`. Every thread counts towards the same limits.
+pub fn decode_parallel_with_limits<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<(Value, &'a [u8]), DecodeError> {
+    located(bytes, decode_data(bytes, limits))
+}
+
+fn decode_data<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<(Value, &'a [u8]), DecodeError> {
+    let mut state = State::new(limits.clone());
+
+    let bytes = decode_envelope(bytes)?;
+    let bytes = decode_header(bytes, &mut state)?;
+    let usage = state.usage();
+
+    let split = spans(bytes).and_then(|(span, rest)| {
+        let val = decode_split(bytes, &span, &state, 0)?;
+        Ok((val, rest))
+    });
+
+    match split {
+        Ok(v) => Ok(v),
+        Err(_) => {
+            state.reset(usage);
+            decode_value(bytes, &state)
+        }
+    }
+}
+
+// Where a value is in the data, along with where each of its children are (keys included) if it is a container large
+// enough to be split between threads.
+struct Span {
+    start: usize,
+    end: usize,
+    children: Vec<Span>,
+}
+
+// Finds where the value at the start of `bytes` and its children are in a single pass over it, returning the bytes
+// after it. Containers are tracked on a stack of their own rather than by recursing, the same as
+// `skip::skip_value`.
+fn spans(bytes: &[u8]) -> Result<(Span, &[u8]), DecodeError> {
+    // How many values are left in each open container, `None` when it ends with the closing tag next to it.
+    let mut open: Vec<(Option<usize>, u8, Span)> = vec![];
+    let mut rest = bytes;
+
+    loop {
+        let closed = match open.last() {
+            Some((Some(0), _, _)) => true,
+            Some((None, end, _)) => {
+                if rest.is_empty() {
+                    return Err(DecodeError::EOFError);
+                }
+                if rest[0] == *end {
+                    rest = &rest[1..];
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        };
+
+        let span = if closed {
+            let (_, _, mut span) = open.pop().unwrap();
+            span.end = bytes.len() - rest.len();
+            // Only large containers are split, so the children of smaller ones are not needed.
+            if span.end - span.start < MIN_BYTES {
+                span.children = vec![];
+            }
+            span
+        } else {
+            if let Some((Some(left), _, _)) = open.last_mut() {
+                *left -= 1;
+            }
+
+            let start = bytes.len() - rest.len();
+            let (container, r) = skip::skip_scalar(rest)?;
+            rest = r;
+
+            let span = Span {
+                start,
+                end: bytes.len() - rest.len(),
+                children: vec![],
+            };
+            match container {
+                Some((0, end)) => {
+                    open.push((None, end, span));
+                    continue;
+                }
+                Some((len, end)) => {
+                    open.push((Some(len), end, span));
+                    continue;
+                }
+                None => span,
+            }
+        };
+
+        let depth = open.len();
+        match open.last_mut() {
+            Some((_, _, parent)) if depth <= MAX_SPLIT_DEPTH => parent.children.push(span),
+            Some(_) => {}
+            None => return Ok((span, rest)),
+        }
+    }
+}
+
+// Decodes the value `span` points to in `bytes`, which is inside of `depth` containers, decoding the children of
+// large containers in parallel.
+fn decode_split(
+    bytes: &[u8],
+    span: &Span,
+    state: &State,
+    depth: usize,
+) -> Result<Value, DecodeError> {
+    let value = &bytes[span.start..span.end];
+    if span.children.is_empty() {
+        return Ok(decode_nested(value, state, depth)?.0);
+    }
+
+    if depth >= state.limits.depth {
+        return Err(DecodeError::DepthLimitError(state.limits.depth));
+    }
+    state.value()?;
+
+    let tag = value[0];
+    if tag & 0x0F == constants::ARRAY_START {
+        let values = span
+            .children
+            .par_iter()
+            .map(|c| decode_split(bytes, c, state, depth + 1))
+            .collect::<Result<Vec<Value>, DecodeError>>()?;
+        return Ok(Value::Array(values));
+    }
+
+    // A container ended by its closing tag can hold a key without a value.
+    if span.children.len() % 2 == 1 {
+        return Err(DecodeError::UnexpectedByteError(
+            value[value.len() - 1],
+            (bytes.len() - span.end + 1) as u64,
+        ));
+    }
+
+    // Keys are decoded before any of the values, as they have to be checked before their values.
+    let mut keys = Vec::with_capacity(span.children.len() / 2);
+    for key in span.children.iter().step_by(2) {
+        keys.push(decode_value(&bytes[key.start..key.end], state)?.0);
+    }
+
+    let values = span
+        .children
+        .par_iter()
+        .skip(1)
+        .step_by(2)
+        .map(|c| decode_split(bytes, c, state, depth + 1))
+        .collect::<Result<Vec<Value>, DecodeError>>()?;
+
+    if tag & 0x0F == constants::DICTIONARY_START {
+        let mut object = HashMap::with_capacity(keys.len());
+        for (key, val) in keys.into_iter().zip(values) {
+            match key {
+                Value::String(s) => {
+                    object.insert(s, val);
+                }
+                _ => return Err(DecodeError::InvalidKeyError(key)),
+            }
+        }
+        return Ok(Value::Object(object));
+    }
+
+    let mut map = Vec::with_capacity(keys.len());
+    for (key, val) in keys.into_iter().zip(values) {
+        if !key.is_scalar() {
+            return Err(DecodeError::InvalidKeyError(key));
+        }
+        map.push((key, val));
+    }
+
+    Ok(Value::Map(map))
+}
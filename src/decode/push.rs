@@ -0,0 +1,542 @@
+//! Decoding Values from data which arrives a chunk at a time
+
+use crate::constants;
+use crate::decode::{at, decode_int32, decode_value, located, skip, DecodeLimits, State};
+use crate::errors::DecodeError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::vec::Vec;
+
+// The part of the data the decoder is waiting for, everything before `Data` is the header.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Phase {
+    #[default]
+    Envelope,
+    Features,
+    Metadata,
+    Schema,
+    StringTable,
+    Data,
+}
+
+// A value which has been started but needs more values before it is complete, the `len` of a container is 0 when it
+// ends with a closing tag. `start` is the offset of the first byte of a container.
+enum Frame {
+    Array {
+        items: Vec<Value>,
+        len: usize,
+        start: u64,
+    },
+    Object {
+        object: HashMap<String, Value>,
+        key: Option<String>,
+        count: usize,
+        len: usize,
+        start: u64,
+    },
+    Map {
+        pairs: Vec<(Value, Value)>,
+        key: Option<Value>,
+        len: usize,
+        start: u64,
+    },
+    StringReference(u64),
+    StringTable(u64, u8),
+    Discard,
+}
+
+impl Frame {
+    fn is_container(&self) -> bool {
+        matches!(
+            self,
+            Frame::Array { .. } | Frame::Object { .. } | Frame::Map { .. }
+        )
+    }
+
+    // Turns a finished container into its value, along with the offset it starts at.
+    fn finish(self) -> Option<(Value, u64)> {
+        match self {
+            Frame::Array { items, start, .. } => Some((Value::Array(items), start)),
+            Frame::Object { object, start, .. } => Some((Value::Object(object), start)),
+            Frame::Map { pairs, start, .. } => Some((Value::Map(pairs), start)),
+            _ => None,
+        }
+    }
+}
+
+enum Step {
+    NeedData,
+    Continue,
+    Done(Value),
+}
+
+/// Decodes EMP bytecode which arrives in chunks of any size, such as from a
+/// network connection.
+///
+/// Chunks are passed in with `PushDecoder::feed` and values are taken out
+/// with `PushDecoder::next_value`. The decoder keeps track of how far it has
+/// got, so each byte is only looked at once no matter how the data is split
+/// up. Once a value is complete the decoder starts on the next one, so
+/// several values sent one after another can be decoded.
+///
+/// After an error the decoder should be thrown away, as it has no way to
+/// find the start of the next value.
+#[derive(Default)]
+pub struct PushDecoder {
+    buffer: Vec<u8>,
+    position: usize,
+    // How far into the bytes after `position` a closing string tag has already been searched for.
+    scanned: usize,
+    offset: u64,
+    phase: Phase,
+    features: u8,
+    state: State,
+    stack: Vec<Frame>,
+    // How many of the frames on `stack` are containers.
+    depth: usize,
+}
+
+impl PushDecoder {
+    /// Creates a decoder with no data.
+    pub fn new() -> PushDecoder {
+        PushDecoder::default()
+    }
+
+    /// Creates a decoder with no data which stops with an
+    /// `emp::errors::DecodeError` as soon as a value goes over any of the
+    /// `limits`. Each value decoded has the whole of the limits to itself.
+    pub fn with_limits(limits: DecodeLimits) -> PushDecoder {
+        PushDecoder {
+            state: State::new(limits),
+            ..PushDecoder::default()
+        }
+    }
+
+    /// Adds the next chunk of data to the decoder.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        if self.position != 0 {
+            self.buffer.drain(..self.position);
+            self.position = 0;
+        }
+
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the number of bytes which have been decoded so far.
+    pub fn position(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the number of bytes which have been fed in but not used yet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.position
+    }
+
+    /// Decodes as much of the data fed in so far as possible, returning the
+    /// next complete `emp::value::Value`.
+    ///
+    /// If the data so far is valid but the value is not complete yet this
+    /// returns `None`, and the value can be finished by calling this again
+    /// after feeding in more data. If the data is invalid this returns a
+    /// `emp::errors::DecodeError`.
+    pub fn next_value(&mut self) -> Result<Option<Value>, DecodeError> {
+        match self.decode_next() {
+            Ok(val) => Ok(val),
+            Err(e) => Err(at(e, self.offset, self.path())),
+        }
+    }
+
+    // The path to the value being decoded, which stops at a container waiting for a key.
+    fn path(&self) -> Path {
+        let mut segments = vec![];
+
+        for frame in &self.stack {
+            let segment = match frame {
+                Frame::Array { items, .. } => Segment::Index(items.len()),
+                Frame::Object { key: Some(k), .. } => Segment::Key(k.to_owned()),
+                Frame::Map { key: Some(k), .. } => key_segment(k),
+                Frame::Object { .. } | Frame::Map { .. } => break,
+                _ => continue,
+            };
+            segments.push(segment);
+        }
+
+        Path { segments }
+    }
+
+    fn decode_next(&mut self) -> Result<Option<Value>, DecodeError> {
+        loop {
+            if self.position == self.buffer.len() {
+                return Ok(None);
+            }
+
+            if self.stack.is_empty() && self.phase != Phase::Data {
+                if !self.step_header()? {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            match self.step()? {
+                Step::NeedData => return Ok(None),
+                Step::Continue => {}
+                Step::Done(val) => {
+                    self.phase = Phase::Envelope;
+                    self.features = 0;
+                    self.state = State::new(self.state.limits.clone());
+                    return Ok(Some(val));
+                }
+            }
+        }
+    }
+
+    // Reads the next part of the header, returning false if more data is needed.
+    fn step_header(&mut self) -> Result<bool, DecodeError> {
+        let bytes = &self.buffer[self.position..];
+
+        match self.phase {
+            Phase::Envelope => {
+                if bytes[0] == constants::EXTENSION | constants::EXTENSION_ENCRYPTED << 4 {
+                    return Err(DecodeError::EncryptedError);
+                }
+                if bytes[0] == constants::EXTENSION | constants::EXTENSION_SIGNED << 4 {
+                    if bytes.len() < 65 {
+                        return Ok(false);
+                    }
+                    self.consume(65);
+                    return Ok(true);
+                }
+                self.phase = Phase::Features;
+            }
+            Phase::Features => {
+                if bytes[0] == constants::EXTENSION | constants::EXTENSION_FEATURES << 4 {
+                    if bytes.len() < 2 {
+                        return Ok(false);
+                    }
+                    if bytes[1] & !constants::FEATURES != 0 {
+                        return Err(DecodeError::UnsupportedFeatureError(bytes[1]));
+                    }
+                    self.features = bytes[1];
+                    self.consume(2);
+                }
+                self.phase = Phase::Metadata;
+            }
+            Phase::Metadata => {
+                if self.features & constants::FEATURE_METADATA != 0 {
+                    self.decode_section(constants::EXTENSION_METADATA)?;
+                    self.stack.push(Frame::Discard);
+                }
+                self.phase = Phase::Schema;
+            }
+            Phase::Schema => {
+                if self.features & constants::FEATURE_SCHEMA != 0 {
+                    self.decode_section(constants::EXTENSION_SCHEMA)?;
+                    self.stack.push(Frame::Discard);
+                }
+                self.phase = Phase::StringTable;
+            }
+            Phase::StringTable => {
+                if self.features & constants::FEATURE_STRING_TABLE != 0
+                    || bytes[0] == constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4
+                {
+                    self.decode_section(constants::EXTENSION_STRING_TABLE)?;
+                    self.stack.push(Frame::StringTable(self.offset, 0));
+                }
+                self.phase = Phase::Data;
+            }
+            Phase::Data => {}
+        }
+
+        Ok(true)
+    }
+
+    // Checks that the next byte is the extension byte for the header section `id` and skips over it.
+    fn decode_section(&mut self, id: u8) -> Result<(), DecodeError> {
+        let tag = self.buffer[self.position];
+        if tag != constants::EXTENSION | id << 4 {
+            return Err(DecodeError::UnexpectedByteError(tag, self.offset));
+        }
+
+        self.consume(1);
+        Ok(())
+    }
+
+    // Reads the next identifier, along with anything stored directly after it.
+    fn step(&mut self) -> Result<Step, DecodeError> {
+        let tag = self.buffer[self.position];
+
+        // The first byte of the string table is where every decoder reports a table which does not hold only strings.
+        if let Some(Frame::StringTable(offset, first)) = self.stack.last_mut() {
+            if *offset == self.offset {
+                *first = tag;
+            }
+        }
+
+        // The index of a string reference is always an integer, so references can not be chained.
+        if let Some(Frame::StringReference(_)) = self.stack.last() {
+            if tag & 0x0F != constants::INT_32 {
+                return Err(DecodeError::UnexpectedByteError(tag, self.offset));
+            }
+        }
+
+        // A map waiting for the value of a key reads the closing tag as that value, which is an error.
+        let closes = match self.stack.last_mut() {
+            Some(Frame::Array { len: 0, .. }) => tag == constants::ARRAY_END,
+            Some(Frame::Object { key, len: 0, .. }) if tag == constants::DICTIONARY_END => {
+                if let Some(k) = key.take() {
+                    return Err(DecodeError::UnmatchedKeyError(k));
+                }
+                true
+            }
+            Some(Frame::Map {
+                key: None, len: 0, ..
+            }) => tag == constants::DICTIONARY_END,
+            _ => false,
+        };
+
+        if closes {
+            self.consume(1);
+            self.depth -= 1;
+            return match self.stack.pop().and_then(Frame::finish) {
+                Some((val, start)) => self.complete(val, start),
+                None => self.complete(Value::Null, self.offset),
+            };
+        }
+
+        let len = match self.token_length()? {
+            Some(l) => l,
+            None => return Ok(Step::NeedData),
+        };
+
+        let left = (tag >> 4) as usize;
+        let frame = match tag & 0x0F {
+            constants::ARRAY_START => Some(Frame::Array {
+                items: vec![],
+                len: left,
+                start: self.offset,
+            }),
+            constants::DICTIONARY_START => Some(Frame::Object {
+                object: HashMap::new(),
+                key: None,
+                count: 0,
+                len: left,
+                start: self.offset,
+            }),
+            constants::STRING_REFERENCE if left == 0 => Some(Frame::StringReference(self.offset)),
+            constants::EXTENSION => {
+                let start = self.buffer[self.position + 1];
+                if start & 0x0F != constants::DICTIONARY_START {
+                    return Err(DecodeError::UnexpectedByteError(start, self.offset + 1));
+                }
+                Some(Frame::Map {
+                    pairs: vec![],
+                    key: None,
+                    len: (start >> 4) as usize,
+                    start: self.offset,
+                })
+            }
+            _ => None,
+        };
+
+        if let Some(f) = frame {
+            let depth = self.state.limits.depth;
+            if f.is_container() {
+                if self.depth >= depth {
+                    return Err(DecodeError::DepthLimitError(depth));
+                }
+                self.depth += 1;
+            }
+            self.state.value()?;
+            self.consume(len);
+            self.stack.push(f);
+            return Ok(Step::Continue);
+        }
+
+        let token = &self.buffer[self.position..self.position + len];
+        let result = match self.stack.last() {
+            // The index of a string reference is part of the reference, not a value of its own.
+            Some(Frame::StringReference(_)) => decode_int32(token),
+            _ => decode_value(token, &self.state),
+        };
+        let val = match located(token, result) {
+            Ok((val, _)) => val,
+            // The error is counted from the start of the token, which has to be moved to where it is in the data.
+            Err(DecodeError::LocatedError(e, l)) => match *e {
+                DecodeError::UnexpectedByteError(b, _) => {
+                    return Err(DecodeError::UnexpectedByteError(b, self.offset + l.offset))
+                }
+                e => return Err(e),
+            },
+            Err(e) => return Err(e),
+        };
+        let start = self.offset;
+        self.consume(len);
+
+        self.complete(val, start)
+    }
+
+    // Returns how many bytes the identifier at the start of the buffer and anything stored directly after it take up,
+    // or `None` if they have not all been fed in yet.
+    fn token_length(&mut self) -> Result<Option<usize>, DecodeError> {
+        let bytes = &self.buffer[self.position..];
+        let tag = bytes[0];
+        let left = (tag >> 4) as usize;
+
+        let len = match skip::number_length(tag) {
+            Some(l) => l,
+            None => match tag & 0x0F {
+                constants::STRING if left != 0 => left + 1,
+                constants::STRING => {
+                    let from = self.scanned.max(1);
+                    match bytes[from..].iter().position(|b| *b == constants::STRING) {
+                        Some(end) => from + end + 1,
+                        // Everything after the tag is part of the string so far, which can't be allowed to
+                        // fill up memory while waiting for the closing tag.
+                        None if bytes.len() - 1 > self.state.limits.string_length => {
+                            return Err(DecodeError::StringLengthLimitError(
+                                self.state.limits.string_length,
+                            ))
+                        }
+                        None => {
+                            self.scanned = bytes.len();
+                            return Ok(None);
+                        }
+                    }
+                }
+                constants::EXTENSION
+                    if tag == constants::EXTENSION | constants::EXTENSION_MAP << 4 =>
+                {
+                    2
+                }
+                constants::BIT
+                | constants::BOOLEAN
+                | constants::NULL
+                | constants::STRING_REFERENCE
+                | constants::ARRAY_START
+                | constants::DICTIONARY_START => 1,
+                _ => return Err(DecodeError::UnexpectedByteError(tag, self.offset)),
+            },
+        };
+
+        if bytes.len() < len {
+            return Ok(None);
+        }
+        Ok(Some(len))
+    }
+
+    // Places a finished value, which starts at the offset `start`, into the container it belongs to, finishing that
+    // container as well if it is full.
+    fn complete(&mut self, mut val: Value, mut start: u64) -> Result<Step, DecodeError> {
+        loop {
+            match self.stack.last() {
+                None => return Ok(Step::Done(val)),
+                Some(Frame::StringReference(offset)) => {
+                    let offset = *offset;
+                    start = offset;
+                    self.stack.pop();
+                    val = match val {
+                        Value::Int32(i) if i >= 0 => match self.state.strings.get(i as usize) {
+                            Some(s) => {
+                                self.state.string(s.len())?;
+                                Value::String(s.to_owned())
+                            }
+                            None => return Err(DecodeError::StringReferenceError(i as u64)),
+                        },
+                        _ => {
+                            return Err(DecodeError::UnexpectedByteError(
+                                constants::STRING_REFERENCE,
+                                offset,
+                            ))
+                        }
+                    };
+                    continue;
+                }
+                Some(Frame::StringTable(offset, first)) => {
+                    let error = DecodeError::UnexpectedByteError(*first, *offset);
+                    self.stack.pop();
+                    let table = match val {
+                        Value::Array(a) => a,
+                        _ => return Err(error),
+                    };
+                    for s in table {
+                        match s {
+                            Value::String(s) => self.state.strings.push(s),
+                            _ => return Err(error),
+                        }
+                    }
+                    return Ok(Step::Continue);
+                }
+                Some(Frame::Discard) => {
+                    self.stack.pop();
+                    return Ok(Step::Continue);
+                }
+                _ => {}
+            }
+
+            let full = match self.stack.last_mut() {
+                Some(Frame::Array { items, len, .. }) => {
+                    items.push(val);
+                    *len != 0 && items.len() == *len
+                }
+                Some(Frame::Object {
+                    object,
+                    key,
+                    count,
+                    len,
+                    ..
+                }) => match key.take() {
+                    None => match val {
+                        Value::String(s) => {
+                            *key = Some(s);
+                            false
+                        }
+                        _ => return Err(self.invalid_key(val, start)),
+                    },
+                    Some(k) => {
+                        object.insert(k, val);
+                        *count += 1;
+                        *len != 0 && *count == *len
+                    }
+                },
+                Some(Frame::Map {
+                    pairs, key, len, ..
+                }) => match key.take() {
+                    None => {
+                        if !val.is_scalar() {
+                            return Err(self.invalid_key(val, start));
+                        }
+                        *key = Some(val);
+                        false
+                    }
+                    Some(k) => {
+                        pairs.push((k, val));
+                        *len != 0 && pairs.len() == *len
+                    }
+                },
+                _ => false,
+            };
+
+            if !full {
+                return Ok(Step::Continue);
+            }
+
+            self.depth -= 1;
+            (val, start) = match self.stack.pop().and_then(Frame::finish) {
+                Some(v) => v,
+                None => return Ok(Step::Continue),
+            };
+        }
+    }
+
+    // A key which is not allowed, placed at its first byte even if it is a container which has been decoded.
+    fn invalid_key(&self, key: Value, start: u64) -> DecodeError {
+        at(DecodeError::InvalidKeyError(key), start, self.path())
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.position += len;
+        self.offset += len as u64;
+        self.scanned = 0;
+    }
+}
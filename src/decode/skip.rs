@@ -0,0 +1,300 @@
+//! Walking over EMP bytecode without decoding it
+
+use crate::constants;
+use crate::decode::{at, decode_header, decode_value, located, within, State};
+use crate::errors::DecodeError;
+use crate::value::path::{Path, Segment};
+use crate::value::Value;
+
+/// Returns the number of bytes a number with the identifier `tag` takes up,
+/// including the identifier itself.
+pub(crate) fn number_length(tag: u8) -> Option<usize> {
+    let width: usize = match tag & 0x0F {
+        constants::INT_64 | constants::DOUBLE => 8,
+        constants::INT_32 | constants::FLOAT => 4,
+        constants::INT_16 => 2,
+        constants::INT_8 => return Some(if tag >> 4 == 0 { 2 } else { 1 }),
+        _ => return None,
+    };
+
+    width
+        .checked_sub((tag >> 4 & 0b0111) as usize)
+        .map(|len| len + 1)
+}
+
+// How many values a container holds and the byte which closes it.
+type Container = (usize, u8);
+
+/// Skips over the value at the start of `bytes`, returning the bytes after it.
+///
+/// Containers are tracked on a stack of their own rather than by recursing,
+/// so deeply nested data can not overflow the call stack.
+pub(super) fn skip_value(mut bytes: &[u8]) -> Result<&[u8], DecodeError> {
+    // How many values are left in each container being skipped, `None` when it ends with the closing tag next to it.
+    let mut open: Vec<(Option<usize>, u8)> = vec![];
+
+    loop {
+        match open.last() {
+            Some((Some(0), _)) => {
+                open.pop();
+                if open.is_empty() {
+                    return Ok(bytes);
+                }
+                continue;
+            }
+            Some((None, end)) => {
+                if bytes.is_empty() {
+                    return Err(DecodeError::EOFError);
+                }
+                if bytes[0] == *end {
+                    bytes = &bytes[1..];
+                    open.pop();
+                    if open.is_empty() {
+                        return Ok(bytes);
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+
+        if let Some((Some(left), _)) = open.last_mut() {
+            *left -= 1;
+        }
+
+        let (container, rest) = skip_scalar(bytes)?;
+        bytes = rest;
+
+        match container {
+            Some((0, end)) => open.push((None, end)),
+            Some((len, end)) => open.push((Some(len), end)),
+            None if open.is_empty() => return Ok(bytes),
+            None => {}
+        }
+    }
+}
+
+// Skips over a value which is not a container, or the identifier of a container along with how many values it holds
+// (0 when it ends with a closing tag) and the byte which closes it.
+pub(super) fn skip_scalar(bytes: &[u8]) -> Result<(Option<Container>, &[u8]), DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+
+    if let Some(len) = number_length(bytes[0]) {
+        if bytes.len() < len {
+            return Err(DecodeError::EOFError);
+        }
+        return Ok((None, &bytes[len..]));
+    }
+
+    let left = (bytes[0] >> 4) as usize;
+
+    match bytes[0] & 0x0F {
+        constants::BIT | constants::BOOLEAN => Ok((None, &bytes[1..])),
+        constants::NULL if bytes[0] == constants::NULL => Ok((None, &bytes[1..])),
+        constants::STRING_REFERENCE => {
+            if left != 0 {
+                return Ok((None, &bytes[1..]));
+            }
+
+            // Larger indices are stored in an Int32 after the identifier.
+            let rest = &bytes[1..];
+            if rest.is_empty() {
+                return Err(DecodeError::EOFError);
+            }
+            if rest[0] & 0x0F != constants::INT_32 {
+                return Err(DecodeError::UnexpectedByteError(rest[0], rest.len() as u64));
+            }
+            skip_scalar(rest)
+        }
+        constants::STRING => {
+            if left != 0 {
+                if bytes.len() < left + 1 {
+                    return Err(DecodeError::EOFError);
+                }
+                return Ok((None, &bytes[left + 1..]));
+            }
+
+            match bytes[1..].iter().position(|b| *b == constants::STRING) {
+                Some(end) => Ok((None, &bytes[end + 2..])),
+                None => Err(DecodeError::EOFError),
+            }
+        }
+        constants::ARRAY_START => Ok((Some((left, constants::ARRAY_END)), &bytes[1..])),
+        constants::DICTIONARY_START => {
+            Ok((Some((left * 2, constants::DICTIONARY_END)), &bytes[1..]))
+        }
+        constants::EXTENSION
+            if bytes[0] == constants::EXTENSION | constants::EXTENSION_MAP << 4 =>
+        {
+            if bytes.len() < 2 {
+                return Err(DecodeError::EOFError);
+            }
+            if bytes[1] & 0x0F != constants::DICTIONARY_START {
+                return Err(DecodeError::UnexpectedByteError(
+                    bytes[1],
+                    (bytes.len() - 1) as u64,
+                ));
+            }
+            skip_scalar(&bytes[1..])
+        }
+        _ => Err(DecodeError::UnexpectedByteError(
+            bytes[0],
+            bytes.len() as u64,
+        )),
+    }
+}
+
+// Finds the child of the container at the start of `bytes` which `segment` points to.
+fn locate_child<'a>(
+    bytes: &'a [u8],
+    segment: &Segment,
+    state: &State,
+) -> Result<Option<&'a [u8]>, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+
+    match (bytes[0] & 0x0F, segment) {
+        (constants::ARRAY_START, Segment::Index(index)) => {
+            let left = (bytes[0] >> 4) as usize;
+            let mut rest = &bytes[1..];
+
+            if left != 0 && *index >= left {
+                return Ok(None);
+            }
+
+            for _ in 0..*index {
+                if !rest.is_empty() && left == 0 && rest[0] == constants::ARRAY_END {
+                    return Ok(None);
+                }
+                rest = skip_value(rest)?;
+            }
+
+            if !rest.is_empty() && left == 0 && rest[0] == constants::ARRAY_END {
+                return Ok(None);
+            }
+            Ok(Some(rest))
+        }
+        (constants::DICTIONARY_START, Segment::Key(_)) => locate_pair(bytes, segment, true, state),
+        (constants::EXTENSION, _)
+            if bytes[0] == constants::EXTENSION | constants::EXTENSION_MAP << 4 =>
+        {
+            locate_pair(&bytes[1..], segment, false, state)
+        }
+        _ => Ok(None),
+    }
+}
+
+// Finds the value in an object or map with a key matching `segment`, Index segments match integer keys in maps. Every
+// key is checked the same way as when the container is decoded, and a key which appears more than once is the last of
+// them.
+fn locate_pair<'a>(
+    bytes: &'a [u8],
+    segment: &Segment,
+    object: bool,
+    state: &State,
+) -> Result<Option<&'a [u8]>, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+
+    let left = (bytes[0] >> 4) as usize;
+    let mut rest = &bytes[1..];
+    let mut found = None;
+
+    let mut i = 0;
+    while left == 0 || i < left {
+        if rest.is_empty() {
+            return Err(DecodeError::EOFError);
+        }
+        if left == 0 && rest[0] == constants::DICTIONARY_END {
+            break;
+        }
+
+        let (key, value) = decode_value(rest, state)?;
+        let matches = match (&key, segment) {
+            (Value::String(s), Segment::Key(k)) => s == k,
+            (Value::String(_), _) => false,
+            _ if object || !key.is_scalar() => {
+                let e = DecodeError::InvalidKeyError(key);
+                return Err(at(e, rest.len() as u64, Path::default()));
+            }
+            (Value::Int8(n), Segment::Index(i)) => *n as i64 == *i as i64,
+            (Value::Int16(n), Segment::Index(i)) => *n as i64 == *i as i64,
+            (Value::Int32(n), Segment::Index(i)) => *n as i64 == *i as i64,
+            (Value::Int64(n), Segment::Index(i)) => *n == *i as i64,
+            _ => false,
+        };
+
+        if let (Value::String(k), true, 0) = (&key, object, left) {
+            if value.first() == Some(&constants::DICTIONARY_END) {
+                let e = DecodeError::UnmatchedKeyError(k.to_owned());
+                return Err(at(e, value.len() as u64, Path::default()));
+            }
+        }
+        if matches {
+            found = Some(value);
+        }
+
+        rest = skip_value(value)?;
+        i += 1;
+    }
+
+    Ok(found)
+}
+
+/// Finds the value at `path` in the data starting at `bytes`, returning the
+/// bytes starting at that value.
+///
+/// If there is no value at `path` this returns `None`.
+pub(super) fn find<'a>(
+    mut bytes: &'a [u8],
+    path: &Path,
+    state: &State,
+) -> Result<Option<&'a [u8]>, DecodeError> {
+    for (i, segment) in path.segments.iter().enumerate() {
+        bytes = match locate_child(bytes, segment, state) {
+            Ok(Some(c)) => c,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                let e = at(e, bytes.len() as u64, Path::default());
+                return Err(within(e, &path.segments[..i]));
+            }
+        };
+    }
+
+    Ok(Some(bytes))
+}
+
+/// Finds the value at `path` in EMP bytecode without decoding anything else,
+/// returning the offset and length of its bytes.
+///
+/// If there is no value at `path` this returns `None`.
+pub(crate) fn locate(bytes: &[u8], path: &Path) -> Result<Option<(usize, usize)>, DecodeError> {
+    located(bytes, find_span(bytes, path))
+}
+
+fn find_span(bytes: &[u8], path: &Path) -> Result<Option<(usize, usize)>, DecodeError> {
+    let mut state = State::default();
+    let data = decode_header(bytes, &mut state)?;
+
+    let current = match find(data, path, &state)? {
+        Some(c) => c,
+        None => return Ok(None),
+    };
+
+    let rest = match skip_value(current) {
+        Ok(r) => r,
+        Err(e) => {
+            let e = at(e, current.len() as u64, Path::default());
+            return Err(within(e, &path.segments));
+        }
+    };
+
+    Ok(Some((
+        bytes.len() - current.len(),
+        current.len() - rest.len(),
+    )))
+}
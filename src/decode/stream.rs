@@ -0,0 +1,435 @@
+//! Decoding Values straight from a reader
+
+use crate::constants;
+use crate::decode::{at, skip, within, DecodeLimits, State};
+use crate::errors::DecodeError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::vec::Vec;
+
+/// Reads EMP bytecode straight from a `std::io::Read`, such as a file or a
+/// socket, only holding a small buffer of the data in memory at a time.
+///
+/// `Decoder::decode` reads the next `Value`, so several EMP values written
+/// one after another can be read from the same stream. The decoder is also an
+/// `Iterator` over every value in the stream, stopping once the stream ends
+/// or after the first error.
+///
+/// Any errors from the reader are returned as a
+/// `emp::errors::DecodeError::IOError`.
+pub struct Decoder<R: Read> {
+    reader: BufReader<R>,
+    position: u64,
+    limits: DecodeLimits,
+    failed: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    /// Creates a decoder reading from `reader`, using
+    /// `emp::decode::DecodeLimits::default()`.
+    pub fn new(reader: R) -> Decoder<R> {
+        Decoder::with_limits(reader, DecodeLimits::default())
+    }
+
+    /// Creates a decoder reading from `reader` which stops with an
+    /// `emp::errors::DecodeError` as soon as a value goes over any of the
+    /// `limits`. Each value in the stream has the whole of the limits to
+    /// itself.
+    pub fn with_limits(reader: R, limits: DecodeLimits) -> Decoder<R> {
+        Decoder {
+            reader: BufReader::new(reader),
+            position: 0,
+            limits,
+            failed: false,
+        }
+    }
+
+    /// Returns the number of bytes which have been decoded so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Reads the next `emp::value::Value` from the stream.
+    ///
+    /// If the stream ends before the value does this returns a
+    /// `emp::errors::DecodeError::EOFError`.
+    pub fn decode(&mut self) -> Result<Value, DecodeError> {
+        match self.decode_next() {
+            Ok(val) => Ok(val),
+            Err(e) => Err(at(e, self.position, Path::default())),
+        }
+    }
+
+    fn decode_next(&mut self) -> Result<Value, DecodeError> {
+        let mut state = State::new(self.limits.clone());
+
+        let tag = self.decode_header(&mut state)?;
+
+        self.decode_value(tag, &state, 0)
+    }
+
+    // Reads everything before the data, returning the identifier of the first byte of the data.
+    fn decode_header(&mut self, state: &mut State) -> Result<u8, DecodeError> {
+        let mut tag = self.read_byte()?;
+
+        loop {
+            if tag == constants::EXTENSION | constants::EXTENSION_ENCRYPTED << 4 {
+                return Err(DecodeError::EncryptedError);
+            }
+            if tag != constants::EXTENSION | constants::EXTENSION_SIGNED << 4 {
+                break;
+            }
+            self.read_bytes(64)?;
+            tag = self.read_byte()?;
+        }
+
+        let mut features = 0;
+        if tag == constants::EXTENSION | constants::EXTENSION_FEATURES << 4 {
+            features = self.read_byte()?;
+            if features & !constants::FEATURES != 0 {
+                // The same as the other decoders, the error is at the start of the features.
+                let e = DecodeError::UnsupportedFeatureError(features);
+                return Err(at(e, self.position - 2, Path::default()));
+            }
+            tag = self.read_byte()?;
+        }
+
+        if features & constants::FEATURE_METADATA != 0 {
+            tag = self.decode_section(tag, constants::EXTENSION_METADATA)?;
+            self.decode_value(tag, state, 0)?;
+            tag = self.read_byte()?;
+        }
+
+        if features & constants::FEATURE_SCHEMA != 0 {
+            tag = self.decode_section(tag, constants::EXTENSION_SCHEMA)?;
+            self.decode_value(tag, state, 0)?;
+            tag = self.read_byte()?;
+        }
+
+        if features & constants::FEATURE_STRING_TABLE != 0
+            && tag != constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4
+        {
+            return Err(DecodeError::UnexpectedByteError(tag, self.position - 1));
+        }
+
+        if tag == constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4 {
+            let position = self.position;
+            tag = self.read_byte()?;
+
+            match self.decode_value(tag, state, 0)? {
+                Value::Array(a) => {
+                    for val in a {
+                        match val {
+                            Value::String(s) => state.strings.push(s),
+                            _ => return Err(DecodeError::UnexpectedByteError(tag, position)),
+                        }
+                    }
+                }
+                _ => return Err(DecodeError::UnexpectedByteError(tag, position)),
+            }
+
+            tag = self.read_byte()?;
+        }
+
+        Ok(tag)
+    }
+
+    // Checks that `tag` is the extension byte for the header section `id`, returning the byte that follows it.
+    fn decode_section(&mut self, tag: u8, id: u8) -> Result<u8, DecodeError> {
+        if tag != constants::EXTENSION | id << 4 {
+            return Err(DecodeError::UnexpectedByteError(tag, self.position - 1));
+        }
+
+        self.read_byte()
+    }
+
+    // Decodes the value starting with the identifier `tag`, which has already been read. `depth` is the number of
+    // containers it is inside of.
+    fn decode_value(&mut self, tag: u8, state: &State, depth: usize) -> Result<Value, DecodeError> {
+        let start = self.position - 1;
+
+        match self.read_value(tag, state, depth) {
+            Ok(val) => Ok(val),
+            // Running out of data or failing to read it happens wherever the reader got to.
+            Err(e @ DecodeError::EOFError) | Err(e @ DecodeError::IOError(_)) => {
+                Err(at(e, self.position, Path::default()))
+            }
+            Err(e) => Err(at(e, start, Path::default())),
+        }
+    }
+
+    fn read_value(&mut self, tag: u8, state: &State, depth: usize) -> Result<Value, DecodeError> {
+        state.value()?;
+
+        if let Some(len) = skip::number_length(tag) {
+            return self.decode_number(tag, len);
+        }
+
+        let left = (tag >> 4) as usize;
+        let container = tag & 0x0F == constants::ARRAY_START
+            || tag & 0x0F == constants::DICTIONARY_START
+            || tag == constants::EXTENSION | constants::EXTENSION_MAP << 4;
+        if container && depth >= state.limits.depth {
+            return Err(DecodeError::DepthLimitError(state.limits.depth));
+        }
+
+        match tag & 0x0F {
+            constants::BIT => Ok(Value::Bit(left == 1)),
+            constants::BOOLEAN => Ok(Value::Boolean(left == 1)),
+            constants::NULL if tag == constants::NULL => Ok(Value::Null),
+            constants::STRING => Ok(Value::String(self.decode_string(left, state)?)),
+            constants::STRING_REFERENCE => {
+                let index = if left != 0 {
+                    left as u64 - 1
+                } else {
+                    let position = self.position;
+                    let tag = self.read_byte()?;
+                    if tag & 0x0F != constants::INT_32 {
+                        return Err(DecodeError::UnexpectedByteError(tag, position));
+                    }
+                    let len = skip::number_length(tag).unwrap_or(1);
+                    match self.decode_number(tag, len)? {
+                        Value::Int32(i) if i >= 0 => i as u64,
+                        _ => return Err(DecodeError::UnexpectedByteError(tag, position)),
+                    }
+                };
+
+                match state.strings.get(index as usize) {
+                    Some(s) => {
+                        state.string(s.len())?;
+                        Ok(Value::String(s.to_owned()))
+                    }
+                    None => Err(DecodeError::StringReferenceError(index)),
+                }
+            }
+            constants::ARRAY_START => {
+                let mut emp_array = vec![];
+                let mut i = 0;
+
+                while left == 0 || i < left {
+                    let tag = self.read_byte()?;
+                    if left == 0 && tag == constants::ARRAY_END {
+                        break;
+                    }
+
+                    match self.decode_value(tag, state, depth + 1) {
+                        Ok(val) => emp_array.push(val),
+                        Err(e) => return Err(within(e, &[Segment::Index(i)])),
+                    }
+                    i += 1;
+                }
+
+                Ok(Value::Array(emp_array))
+            }
+            constants::DICTIONARY_START => {
+                let mut emp_object = HashMap::new();
+
+                for (key, val) in self.decode_pairs(left, true, state, depth + 1)? {
+                    if let Value::String(s) = key {
+                        emp_object.insert(s, val);
+                    }
+                }
+
+                Ok(Value::Object(emp_object))
+            }
+            constants::EXTENSION if tag == constants::EXTENSION | constants::EXTENSION_MAP << 4 => {
+                let tag = self.read_byte()?;
+                if tag & 0x0F != constants::DICTIONARY_START {
+                    return Err(DecodeError::UnexpectedByteError(tag, self.position - 1));
+                }
+
+                let emp_map = self.decode_pairs((tag >> 4) as usize, false, state, depth + 1)?;
+                Ok(Value::Map(emp_map))
+            }
+            _ => Err(DecodeError::UnexpectedByteError(tag, self.position - 1)),
+        }
+    }
+
+    // Reads `left` key value pairs, or if `left` is 0 every pair until the end of the dictionary. Each key is checked
+    // as soon as it is read, keys of an `object` have to be strings and keys of a map have to be scalars.
+    fn decode_pairs(
+        &mut self,
+        left: usize,
+        object: bool,
+        state: &State,
+        depth: usize,
+    ) -> Result<Vec<(Value, Value)>, DecodeError> {
+        let mut pairs = vec![];
+
+        while left == 0 || pairs.len() < left {
+            let tag = self.read_byte()?;
+            if left == 0 && tag == constants::DICTIONARY_END {
+                break;
+            }
+
+            let start = self.position - 1;
+            let key = self.decode_value(tag, state, depth)?;
+            let valid = match key {
+                Value::String(_) => true,
+                _ => !object && key.is_scalar(),
+            };
+            if !valid {
+                return Err(at(
+                    DecodeError::InvalidKeyError(key),
+                    start,
+                    Path::default(),
+                ));
+            }
+
+            // Running out of data after a key is inside of the value of that key.
+            let tag = match self.read_byte() {
+                Ok(t) => t,
+                Err(e) => {
+                    let e = at(e, self.position, Path::default());
+                    return Err(within(e, &[key_segment(&key)]));
+                }
+            };
+            // A map reads the closing tag as the value of the key, which is an error of its own.
+            if let (Value::String(s), true, 0) = (&key, object, left) {
+                if tag == constants::DICTIONARY_END {
+                    let e = DecodeError::UnmatchedKeyError(s.to_owned());
+                    return Err(at(e, self.position - 1, Path::default()));
+                }
+            }
+
+            match self.decode_value(tag, state, depth) {
+                Ok(val) => pairs.push((key, val)),
+                Err(e) => return Err(within(e, &[key_segment(&key)])),
+            }
+        }
+
+        Ok(pairs)
+    }
+
+    // Reads a string which is `left` bytes long, or if `left` is 0 ends with a closing tag.
+    fn decode_string(&mut self, left: usize, state: &State) -> Result<String, DecodeError> {
+        let bytes = if left != 0 {
+            state.string(left)?;
+            self.read_bytes(left)?
+        } else {
+            // Reading stops one byte past the longest string allowed, so a string
+            // which is never closed can't fill up memory.
+            let most = (state.limits.string_length as u64).saturating_add(1);
+            let mut bytes = vec![];
+            match (&mut self.reader)
+                .take(most)
+                .read_until(constants::STRING, &mut bytes)
+            {
+                Ok(_) => {}
+                Err(e) => return Err(DecodeError::IOError(e)),
+            }
+            self.position += bytes.len() as u64;
+
+            if bytes.last() != Some(&constants::STRING) {
+                if bytes.len() as u64 == most {
+                    return Err(DecodeError::StringLengthLimitError(
+                        state.limits.string_length,
+                    ));
+                }
+                return Err(DecodeError::EOFError);
+            }
+            bytes.pop();
+            state.string(bytes.len())?;
+            bytes
+        };
+
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(e) => Err(DecodeError::StringDecodeError(e.utf8_error())),
+        }
+    }
+
+    // Reads a number which takes up `len` bytes including its identifier `tag`.
+    fn decode_number(&mut self, tag: u8, len: usize) -> Result<Value, DecodeError> {
+        if tag & 0x0F == constants::INT_8 {
+            if len == 1 {
+                return Ok(Value::Int8((tag >> 4) as i8));
+            }
+            return Ok(Value::Int8(self.read_byte()? as i8));
+        }
+
+        // The bytes are read into the end of a u64 so that the trimmed leading zeros are filled back in.
+        let mut buffer = [0; 8];
+        let bytes = self.read_bytes(len - 1)?;
+        buffer[9 - len..].copy_from_slice(&bytes);
+
+        let bits = u64::from_be_bytes(buffer);
+        let negative = tag >> 7 == 1;
+
+        match tag & 0x0F {
+            constants::INT_16 => {
+                let i = bits as u16 as i16;
+                Ok(Value::Int16(if negative { i.wrapping_neg() } else { i }))
+            }
+            constants::INT_32 => {
+                let i = bits as u32 as i32;
+                Ok(Value::Int32(if negative { i.wrapping_neg() } else { i }))
+            }
+            constants::INT_64 => {
+                let i = bits as i64;
+                Ok(Value::Int64(if negative { i.wrapping_neg() } else { i }))
+            }
+            constants::FLOAT => {
+                let f = f32::from_bits(bits as u32);
+                Ok(Value::Float(if negative { -f } else { f }))
+            }
+            _ => {
+                let d = f64::from_bits(bits);
+                Ok(Value::Double(if negative { -d } else { d }))
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, DecodeError> {
+        let mut byte = [0];
+        self.read_into(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, DecodeError> {
+        let mut bytes = vec![0; len];
+        self.read_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // Fills `buffer` from the reader, counting every byte read so errors are placed where the data stopped.
+    fn read_into(&mut self, buffer: &mut [u8]) -> Result<(), DecodeError> {
+        let mut read = 0;
+
+        while read < buffer.len() {
+            match self.reader.read(&mut buffer[read..]) {
+                Ok(0) => return Err(DecodeError::EOFError),
+                Ok(n) => {
+                    read += n;
+                    self.position += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(DecodeError::IOError(e)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = Result<Value, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // After an error the stream is no longer lined up with the start of a value.
+        if self.failed {
+            return None;
+        }
+
+        let next = match self.reader.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => self.decode(),
+            Err(e) => Err(at(DecodeError::IOError(e), self.position, Path::default())),
+        };
+        self.failed = next.is_err();
+
+        Some(next)
+    }
+}
@@ -0,0 +1,356 @@
+//! Checking that EMP Bytecode is well formed without decoding it
+
+use crate::constants;
+use crate::decode::{
+    at, decode_envelope, decode_features, decode_section, decode_string_table, decode_value,
+    located, skip, State,
+};
+use crate::errors::DecodeError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::vec::Vec;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// Statistics about EMP bytecode collected by
+/// `emp::decode::validate::validate`.
+pub struct Stats {
+    /// How deeply containers are nested, a lone scalar has a depth of 0.
+    pub depth: usize,
+    /// The number of values in the data, including object and map keys.
+    pub elements: usize,
+    /// The number of bytes taken up by the data, including any header.
+    pub length: usize,
+}
+
+// A container which has been started but not ended.
+struct Frame {
+    // The byte which ends the container, only used when `len` is 0.
+    end: u8,
+    // How many values the container holds (keys included), 0 when it ends with a closing tag instead.
+    len: usize,
+    count: usize,
+    object: bool,
+    pairs: bool,
+    // How many bytes were left when the last key started.
+    key: usize,
+}
+
+/// Checks that a slice of `u8`s is well formed EMP bytecode, without building
+/// any `Value`s.
+///
+/// If the data is well formed this returns `emp::decode::validate::Stats`
+/// about it, otherwise it returns a `emp::errors::DecodeError` for the first
+/// problem in it. Containers are walked with a stack of their own rather than
+/// by recursing, so deeply nested data can not overflow the stack. Like
+/// `emp::decode::decode`, any bytes after the end of the data are ignored and
+/// not counted in the length.
+pub fn validate(bytes: &[u8]) -> Result<Stats, DecodeError> {
+    located(bytes, check(bytes))
+}
+
+fn check(bytes: &[u8]) -> Result<Stats, DecodeError> {
+    let (features, mut rest) = decode_features(decode_envelope(bytes)?)?;
+
+    if features & constants::FEATURE_METADATA != 0 {
+        rest = decode_section(rest, constants::EXTENSION_METADATA)?;
+        rest = walk(rest, 0, false, &[], &mut Stats::default())?;
+    }
+
+    if features & constants::FEATURE_SCHEMA != 0 {
+        rest = decode_section(rest, constants::EXTENSION_SCHEMA)?;
+        rest = walk(rest, 0, false, &[], &mut Stats::default())?;
+    }
+
+    if features & constants::FEATURE_STRING_TABLE != 0 {
+        decode_section(rest, constants::EXTENSION_STRING_TABLE)?;
+    }
+
+    let header = rest;
+    let mut strings = 0;
+    if !rest.is_empty() && rest[0] == constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4
+    {
+        let mut table = Stats::default();
+        rest = walk(&rest[1..], 0, true, &[], &mut table)?;
+        // Every value in the table except the array holding them is a string.
+        strings = table.elements - 1;
+    }
+
+    let mut stats = Stats::default();
+    rest = walk(rest, strings, false, header, &mut stats)?;
+    stats.length = bytes.len() - rest.len();
+
+    Ok(stats)
+}
+
+// Checks the value at the start of `bytes`, which may use the first `strings` entries of the string table, returning
+// the bytes after it. When `table` is set the value has to be an array of strings. `header` starts at the string table
+// the value uses, which is only read to show keys in the path of an error.
+fn walk<'a>(
+    mut bytes: &'a [u8],
+    strings: usize,
+    table: bool,
+    header: &[u8],
+    stats: &mut Stats,
+) -> Result<&'a [u8], DecodeError> {
+    let start = bytes;
+    let mut frames: Vec<Frame> = vec![];
+
+    match walk_frames(&mut bytes, &mut frames, strings, table, stats) {
+        Ok(()) => Ok(bytes),
+        Err(e) => Err(at(
+            e,
+            bytes.len() as u64,
+            frames_path(start, &frames, header),
+        )),
+    }
+}
+
+// The path to the value being checked inside of the containers in `frames`, which stops at a container waiting for a
+// key. Keys are only decoded here, once there is an error to show them in.
+fn frames_path(start: &[u8], frames: &[Frame], header: &[u8]) -> Path {
+    let mut state = State::default();
+    if decode_string_table(header, &mut state).is_err() {
+        state = State::default();
+    }
+    let mut segments = vec![];
+
+    for frame in frames {
+        let segment = if !frame.pairs {
+            Segment::Index(frame.count)
+        } else if frame.count % 2 == 1 {
+            match decode_value(&start[start.len() - frame.key..], &state) {
+                Ok((key, _)) => key_segment(&key),
+                Err(_) => break,
+            }
+        } else {
+            break;
+        };
+        segments.push(segment);
+    }
+
+    Path { segments }
+}
+
+// Does the work of `walk`, leaving `bytes` at the start of the value an error is in along with the containers it is
+// inside of in `frames`.
+fn walk_frames(
+    bytes: &mut &[u8],
+    frames: &mut Vec<Frame>,
+    strings: usize,
+    table: bool,
+    stats: &mut Stats,
+) -> Result<(), DecodeError> {
+    let start = *bytes;
+
+    loop {
+        let mut key = false;
+        let mut object_key = false;
+
+        if let Some(frame) = frames.last() {
+            let ends = if frame.len == 0 {
+                !bytes.is_empty() && bytes[0] == frame.end
+            } else {
+                frame.count == frame.len
+            };
+
+            if ends {
+                if frame.len == 0 {
+                    if frame.pairs && frame.count % 2 == 1 {
+                        let e = unmatched_key(&start[start.len() - frame.key..], frame.object);
+                        // An object is finished by its closing tag even after a key, so the error is not inside of
+                        // that key, while a map reads the tag as the value of the key.
+                        if frame.object {
+                            frames.pop();
+                        }
+                        return Err(e);
+                    }
+                    *bytes = &bytes[1..];
+                }
+
+                frames.pop();
+                match frames.last_mut() {
+                    Some(f) => f.count += 1,
+                    None => return Ok(()),
+                }
+                continue;
+            }
+
+            key = frame.pairs && frame.count % 2 == 0;
+            object_key = key && frame.object;
+        }
+
+        if bytes.is_empty() {
+            return Err(DecodeError::EOFError);
+        }
+
+        if key {
+            if let Some(f) = frames.last_mut() {
+                f.key = bytes.len();
+            }
+        }
+
+        let tag = bytes[0];
+        stats.elements += 1;
+
+        if table
+            && (!frames.is_empty() || tag & 0x0F != constants::ARRAY_START)
+            && (frames.len() != 1 || tag & 0x0F != constants::STRING)
+        {
+            // Like the other decoders, the error is at the start of the table rather than the value inside of it.
+            frames.clear();
+            return Err(DecodeError::UnexpectedByteError(
+                start[0],
+                start.len() as u64,
+            ));
+        }
+
+        // Keys of objects have to be strings, while keys of maps can be any value except a container.
+        let scalar = tag != constants::NULL
+            && !matches!(
+                tag & 0x0F,
+                constants::ARRAY_START | constants::DICTIONARY_START
+            )
+            && tag != constants::EXTENSION | constants::EXTENSION_MAP << 4;
+        let string = matches!(tag & 0x0F, constants::STRING | constants::STRING_REFERENCE);
+        if key && tag == constants::NULL {
+            return Err(DecodeError::InvalidKeyError(Value::Null));
+        }
+        // Containers are not decoded to show them in the error, since that would need them to be walked twice.
+        if key && !scalar {
+            return Err(DecodeError::UnexpectedByteError(tag, bytes.len() as u64));
+        }
+        if object_key && !string {
+            return Err(invalid_key(bytes));
+        }
+
+        let container = match tag & 0x0F {
+            constants::ARRAY_START => Some((constants::ARRAY_END, false, false, 1)),
+            constants::DICTIONARY_START => Some((constants::DICTIONARY_END, true, true, 1)),
+            constants::EXTENSION if tag == constants::EXTENSION | constants::EXTENSION_MAP << 4 => {
+                if bytes.len() < 2 {
+                    return Err(DecodeError::EOFError);
+                }
+                if bytes[1] & 0x0F != constants::DICTIONARY_START {
+                    return Err(DecodeError::UnexpectedByteError(
+                        bytes[1],
+                        (bytes.len() - 1) as u64,
+                    ));
+                }
+                Some((constants::DICTIONARY_END, false, true, 2))
+            }
+            _ => None,
+        };
+
+        if let Some((end, object, pairs, skip)) = container {
+            let left = (bytes[skip - 1] >> 4) as usize;
+
+            frames.push(Frame {
+                end,
+                len: if pairs { left * 2 } else { left },
+                count: 0,
+                object,
+                pairs,
+                key: 0,
+            });
+            stats.depth = stats.depth.max(frames.len());
+            *bytes = &bytes[skip..];
+            continue;
+        }
+
+        *bytes = walk_scalar(bytes, strings)?;
+
+        match frames.last_mut() {
+            Some(f) => f.count += 1,
+            None => return Ok(()),
+        }
+    }
+}
+
+// Checks a value which is not a container, returning the bytes after it.
+fn walk_scalar(bytes: &[u8], strings: usize) -> Result<&[u8], DecodeError> {
+    let tag = bytes[0];
+    let left = (tag >> 4) as usize;
+
+    match tag & 0x0F {
+        constants::STRING => {
+            let (string, rest) = if left != 0 {
+                if bytes.len() < left + 1 {
+                    return Err(DecodeError::EOFError);
+                }
+                (&bytes[1..left + 1], &bytes[left + 1..])
+            } else {
+                match bytes[1..].iter().position(|b| *b == constants::STRING) {
+                    Some(end) => (&bytes[1..end + 1], &bytes[end + 2..]),
+                    None => return Err(DecodeError::EOFError),
+                }
+            };
+
+            match std::str::from_utf8(string) {
+                Ok(_) => Ok(rest),
+                Err(e) => Err(DecodeError::StringDecodeError(e)),
+            }
+        }
+        constants::STRING_REFERENCE => {
+            let (index, rest) = if left != 0 {
+                (left - 1, &bytes[1..])
+            } else {
+                let rest = &bytes[1..];
+                if rest.is_empty() {
+                    return Err(DecodeError::EOFError);
+                }
+                if rest[0] & 0x0F != constants::INT_32 {
+                    return Err(DecodeError::UnexpectedByteError(rest[0], rest.len() as u64));
+                }
+
+                match walk_number(rest)? {
+                    (Value::Int32(i), r) if i >= 0 => (i as usize, r),
+                    _ => return Err(DecodeError::UnexpectedByteError(rest[0], rest.len() as u64)),
+                }
+            };
+
+            if index >= strings {
+                return Err(DecodeError::StringReferenceError(index as u64));
+            }
+            Ok(rest)
+        }
+        _ => Ok(walk_number(bytes)?.1),
+    }
+}
+
+// Checks a number, Bit, Boolean or Null, none of which allocate when they are decoded.
+fn walk_number(bytes: &[u8]) -> Result<(Value, &[u8]), DecodeError> {
+    let known = match skip::number_length(bytes[0]) {
+        Some(len) if bytes.len() < len => return Err(DecodeError::EOFError),
+        Some(_) => true,
+        None => {
+            bytes[0] == constants::NULL
+                || matches!(bytes[0] & 0x0F, constants::BIT | constants::BOOLEAN)
+        }
+    };
+
+    if !known {
+        return Err(DecodeError::UnexpectedByteError(
+            bytes[0],
+            bytes.len() as u64,
+        ));
+    }
+
+    decode_value(bytes, &State::default())
+}
+
+// Builds the error for an object key which is not a string, decoding it so that it can be shown.
+fn invalid_key(bytes: &[u8]) -> DecodeError {
+    match walk_number(bytes) {
+        Ok((key, _)) => DecodeError::InvalidKeyError(key),
+        Err(e) => e,
+    }
+}
+
+// Builds the error for a dictionary which ends after a key, `bytes` starts at that key.
+fn unmatched_key(bytes: &[u8], object: bool) -> DecodeError {
+    match decode_value(bytes, &State::default()) {
+        Ok((Value::String(s), _)) if object => DecodeError::UnmatchedKeyError(s),
+        Ok((_, rest)) => DecodeError::UnexpectedByteError(rest[0], rest.len() as u64),
+        Err(_) => DecodeError::UnexpectedByteError(bytes[0], bytes.len() as u64),
+    }
+}
@@ -0,0 +1,100 @@
+//! Verifying signed EMP Bytecode
+
+use crate::constants;
+use crate::decode;
+use crate::errors::DecodeError;
+use crate::value::Value;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+const SIGNATURE_LENGTH: usize = 64;
+
+/// Checks a detached signature created by
+/// `emp::encode::sign::sign_detached` against EMP bytecode using the 32 byte
+/// public key of the signer.
+///
+/// If the signature does not match it returns
+/// `emp::errors::DecodeError::SignatureError`.
+pub fn verify_detached(
+    bytes: &[u8],
+    signature: &[u8; 64],
+    public: &[u8; 32],
+) -> Result<(), DecodeError> {
+    decode::located(bytes, check(bytes, signature, public))
+}
+
+fn check(bytes: &[u8], signature: &[u8; 64], public: &[u8; 32]) -> Result<(), DecodeError> {
+    let key = match VerifyingKey::from_bytes(public) {
+        Ok(k) => k,
+        Err(_) => return Err(DecodeError::SignatureError),
+    };
+
+    match key.verify(bytes, &Signature::from_bytes(signature)) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(DecodeError::SignatureError),
+    }
+}
+
+/// Checks the signature embedded by `emp::encode::sign::sign` using the 32
+/// byte public key of the signer, returning the signed EMP bytecode.
+///
+/// If the signature does not match it returns
+/// `emp::errors::DecodeError::SignatureError`.
+pub fn verify<'a>(bytes: &'a [u8], public: &[u8; 32]) -> Result<&'a [u8], DecodeError> {
+    decode::located(bytes, check_embedded(bytes, public))
+}
+
+fn check_embedded<'a>(bytes: &'a [u8], public: &[u8; 32]) -> Result<&'a [u8], DecodeError> {
+    let identifier = constants::EXTENSION | constants::EXTENSION_SIGNED << 4;
+
+    if bytes.is_empty() {
+        return Err(DecodeError::EOFError);
+    }
+    if bytes[0] != identifier {
+        return Err(DecodeError::UnexpectedByteError(
+            bytes[0],
+            bytes.len() as u64,
+        ));
+    }
+    if bytes.len() < 1 + SIGNATURE_LENGTH {
+        return Err(DecodeError::EOFError);
+    }
+
+    let mut signature = [0; SIGNATURE_LENGTH];
+    signature.copy_from_slice(&bytes[1..1 + SIGNATURE_LENGTH]);
+
+    let data = &bytes[1 + SIGNATURE_LENGTH..];
+    let mut message = vec![identifier];
+    message.extend_from_slice(data);
+
+    check(&message, &signature, public)?;
+
+    Ok(data)
+}
+
+/// Checks the signature embedded by `emp::encode::sign::encode_signed` and
+/// decodes the data into an `emp::value::Value`.
+pub fn decode_verified(bytes: &[u8], public: &[u8; 32]) -> Result<Value, DecodeError> {
+    verify(bytes, public)?;
+
+    // The decoder skips over the signature itself, so errors are placed from the start of `bytes`.
+    match decode::decode(bytes) {
+        Ok((v, _)) => Ok(v),
+        Err(e) => Err(e),
+    }
+}
+
+/// Checks a detached signature created by
+/// `emp::encode::sign::encode_signed_detached` and decodes the data into an
+/// `emp::value::Value`.
+pub fn decode_verified_detached(
+    bytes: &[u8],
+    signature: &[u8; 64],
+    public: &[u8; 32],
+) -> Result<Value, DecodeError> {
+    verify_detached(bytes, signature, public)?;
+
+    match decode::decode(bytes) {
+        Ok((v, _)) => Ok(v),
+        Err(e) => Err(e),
+    }
+}
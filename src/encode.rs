@@ -1,22 +1,293 @@
 //! Encoding Values into EMP Bytecode
 
 use crate::constants;
+use crate::value::schema::Schema;
 use crate::value::Value;
+use std::collections::HashMap;
+use std::io::{self, Write};
 use std::vec::Vec;
 
+#[cfg(feature = "encryption")]
+pub mod encrypt;
 pub mod json;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod patch;
+#[cfg(feature = "signatures")]
+pub mod sign;
+pub mod stream;
+
+#[derive(Clone, Debug)]
+/// Options for the encoder, controlling which optimizations are used.
+///
+/// `EncodeOptions::default()` is what `emp::encode::encode` uses, while
+/// `EncodeOptions::speed()` and `EncodeOptions::size()` favour encoding speed
+/// and output size respectively. Data encoded with any set of options can be
+/// decoded by `emp::decode::decode` without any configuration.
+pub struct EncodeOptions {
+    /// Store the length of short strings, arrays and objects in their
+    /// identifier instead of ending them with a closing tag.
+    pub nibble_lengths: bool,
+    /// Trim the leading `0x00` bytes off of numbers.
+    pub trim_leading_zeros: bool,
+    /// Store Int8s between 1 and 15 in their identifier.
+    pub pack_int8: bool,
+    /// Store strings which are at least this many bytes long and appear more
+    /// than once in a string table, `None` stores every string inline.
+    pub string_table: Option<usize>,
+    /// An object stored before the data, for things like the author, the
+    /// application that created it, a timestamp or any other tags. It can be
+    /// read with `emp::decode::decode_metadata` without decoding the data.
+    pub metadata: Option<HashMap<String, Value>>,
+    /// A schema describing the data, stored before it so that other tools
+    /// can read it with `emp::decode::decode_schema`.
+    pub schema: Option<Schema>,
+    /// Write the keys of objects in sorted order, so that the same `Value`
+    /// always encodes into the same bytes.
+    pub canonical: bool,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            nibble_lengths: true,
+            trim_leading_zeros: true,
+            pack_int8: true,
+            string_table: None,
+            metadata: None,
+            schema: None,
+            canonical: false,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Options which skip any optimization that needs extra work per value.
+    pub fn speed() -> Self {
+        EncodeOptions {
+            nibble_lengths: true,
+            trim_leading_zeros: false,
+            pack_int8: false,
+            string_table: None,
+            metadata: None,
+            schema: None,
+            canonical: false,
+        }
+    }
+
+    /// Options which give every number of the same type its full width and
+    /// every string and container a closing tag instead of a length in its
+    /// identifier, so that numbers can be overwritten with
+    /// `emp::encode::patch::patch` later on.
+    pub fn fixed_width() -> Self {
+        EncodeOptions {
+            nibble_lengths: false,
+            trim_leading_zeros: false,
+            pack_int8: false,
+            string_table: None,
+            metadata: None,
+            schema: None,
+            canonical: false,
+        }
+    }
+
+    /// Options which produce the smallest output possible.
+    pub fn size() -> Self {
+        EncodeOptions {
+            nibble_lengths: true,
+            trim_leading_zeros: true,
+            pack_int8: true,
+            string_table: Some(2),
+            metadata: None,
+            schema: None,
+            canonical: false,
+        }
+    }
+}
+
+struct State<'o> {
+    options: &'o EncodeOptions,
+    strings: HashMap<String, usize>,
+}
 
 /// Encodes an `emp::value::Value` into a `Vec<u8>` to store in a `.emp` file.
 pub fn encode(val: Value) -> Vec<u8> {
+    encode_with(&val, &EncodeOptions::default())
+}
+
+/// Encodes an `emp::value::Value` into a `Vec<u8>`, storing repeated strings
+/// in a string table at the start of the data.
+///
+/// Only strings which are at least `threshold` bytes long and appear more
+/// than once are moved into the table, every other string is stored inline.
+pub fn encode_with_string_table(val: Value, threshold: usize) -> Vec<u8> {
+    encode_with(
+        &val,
+        &EncodeOptions {
+            string_table: Some(threshold),
+            ..EncodeOptions::default()
+        },
+    )
+}
+
+/// Encodes an `emp::value::Value` into a `Vec<u8>` using the given
+/// `emp::encode::EncodeOptions`.
+///
+/// If any of the options need the decoder to know about them, a header
+/// listing them is placed at the start of the data.
+pub fn encode_with(val: &Value, options: &EncodeOptions) -> Vec<u8> {
+    let mut value = vec![];
+    encode_into_with(val, options, &mut value);
+
+    value
+}
+
+/// Encodes an `emp::value::Value` onto the end of `out`, without taking
+/// ownership of the value.
+///
+/// Reusing the same `Vec<u8>` for many values, clearing it in between, saves
+/// allocating a new one every time.
+pub fn encode_into(val: &Value, out: &mut Vec<u8>) {
+    encode_into_with(val, &EncodeOptions::default(), out);
+}
+
+/// Encodes an `emp::value::Value` onto the end of `out` using the given
+/// `emp::encode::EncodeOptions`.
+pub fn encode_into_with(val: &Value, options: &EncodeOptions, out: &mut Vec<u8>) {
+    let mut state = State {
+        options,
+        strings: HashMap::new(),
+    };
+
+    write_header(Some(val), &mut state, out).expect("Writing to a Vec<u8> can not fail");
+    write_value(val, &state, out).expect("Writing to a Vec<u8> can not fail");
+}
+
+/// Returns the number of bytes `emp::encode::encode` would produce for an
+/// `emp::value::Value`, without keeping any of them.
+pub fn encoded_len(val: &Value) -> usize {
+    encoded_len_with(val, &EncodeOptions::default())
+}
+
+/// Returns the number of bytes `emp::encode::encode_with` would produce for
+/// an `emp::value::Value` with the given `emp::encode::EncodeOptions`.
+///
+/// The value is run through the encoder with the bytes counted instead of
+/// stored, so the length always matches what would have been written.
+pub fn encoded_len_with(val: &Value, options: &EncodeOptions) -> usize {
+    let mut state = State {
+        options,
+        strings: HashMap::new(),
+    };
+
+    let mut counter = Counter { len: 0 };
+    write_header(Some(val), &mut state, &mut counter).expect("Counting bytes can not fail");
+    write_value(val, &state, &mut counter).expect("Counting bytes can not fail");
+
+    counter.len
+}
+
+// A writer which only counts the bytes written into it.
+struct Counter {
+    len: usize,
+}
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Writes the features, metadata, schema and string table which go before the data, filling in the string table of
+// `state`. The string table can only be built when the whole value is known up front.
+fn write_header<W: Write + ?Sized>(
+    val: Option<&Value>,
+    state: &mut State,
+    out: &mut W,
+) -> io::Result<()> {
+    let options = state.options;
+    let mut features = 0;
+    let mut table: Vec<(String, usize)> = vec![];
+
+    if let (Some(threshold), Some(val)) = (options.string_table, val) {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        count_strings(val, &mut counts);
+
+        table = counts
+            .into_iter()
+            .filter(|(s, c)| *c > 1 && s.len() >= threshold)
+            .collect();
+
+        // The most common strings are placed first so that they get the indices which fit into the identifier.
+        table.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    }
+
+    if options.metadata.is_some() {
+        features |= constants::FEATURE_METADATA;
+    }
+    if options.schema.is_some() {
+        features |= constants::FEATURE_SCHEMA;
+    }
+    if !table.is_empty() {
+        features |= constants::FEATURE_STRING_TABLE;
+    }
+
+    if features != 0 {
+        out.write_all(&[
+            constants::EXTENSION | constants::EXTENSION_FEATURES << 4,
+            features,
+        ])?;
+    }
+
+    if let Some(metadata) = &options.metadata {
+        out.write_all(&[constants::EXTENSION | constants::EXTENSION_METADATA << 4])?;
+        write_object(metadata, state, out)?;
+    }
+
+    if let Some(schema) = &options.schema {
+        out.write_all(&[constants::EXTENSION | constants::EXTENSION_SCHEMA << 4])?;
+        write_value(&schema.to_value(), state, out)?;
+    }
+
+    if !table.is_empty() {
+        let set_len = nibble_length(table.len(), state);
+
+        out.write_all(&[
+            constants::EXTENSION | constants::EXTENSION_STRING_TABLE << 4,
+            constants::ARRAY_START | if set_len { table.len() << 4 } else { 0 } as u8,
+        ])?;
+        for (s, _) in &table {
+            write_string(s, state, out)?;
+        }
+
+        if !set_len {
+            out.write_all(&[constants::ARRAY_END])?;
+        }
+    }
+
+    for (i, (s, _)) in table.into_iter().enumerate() {
+        state.strings.insert(s, i);
+    }
+
+    Ok(())
+}
+
+// Writes the bytes of `val` straight into `out`, so that containers never need to copy the bytes of their children.
+fn write_value<W: Write + ?Sized>(val: &Value, state: &State, out: &mut W) -> io::Result<()> {
     // Most of the compression in this program comes from the fact that all identifier bytes only take up
     // the last 4 bits, which means that the first 4 bits can be used to store information.
     match val {
         // Null values are represented by 0x05 or 0b0101, no compression here.
-        Value::Null => return vec![constants::NULL],
+        Value::Null => out.write_all(&[constants::NULL]),
 
         // Bits and booleans both store their value in their identifier, storing it in the 4th bit.
-        Value::Bit(b) => return vec![constants::BIT | (if b { 1 } else { 0 } << 4)],
-        Value::Boolean(b) => return vec![constants::BOOLEAN | (if b { 1 } else { 0 } << 4)],
+        Value::Bit(b) => out.write_all(&[constants::BIT | (if *b { 1 } else { 0 } << 4)]),
+        Value::Boolean(b) => out.write_all(&[constants::BOOLEAN | (if *b { 1 } else { 0 } << 4)]),
 
         // All number values except for bits and bytes have what I call heading byte optimization and
         // negative number optimization.
@@ -26,71 +297,51 @@ pub fn encode(val: Value) -> Vec<u8> {
         // of bytes containing 0x00, which saves on space when these are used to store small values. This does
         // mean that for a 0l there is a byte containing 0x00 which cannot be trimmed off but I have come to
         // the conclusion that it is worth it for the optimization of negative numbers.
-        Value::Int64(i) => {
-            let bytes = i.abs().to_be_bytes();
-            let leading = get_leading_zeros(Vec::from(bytes));
-            let mut value =
-                vec![constants::INT_64 | leading << 4 | if i < 0 { 0b10000000 } else { 0 }];
-
-            value.append(&mut (bytes[(leading as usize)..bytes.len()]).to_vec());
-
-            return value;
-        }
-        Value::Int32(i) => {
-            let bytes = i.abs().to_be_bytes();
-            let leading = get_leading_zeros(Vec::from(bytes));
-            let mut value =
-                vec![constants::INT_32 | leading << 4 | if i < 0 { 0b10000000 } else { 0 }];
-
-            value.append(&mut (bytes[(leading as usize)..bytes.len()]).to_vec());
-
-            return value;
-        }
-        Value::Int16(i) => {
-            let bytes = i.abs().to_be_bytes();
-            let leading = get_leading_zeros(Vec::from(bytes));
-            let mut value =
-                vec![constants::INT_16 | leading << 4 | if i < 0 { 0b10000000 } else { 0 }];
-
-            value.append(&mut (bytes[(leading as usize)..bytes.len()]).to_vec());
-
-            return value;
-        }
-        Value::Float(i) => {
-            let bytes = i.abs().to_be_bytes();
-            let leading = get_leading_zeros(Vec::from(bytes));
-            let mut value =
-                vec![constants::FLOAT | leading << 4 | if i < 0.0 { 0b10000000 } else { 0 }];
-
-            value.append(&mut (bytes[(leading as usize)..bytes.len()]).to_vec());
-
-            return value;
-        }
-        Value::Double(i) => {
-            let bytes = i.abs().to_be_bytes();
-            let leading = get_leading_zeros(Vec::from(bytes));
-            let mut value =
-                vec![constants::DOUBLE | leading << 4 | if i < 0.0 { 0b10000000 } else { 0 }];
-
-            value.append(&mut (bytes[(leading as usize)..bytes.len()]).to_vec());
-
-            return value;
-        }
+        Value::Int64(i) => write_number(
+            constants::INT_64,
+            &i.unsigned_abs().to_be_bytes(),
+            *i < 0,
+            state,
+            out,
+        ),
+        Value::Int32(i) => write_number(
+            constants::INT_32,
+            &i.unsigned_abs().to_be_bytes(),
+            *i < 0,
+            state,
+            out,
+        ),
+        Value::Int16(i) => write_number(
+            constants::INT_16,
+            &i.unsigned_abs().to_be_bytes(),
+            *i < 0,
+            state,
+            out,
+        ),
+        Value::Float(i) => write_number(
+            constants::FLOAT,
+            &i.abs().to_be_bytes(),
+            *i < 0.0,
+            state,
+            out,
+        ),
+        Value::Double(i) => write_number(
+            constants::DOUBLE,
+            &i.abs().to_be_bytes(),
+            *i < 0.0,
+            state,
+            out,
+        ),
 
         // Due to the fact that Int8s are one byte instead it is checked wheter the value is greater than 0
         // and less than 16, if this is met then it will instead store the value of the byte in the first
         // 4 bits of the identifier, saving a byte in total.
         Value::Int8(i) => {
-            let mut value =
-                vec![constants::INT_8 | if i <= 0x0F && i > 0 { i as u8 } else { 0 } << 4];
-
-            if i > 0xf || i <= 0 {
-                for val in i.to_be_bytes() {
-                    value.push(val);
-                }
+            if state.options.pack_int8 && *i <= 0x0F && *i > 0 {
+                return out.write_all(&[constants::INT_8 | (*i as u8) << 4]);
             }
 
-            return value;
+            out.write_all(&[constants::INT_8, *i as u8])
         }
 
         // Strings, Arrays, and Objects are similar as they are all lists of values, and so have the same optimization.
@@ -101,76 +352,176 @@ pub fn encode(val: Value) -> Vec<u8> {
         // Theorecticially, for strings the first character can be split into 2 parts of 4 bits each and distributed to
         // the top 4 bits of both the opening and closing tags, saving a byte every time, but this would also require
         // the banning of all characters with the lower 4 bits 0b0100
-        Value::String(s) => {
-            let set_len = s.len() <= 0x0F && s.len() != 0;
-
-            let mut value = vec![constants::STRING | if set_len { s.len() << 4 } else { 0 } as u8];
+        //
+        // When a string table is in use, strings found in the table are replaced with a reference to their index,
+        // indices below 15 are stored in the top 4 bits of the identifier (offset by one, as 0 means that the index
+        // is stored in an Int32 directly after the identifier).
+        Value::String(s) => write_string(s, state, out),
+        Value::Array(a) => {
+            let set_len = nibble_length(a.len(), state);
 
-            for val in s.replace(|c: char| !c.is_ascii(), "").as_bytes() {
-                if val.to_owned() != constants::STRING {
-                    value.push(val.to_owned());
-                }
+            out.write_all(
+                &[constants::ARRAY_START | if set_len { a.len() << 4 } else { 0 } as u8],
+            )?;
+            for val in a {
+                write_value(val, state, out)?;
             }
 
             if !set_len {
-                value.push(constants::STRING);
+                out.write_all(&[constants::ARRAY_END])?;
             }
-            return value;
+
+            Ok(())
         }
-        Value::Array(a) => {
-            let set_len = a.len() <= 0x0F && a.len() != 0;
+        Value::Object(o) => write_object(o, state, out),
 
-            let mut value =
-                vec![constants::ARRAY_START | if set_len { a.len() << 4 } else { 0 } as u8];
-            for val in a {
-                for byte in encode(val) {
-                    value.push(byte);
-                }
+        // Maps are stored the same way as objects but with an extension byte in front of them, as the top 4 bits
+        // of the extension byte are used to identify it there is no space to store the length in, so this costs
+        // an extra byte compared to an object.
+        Value::Map(m) => {
+            let set_len = nibble_length(m.len(), state);
+
+            out.write_all(&[
+                constants::EXTENSION | constants::EXTENSION_MAP << 4,
+                constants::DICTIONARY_START | if set_len { m.len() << 4 } else { 0 } as u8,
+            ])?;
+            for (k, v) in m {
+                write_value(k, state, out)?;
+                write_value(v, state, out)?;
             }
 
             if !set_len {
-                value.push(constants::ARRAY_END);
+                out.write_all(&[constants::DICTIONARY_END])?;
             }
 
-            return value;
+            Ok(())
+        }
+    }
+}
+
+fn write_object<W: Write + ?Sized>(
+    o: &HashMap<String, Value>,
+    state: &State,
+    out: &mut W,
+) -> io::Result<()> {
+    let set_len = nibble_length(o.len(), state);
+
+    out.write_all(&[constants::DICTIONARY_START | if set_len { o.len() << 4 } else { 0 } as u8])?;
+
+    // Sorting the keys needs a list of them, so it is only built when canonical output was asked for.
+    if state.options.canonical {
+        let mut keys: Vec<&String> = o.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            write_string(key, state, out)?;
+            write_value(&o[key], state, out)?;
+        }
+    } else {
+        for (key, val) in o {
+            write_string(key, state, out)?;
+            write_value(val, state, out)?;
+        }
+    }
+
+    if !set_len {
+        out.write_all(&[constants::DICTIONARY_END])?;
+    }
+
+    Ok(())
+}
+
+// Whether a string or container of length `len` stores it in its identifier instead of ending with a closing tag.
+fn nibble_length(len: usize, state: &State) -> bool {
+    state.options.nibble_lengths && len <= 0x0F && len != 0
+}
+
+fn write_number<W: Write + ?Sized>(
+    tag: u8,
+    bytes: &[u8],
+    negative: bool,
+    state: &State,
+    out: &mut W,
+) -> io::Result<()> {
+    let leading = get_leading_zeros(bytes, state);
+
+    out.write_all(&[tag | leading << 4 | if negative { 0b10000000 } else { 0 }])?;
+    out.write_all(&bytes[(leading as usize)..])
+}
+
+fn write_string<W: Write + ?Sized>(s: &str, state: &State, out: &mut W) -> io::Result<()> {
+    if let Some(i) = state.strings.get(s) {
+        return write_string_reference(*i, out);
+    }
+
+    let set_len = nibble_length(s.len(), state);
+
+    out.write_all(&[constants::STRING | if set_len { s.len() << 4 } else { 0 } as u8])?;
+
+    // Bytes which can not be stored are left out, writing the parts between them keeps this from copying the string.
+    for part in s
+        .as_bytes()
+        .split(|b| !b.is_ascii() || *b == constants::STRING)
+    {
+        out.write_all(part)?;
+    }
+
+    if !set_len {
+        out.write_all(&[constants::STRING])?;
+    }
+    Ok(())
+}
+
+fn write_string_reference<W: Write + ?Sized>(index: usize, out: &mut W) -> io::Result<()> {
+    if index < 0x0F {
+        return out.write_all(&[constants::STRING_REFERENCE | ((index as u8 + 1) << 4)]);
+    }
+
+    // Larger indices are always stored the way `encode` would store an Int32.
+    let state = State {
+        options: &EncodeOptions::default(),
+        strings: HashMap::new(),
+    };
+
+    out.write_all(&[constants::STRING_REFERENCE])?;
+    write_value(&Value::Int32(index as i32), &state, out)
+}
+
+fn count_strings(val: &Value, counts: &mut HashMap<String, usize>) {
+    match val {
+        Value::String(s) => *counts.entry(s.to_owned()).or_insert(0) += 1,
+        Value::Array(a) => {
+            for v in a {
+                count_strings(v, counts);
+            }
         }
         Value::Object(o) => {
-            let set_len = o.keys().len() <= 0x0F && o.keys().len() != 0;
-
-            let mut value = vec![
-                constants::DICTIONARY_START | if set_len { o.keys().len() << 4 } else { 0 } as u8,
-            ];
-            for key in o.keys() {
-                for byte in encode(Value::String(key.to_owned())) {
-                    value.push(byte);
-                }
-
-                let val = match o.get(key) {
-                    Some(e) => encode(e.clone()),
-                    None => vec![constants::NULL],
-                };
-
-                for byte in val {
-                    value.push(byte);
-                }
+            for (k, v) in o {
+                *counts.entry(k.to_owned()).or_insert(0) += 1;
+                count_strings(v, counts);
             }
-
-            if !set_len {
-                value.push(constants::DICTIONARY_END);
+        }
+        Value::Map(m) => {
+            for (k, v) in m {
+                count_strings(k, counts);
+                count_strings(v, counts);
             }
-
-            return value;
         }
+        _ => {}
     }
 }
 
-fn get_leading_zeros(val: Vec<u8>) -> u8 {
+fn get_leading_zeros(val: &[u8], state: &State) -> u8 {
     let mut out = 0;
+    if !state.options.trim_leading_zeros {
+        return out;
+    }
+
     for v in val {
-        if v != 0 || out == 7 {
+        if *v != 0 || out == 7 {
             return out;
         }
         out += 1
     }
-    return out;
+    out
 }
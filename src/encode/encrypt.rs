@@ -0,0 +1,42 @@
+//! Encrypting EMP Bytecode
+
+use crate::constants;
+use crate::encode;
+use crate::value::Value;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key};
+use std::vec::Vec;
+
+/// Encrypts EMP bytecode with ChaCha20-Poly1305 using a 32 byte `key`.
+///
+/// The output starts with an identifier and a random 12 byte nonce, followed
+/// by the encrypted data and its 16 byte authentication tag. It can be
+/// decrypted with `emp::decode::decrypt::decrypt`.
+pub fn encrypt(bytes: &[u8], key: &[u8; 32]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let identifier = constants::EXTENSION | constants::EXTENSION_ENCRYPTED << 4;
+
+    // The identifier is authenticated along with the data so that it cannot be swapped out.
+    let mut encrypted = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: bytes,
+                aad: &[identifier],
+            },
+        )
+        .expect("Data is too large to encrypt");
+
+    let mut value = vec![identifier];
+    value.extend_from_slice(&nonce);
+    value.append(&mut encrypted);
+
+    value
+}
+
+/// Encodes an `emp::value::Value` using the given
+/// `emp::encode::EncodeOptions` and encrypts it with a 32 byte `key`.
+pub fn encode_encrypted(val: &Value, options: &encode::EncodeOptions, key: &[u8; 32]) -> Vec<u8> {
+    encrypt(&encode::encode_with(val, options), key)
+}
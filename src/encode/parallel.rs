@@ -0,0 +1,115 @@
+//! Encoding large Values on several threads
+
+use crate::constants;
+use crate::encode::{nibble_length, write_header, write_string, write_value, EncodeOptions, State};
+use crate::value::Value;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io;
+use std::vec::Vec;
+
+// Containers with fewer children than this are written on the thread that reaches them.
+const MIN_CHILDREN: usize = 64;
+
+/// Encodes an `emp::value::Value` into a `Vec<u8>` using the given
+/// `emp::encode::EncodeOptions`, splitting large containers between threads.
+///
+/// The children of every array, object and map with enough of them are
+/// encoded in chunks on the rayon thread pool and then joined back together
+/// in order, so the output is byte for byte the same as
+/// `emp::encode::encode_with`.
+pub fn encode_parallel(val: &Value, options: &EncodeOptions) -> Vec<u8> {
+    let mut state = State {
+        options,
+        strings: HashMap::new(),
+    };
+
+    let mut value = vec![];
+    write_header(Some(val), &mut state, &mut value).expect("Writing to a Vec<u8> can not fail");
+    write_parallel(val, &state, &mut value).expect("Writing to a Vec<u8> can not fail");
+
+    value
+}
+
+// Writes `val` the same way as `write_value`, except that the children of large containers are written in parallel.
+fn write_parallel(val: &Value, state: &State, out: &mut Vec<u8>) -> io::Result<()> {
+    let (tag, end, len) = match val {
+        Value::Array(a) => (constants::ARRAY_START, constants::ARRAY_END, a.len()),
+        Value::Object(o) => (
+            constants::DICTIONARY_START,
+            constants::DICTIONARY_END,
+            o.len(),
+        ),
+        Value::Map(m) => {
+            out.push(constants::EXTENSION | constants::EXTENSION_MAP << 4);
+            (
+                constants::DICTIONARY_START,
+                constants::DICTIONARY_END,
+                m.len(),
+            )
+        }
+        _ => return write_value(val, state, out),
+    };
+
+    let set_len = nibble_length(len, state);
+    out.push(tag | if set_len { len << 4 } else { 0 } as u8);
+
+    match val {
+        Value::Array(a) => write_children(a, out, |v, out| write_parallel(v, state, out))?,
+        Value::Object(o) => {
+            let mut fields: Vec<(&String, &Value)> = o.iter().collect();
+            if state.options.canonical {
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+            }
+
+            write_children(&fields, out, |(k, v), out| {
+                write_string(k, state, out)?;
+                write_parallel(v, state, out)
+            })?;
+        }
+        Value::Map(m) => write_children(m, out, |(k, v), out| {
+            write_parallel(k, state, out)?;
+            write_parallel(v, state, out)
+        })?,
+        _ => {}
+    }
+
+    if !set_len {
+        out.push(end);
+    }
+
+    Ok(())
+}
+
+// Writes every child with `write`, splitting them into chunks which are written on different threads when there are
+// enough of them.
+fn write_children<T: Sync>(
+    children: &[T],
+    out: &mut Vec<u8>,
+    write: impl Fn(&T, &mut Vec<u8>) -> io::Result<()> + Sync,
+) -> io::Result<()> {
+    if children.len() < MIN_CHILDREN {
+        for child in children {
+            write(child, out)?;
+        }
+        return Ok(());
+    }
+
+    let size = (children.len() / (rayon::current_num_threads() * 4)).max(MIN_CHILDREN / 4);
+    let chunks: Vec<Vec<u8>> = children
+        .par_chunks(size)
+        .map(|chunk| {
+            let mut bytes = vec![];
+            for child in chunk {
+                write(child, &mut bytes)?;
+            }
+            Ok(bytes)
+        })
+        .collect::<io::Result<_>>()?;
+
+    for chunk in chunks {
+        out.extend_from_slice(&chunk);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Overwriting values in EMP Bytecode without re-encoding it
+
+use crate::constants;
+use crate::decode::skip;
+use crate::errors::PatchError;
+use crate::value::{path, Value};
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::vec::Vec;
+
+/// Overwrites the value at `path` in EMP bytecode with `val`, leaving the
+/// rest of the bytes untouched.
+///
+/// Only Bits, Booleans and numbers can be patched, and `val` must be the same
+/// type as the value that is already there. Numbers also have to fit into
+/// the bytes taken up by the old value, which is always the case for data
+/// encoded with `emp::encode::EncodeOptions::fixed_width()`. Signed and
+/// encrypted data can not be patched.
+pub fn patch(bytes: &mut [u8], path: &str, val: Value) -> Result<(), PatchError> {
+    let (offset, new) = locate_patch(bytes, path, val)?;
+
+    bytes[offset..offset + new.len()].copy_from_slice(&new);
+
+    Ok(())
+}
+
+/// Overwrites the value at `path` in a `.emp` file with `val`, only writing
+/// the bytes of that value back to the file.
+///
+/// This has the same restrictions as `emp::encode::patch::patch`.
+pub fn patch_file<P: AsRef<std::path::Path>>(
+    file: P,
+    path: &str,
+    val: Value,
+) -> Result<(), PatchError> {
+    let mut f = match OpenOptions::new().read(true).write(true).open(file) {
+        Ok(f) => f,
+        Err(e) => return Err(PatchError::IOError(e)),
+    };
+
+    let mut bytes = vec![];
+    if let Err(e) = f.read_to_end(&mut bytes) {
+        return Err(PatchError::IOError(e));
+    }
+
+    let (offset, new) = locate_patch(&bytes, path, val)?;
+
+    if let Err(e) = f.seek(SeekFrom::Start(offset as u64)) {
+        return Err(PatchError::IOError(e));
+    }
+    if let Err(e) = f.write_all(&new) {
+        return Err(PatchError::IOError(e));
+    }
+
+    Ok(())
+}
+
+// Finds where `val` needs to be written and encodes it to the same length as the value already there.
+fn locate_patch(bytes: &[u8], path: &str, val: Value) -> Result<(usize, Vec<u8>), PatchError> {
+    let parsed = match path::from_str(path) {
+        Ok(p) => p,
+        Err(e) => return Err(PatchError::ParseError(e)),
+    };
+
+    // Changing signed data would break the signature, and encrypted data has to be decrypted first.
+    if let Some(tag) = bytes.first() {
+        if *tag == constants::EXTENSION | constants::EXTENSION_SIGNED << 4 {
+            return Err(PatchError::SignedError);
+        }
+        if *tag == constants::EXTENSION | constants::EXTENSION_ENCRYPTED << 4 {
+            return Err(PatchError::EncryptedError);
+        }
+    }
+
+    let (offset, len) = match skip::locate(bytes, &parsed) {
+        Ok(Some(l)) => l,
+        Ok(None) => return Err(PatchError::PathNotFoundError(path.to_owned())),
+        Err(e) => return Err(PatchError::DecodeError(e)),
+    };
+
+    let new = match encode_to_length(bytes[offset], len, &val) {
+        Some(n) => n,
+        None => {
+            return if val_tag(&val) != Some(bytes[offset] & 0x0F) {
+                Err(PatchError::TypeMismatchError(val))
+            } else {
+                Err(PatchError::SizeError(val))
+            }
+        }
+    };
+
+    Ok((offset, new))
+}
+
+fn val_tag(val: &Value) -> Option<u8> {
+    match val {
+        Value::Bit(_) => Some(constants::BIT),
+        Value::Boolean(_) => Some(constants::BOOLEAN),
+        Value::Int8(_) => Some(constants::INT_8),
+        Value::Int16(_) => Some(constants::INT_16),
+        Value::Int32(_) => Some(constants::INT_32),
+        Value::Int64(_) => Some(constants::INT_64),
+        Value::Float(_) => Some(constants::FLOAT),
+        Value::Double(_) => Some(constants::DOUBLE),
+        _ => None,
+    }
+}
+
+// Encodes `val` into exactly `len` bytes, as long as it is the same type as `tag`.
+fn encode_to_length(tag: u8, len: usize, val: &Value) -> Option<Vec<u8>> {
+    if val_tag(val) != Some(tag & 0x0F) {
+        return None;
+    }
+
+    let (bytes, negative) = match val {
+        Value::Bit(b) | Value::Boolean(b) => {
+            return Some(vec![tag & 0x0F | if *b { 1 } else { 0 } << 4]);
+        }
+        Value::Int8(i) => {
+            if len == 1 && *i > 0 && *i <= 0x0F {
+                return Some(vec![constants::INT_8 | (*i as u8) << 4]);
+            }
+            if len == 2 {
+                return Some(vec![constants::INT_8, *i as u8]);
+            }
+            return None;
+        }
+        Value::Int16(i) => (i.unsigned_abs().to_be_bytes().to_vec(), *i < 0),
+        Value::Int32(i) => (i.unsigned_abs().to_be_bytes().to_vec(), *i < 0),
+        Value::Int64(i) => (i.unsigned_abs().to_be_bytes().to_vec(), *i < 0),
+        Value::Float(f) => (f.abs().to_be_bytes().to_vec(), *f < 0.0),
+        Value::Double(d) => (d.abs().to_be_bytes().to_vec(), *d < 0.0),
+        _ => return None,
+    };
+
+    // The old value's leading zero count has to be kept, so the new value can only be written if it has at
+    // least that many leading zeros.
+    let leading = bytes.len().checked_sub(len - 1)?;
+    if bytes[..leading].iter().any(|b| *b != 0) {
+        return None;
+    }
+
+    let mut value = vec![tag & 0x0F | (leading as u8) << 4 | if negative { 0b10000000 } else { 0 }];
+    value.extend_from_slice(&bytes[leading..]);
+
+    Some(value)
+}
@@ -0,0 +1,77 @@
+//! Signing EMP Bytecode
+
+use crate::constants;
+use crate::encode;
+use crate::value::Value;
+use ed25519_dalek::{Signer, SigningKey};
+use std::vec::Vec;
+
+/// Returns the 32 byte Ed25519 public key for a 32 byte secret key, which is
+/// what is needed to verify signatures made with the secret key.
+pub fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+    SigningKey::from_bytes(secret).verifying_key().to_bytes()
+}
+
+/// Signs EMP bytecode with Ed25519 using a 32 byte secret key, returning the
+/// 64 byte signature to be stored separately from the data.
+///
+/// It can be checked with `emp::decode::verify::verify_detached`.
+pub fn sign_detached(bytes: &[u8], secret: &[u8; 32]) -> [u8; 64] {
+    SigningKey::from_bytes(secret).sign(bytes).to_bytes()
+}
+
+/// Signs EMP bytecode with Ed25519 using a 32 byte secret key, embedding the
+/// signature in front of the data.
+///
+/// The output starts with an identifier and the 64 byte signature, followed
+/// by the data. It can still be read by `emp::decode::decode`, but should be
+/// checked with `emp::decode::verify::verify` first.
+pub fn sign(bytes: &[u8], secret: &[u8; 32]) -> Vec<u8> {
+    let identifier = constants::EXTENSION | constants::EXTENSION_SIGNED << 4;
+
+    // The identifier is signed along with the data so that an embedded signature cannot be passed off as
+    // a detached one.
+    let mut message = vec![identifier];
+    message.extend_from_slice(bytes);
+
+    let mut value = vec![identifier];
+    value.extend_from_slice(&sign_detached(&message, secret));
+    value.extend_from_slice(bytes);
+
+    value
+}
+
+/// Encodes an `emp::value::Value` with its object keys sorted and embeds a
+/// signature made with a 32 byte secret key in front of it.
+///
+/// Apart from sorting the keys, this uses the given
+/// `emp::encode::EncodeOptions`.
+pub fn encode_signed(val: &Value, options: &encode::EncodeOptions, secret: &[u8; 32]) -> Vec<u8> {
+    sign(&encode_canonical(val, options), secret)
+}
+
+/// Encodes an `emp::value::Value` with its object keys sorted and signs it
+/// with a 32 byte secret key, returning the data and the 64 byte signature.
+///
+/// Apart from sorting the keys, this uses the given
+/// `emp::encode::EncodeOptions`.
+pub fn encode_signed_detached(
+    val: &Value,
+    options: &encode::EncodeOptions,
+    secret: &[u8; 32],
+) -> (Vec<u8>, [u8; 64]) {
+    let bytes = encode_canonical(val, options);
+    let signature = sign_detached(&bytes, secret);
+
+    (bytes, signature)
+}
+
+fn encode_canonical(val: &Value, options: &encode::EncodeOptions) -> Vec<u8> {
+    encode::encode_with(
+        val,
+        &encode::EncodeOptions {
+            canonical: true,
+            ..options.clone()
+        },
+    )
+}
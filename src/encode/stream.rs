@@ -0,0 +1,238 @@
+//! Encoding Values straight into a writer
+
+use crate::constants;
+use crate::encode::{write_header, write_string, write_value, EncodeOptions, State};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::vec::Vec;
+
+/// Writes EMP bytecode straight into a `std::io::Write`, such as a file or a
+/// socket, without building the whole output in memory first.
+///
+/// A whole `Value` can be written with `Encoder::push`, or the data can be
+/// built up a piece at a time by opening containers with
+/// `Encoder::begin_array`, `Encoder::begin_object` and `Encoder::begin_map`,
+/// pushing their children and closing them with `Encoder::end`. Inside of
+/// objects every key written with `Encoder::key` has to be followed by its
+/// value, and inside of maps every scalar key pushed has to be followed by its
+/// value. Anything else returns an `io::Error` of kind `InvalidInput`.
+///
+/// Containers opened with the `begin_` functions do not know their length
+/// ahead of time, so they always end with a closing tag. The string table of
+/// `EncodeOptions` is only used if the first thing written is a whole `Value`
+/// passed to `Encoder::push`.
+///
+/// Every tag and value is passed to the writer as soon as it is encoded, a
+/// few bytes at a time, so a file or socket should be wrapped in a
+/// `std::io::BufWriter` first. `Encoder::finish` flushes it.
+///
+/// ```
+/// use emp::encode::stream::Encoder;
+/// use emp::value::Value;
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// let path = std::env::temp_dir().join("emp-stream-encoder-example");
+/// let mut encoder = Encoder::new(BufWriter::new(File::create(&path)?));
+/// encoder.begin_array()?;
+/// for i in 0..1000 {
+///     encoder.push(&Value::Int32(i))?;
+/// }
+/// encoder.finish()?;
+/// # std::fs::remove_file(&path)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Encoder<W: Write> {
+    writer: W,
+    options: EncodeOptions,
+    strings: HashMap<String, usize>,
+    // Every container which is still open, innermost last.
+    open: Vec<Open>,
+    started: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Array,
+    Object,
+    Map,
+}
+
+// A container which is still open.
+struct Open {
+    kind: Kind,
+    // Whether a key has been written and its value has to come next.
+    value_next: bool,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder writing into `writer` using
+    /// `EncodeOptions::default()`.
+    pub fn new(writer: W) -> Encoder<W> {
+        Encoder::with_options(writer, EncodeOptions::default())
+    }
+
+    /// Creates an encoder writing into `writer` using the given
+    /// `emp::encode::EncodeOptions`.
+    pub fn with_options(writer: W, options: EncodeOptions) -> Encoder<W> {
+        Encoder {
+            writer,
+            options,
+            strings: HashMap::new(),
+            open: vec![],
+            started: false,
+        }
+    }
+
+    /// Writes a whole `emp::value::Value`, either as the data itself or as
+    /// the next child of the innermost open container.
+    pub fn push(&mut self, val: &Value) -> io::Result<()> {
+        self.start(Some(val))?;
+        self.next(val.is_scalar())?;
+
+        let state = State {
+            options: &self.options,
+            strings: std::mem::take(&mut self.strings),
+        };
+        let result = write_value(val, &state, &mut self.writer);
+        self.strings = state.strings;
+
+        result
+    }
+
+    /// Writes the key of the next field of the innermost open object.
+    pub fn key(&mut self, key: &str) -> io::Result<()> {
+        match self.open.last_mut() {
+            Some(Open {
+                kind: Kind::Object,
+                value_next,
+            }) => {
+                if *value_next {
+                    return Err(misuse("The value of the last key has not been written"));
+                }
+                *value_next = true;
+            }
+            _ => return Err(misuse("Keys can only be written inside of an object")),
+        }
+
+        let state = State {
+            options: &self.options,
+            strings: std::mem::take(&mut self.strings),
+        };
+        let result = write_string(key, &state, &mut self.writer);
+        self.strings = state.strings;
+
+        result
+    }
+
+    /// Opens an array, every value pushed until the matching `Encoder::end`
+    /// is placed inside of it.
+    pub fn begin_array(&mut self) -> io::Result<()> {
+        self.begin(Kind::Array, &[constants::ARRAY_START])
+    }
+
+    /// Opens an object, which is filled with calls to `Encoder::key` each
+    /// followed by a value until the matching `Encoder::end`.
+    pub fn begin_object(&mut self) -> io::Result<()> {
+        self.begin(Kind::Object, &[constants::DICTIONARY_START])
+    }
+
+    /// Opens a map, which is filled by pushing each key followed by its value
+    /// until the matching `Encoder::end`.
+    pub fn begin_map(&mut self) -> io::Result<()> {
+        self.begin(
+            Kind::Map,
+            &[
+                constants::EXTENSION | constants::EXTENSION_MAP << 4,
+                constants::DICTIONARY_START,
+            ],
+        )
+    }
+
+    /// Closes the innermost open container.
+    pub fn end(&mut self) -> io::Result<()> {
+        match self.open.last() {
+            Some(Open {
+                value_next: true, ..
+            }) => Err(misuse("The value of the last key has not been written")),
+            Some(Open { kind, .. }) => {
+                let end = match kind {
+                    Kind::Array => constants::ARRAY_END,
+                    _ => constants::DICTIONARY_END,
+                };
+                self.open.pop();
+                self.writer.write_all(&[end])
+            }
+            None => Err(misuse("There is no open container to end")),
+        }
+    }
+
+    /// Closes every container which is still open, flushes the writer and
+    /// returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        while !self.open.is_empty() {
+            self.end()?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn begin(&mut self, kind: Kind, tags: &[u8]) -> io::Result<()> {
+        self.start(None)?;
+        self.next(false)?;
+        self.writer.write_all(tags)?;
+        self.open.push(Open {
+            kind,
+            value_next: false,
+        });
+        Ok(())
+    }
+
+    // Checks that a value can be written next in the innermost open container, keys and values have to take turns
+    // inside of objects and maps.
+    fn next(&mut self, scalar: bool) -> io::Result<()> {
+        let open = match self.open.last_mut() {
+            Some(o) => o,
+            None => return Ok(()),
+        };
+
+        match open.kind {
+            Kind::Array => {}
+            Kind::Object if !open.value_next => {
+                return Err(misuse("Values inside of an object need a key first"));
+            }
+            Kind::Map if !open.value_next && !scalar => {
+                return Err(misuse("The keys of a map have to be scalar values"));
+            }
+            _ => open.value_next = !open.value_next,
+        }
+
+        Ok(())
+    }
+
+    // Writes the header before the first value, and makes sure nothing is written after the data is complete.
+    fn start(&mut self, val: Option<&Value>) -> io::Result<()> {
+        if self.started {
+            if self.open.is_empty() {
+                return Err(misuse("The data has already been written"));
+            }
+            return Ok(());
+        }
+
+        self.started = true;
+
+        let mut state = State {
+            options: &self.options,
+            strings: HashMap::new(),
+        };
+        write_header(val, &mut state, &mut self.writer)?;
+        self.strings = state.strings;
+
+        Ok(())
+    }
+}
+
+fn misuse(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
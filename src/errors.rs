@@ -1,6 +1,8 @@
 //! When Decoding or Parsing encounters something wrong
 
 use crate::value;
+use crate::value::path::Path;
+use crate::value::schema::Schema;
 use std::fmt;
 
 #[derive(Debug)]
@@ -11,6 +13,58 @@ pub enum DecodeError {
     UnmatchedKeyError(std::string::String),
     StringDecodeError(std::str::Utf8Error),
     InvalidKeyError(value::Value),
+    StringReferenceError(u64),
+    UnsupportedFeatureError(u8),
+    SchemaError(SchemaError),
+    EncryptedError,
+    AuthenticationError,
+    SignatureError,
+    IOError(std::io::Error),
+    DepthLimitError(usize),
+    ElementLimitError(usize),
+    StringLengthLimitError(usize),
+    AllocationLimitError(usize),
+    LocatedError(Box<DecodeError>, Location),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// Where in the data a `DecodeError` happened.
+pub struct Location {
+    /// How many bytes from the start of the data the error is at.
+    pub offset: u64,
+    /// The path to the value the error is in, such as `$.players[2].name`.
+    /// Errors in the header or in a key have the path of the container
+    /// they are in.
+    pub path: Path,
+}
+
+impl DecodeError {
+    /// Returns where in the data the error happened.
+    ///
+    /// Every decoder returns the errors it finds in the data as a
+    /// `DecodeError::LocatedError`, so this only returns `None` for errors
+    /// which are not about the data, such as a file which can not be opened.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            DecodeError::LocatedError(_, l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its location, for matching on what went
+    /// wrong.
+    pub fn cause(&self) -> &DecodeError {
+        match self {
+            DecodeError::LocatedError(e, _) => e.cause(),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte {} ({})", self.offset, self.path)
+    }
 }
 
 #[derive(Debug)]
@@ -21,6 +75,10 @@ pub enum ParseError {
     UnexpectedTokenError(std::string::String),
     InvalidKeyError(value::Value),
     InvalidNumberError(char),
+    DepthLimitError(usize),
+    ElementLimitError(usize),
+    StringLengthLimitError(usize),
+    AllocationLimitError(usize),
 }
 
 impl fmt::Display for DecodeError {
@@ -31,13 +89,37 @@ impl fmt::Display for DecodeError {
 
 fn display_decode_error(val: &DecodeError) -> String {
     match val {
-        DecodeError::UnexpectedByteError(b, l) => {
-            return format!("Unexpected Byte: 0x{:x?} at location {}", b, l)
+        DecodeError::UnexpectedByteError(b, _) => format!("Unexpected Byte: 0x{:02x}", b),
+        DecodeError::EOFError => "Unexpected EOF".to_owned(),
+        DecodeError::UnmatchedKeyError(k) => format!("Unmatched Key: `{}`", k),
+        DecodeError::StringDecodeError(e) => format!("Unable to decode string data: {}", e),
+        DecodeError::InvalidKeyError(k) => format!("Invalid Key: `{}`", k),
+        DecodeError::StringReferenceError(i) => {
+            format!("String reference {} is not in the string table", i)
+        }
+        DecodeError::UnsupportedFeatureError(f) => {
+            format!("Unsupported features: 0b{:08b}", f)
+        }
+        DecodeError::SchemaError(e) => format!("Unable to read schema: {}", e),
+        DecodeError::EncryptedError => "Data is encrypted".to_owned(),
+        DecodeError::AuthenticationError => {
+            "Unable to decrypt data (Wrong key or the data was modified)".to_owned()
+        }
+        DecodeError::SignatureError => {
+            "Invalid signature (Wrong key or the data was modified)".to_owned()
         }
-        DecodeError::EOFError => return "Unexpected EOF".to_owned(),
-        DecodeError::UnmatchedKeyError(k) => return format!("Unmatched Key: `{}`", k),
-        DecodeError::StringDecodeError(e) => return format!("Unable to decode string data: {}", e),
-        DecodeError::InvalidKeyError(k) => return format!("Invalid Key: `{}`", k),
+        DecodeError::IOError(e) => format!("Unable to read data: {}", e),
+        DecodeError::DepthLimitError(l) => {
+            format!("Data is nested more than {} levels deep", l)
+        }
+        DecodeError::ElementLimitError(l) => format!("Data has more than {} values", l),
+        DecodeError::StringLengthLimitError(l) => {
+            format!("String is longer than {} bytes", l)
+        }
+        DecodeError::AllocationLimitError(l) => {
+            format!("Data needs more than {} bytes of memory", l)
+        }
+        DecodeError::LocatedError(e, l) => format!("{} at {}", e, l),
     }
 }
 
@@ -49,12 +131,121 @@ impl fmt::Display for ParseError {
 
 fn display_parse_error(val: &ParseError) -> String {
     match val {
-        ParseError::UnexpectedCharacterError(c) => return format!("Unexpected Character: '{}'", c),
-        ParseError::UnexpectedTokenError(c) => return format!("Unexpected Token: \"{}\"", c),
-        ParseError::EOFError => return "Unexpected EOF".to_owned(),
-        ParseError::InvalidKeyError(k) => return format!("Invalid Key: `{}`", k),
+        ParseError::UnexpectedCharacterError(c) => format!("Unexpected Character: '{}'", c),
+        ParseError::UnexpectedTokenError(c) => format!("Unexpected Token: \"{}\"", c),
+        ParseError::EOFError => "Unexpected EOF".to_owned(),
+        ParseError::InvalidKeyError(k) => format!("Invalid Key: `{}`", k),
         ParseError::InvalidNumberError(c) => {
-            return format!("Could not parse number with signifier: `{}`", c)
+            format!("Could not parse number with signifier: `{}`", c)
+        }
+        ParseError::DepthLimitError(l) => {
+            format!("Data is nested more than {} levels deep", l)
+        }
+        ParseError::ElementLimitError(l) => format!("Data has more than {} values", l),
+        ParseError::StringLengthLimitError(l) => {
+            format!("String is longer than {} bytes", l)
+        }
+        ParseError::AllocationLimitError(l) => {
+            format!("Data needs more than {} bytes of memory", l)
+        }
+    }
+}
+
+#[derive(Debug)]
+/// This represents all possible errors when patching bytecode data in place
+pub enum PatchError {
+    DecodeError(DecodeError),
+    IOError(std::io::Error),
+    ParseError(ParseError),
+    PathNotFoundError(std::string::String),
+    TypeMismatchError(value::Value),
+    SizeError(value::Value),
+    SignedError,
+    EncryptedError,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", display_patch_error(self))
+    }
+}
+
+fn display_patch_error(val: &PatchError) -> String {
+    match val {
+        PatchError::DecodeError(e) => format!("Unable to decode data: {}", e),
+        PatchError::IOError(e) => format!("Unable to access file: {}", e),
+        PatchError::ParseError(e) => format!("Unable to parse path: {}", e),
+        PatchError::PathNotFoundError(p) => format!("No value at path: `{}`", p),
+        PatchError::TypeMismatchError(v) => {
+            format!(
+                "Value `{}` does not match the type of the existing value",
+                v
+            )
+        }
+        PatchError::SizeError(v) => {
+            format!("Value `{}` does not fit in the existing value's bytes", v)
+        }
+        PatchError::SignedError => {
+            "Cannot patch signed data, the signature would no longer match".to_owned()
+        }
+        PatchError::EncryptedError => "Cannot patch encrypted data".to_owned(),
+    }
+}
+
+#[derive(Debug)]
+/// This represents all possible errors when sending values with
+/// `emp::codec::Codec`
+pub enum CodecError {
+    DecodeError(DecodeError),
+    IOError(std::io::Error),
+    FrameSizeError(usize),
+}
+
+impl From<std::io::Error> for CodecError {
+    fn from(e: std::io::Error) -> Self {
+        CodecError::IOError(e)
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", display_codec_error(self))
+    }
+}
+
+fn display_codec_error(val: &CodecError) -> String {
+    match val {
+        CodecError::DecodeError(e) => format!("Unable to decode frame: {}", e),
+        CodecError::IOError(e) => format!("Unable to use stream: {}", e),
+        CodecError::FrameSizeError(l) => {
+            format!("Frame of at least {} bytes is larger than allowed", l)
+        }
+    }
+}
+
+#[derive(Debug)]
+/// This represents all possible errors when reading a schema or checking a
+/// value against one
+pub enum SchemaError {
+    InvalidSchemaError(value::Value),
+    TypeMismatchError(Path, Schema),
+    MissingFieldError(Path),
+    UnknownFieldError(Path),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", display_schema_error(self))
+    }
+}
+
+fn display_schema_error(val: &SchemaError) -> String {
+    match val {
+        SchemaError::InvalidSchemaError(v) => format!("Invalid Schema: `{}`", v),
+        SchemaError::TypeMismatchError(p, s) => {
+            format!("Value at `{}` does not match schema `{}`", p, s)
         }
+        SchemaError::MissingFieldError(p) => format!("Missing Field: `{}`", p),
+        SchemaError::UnknownFieldError(p) => format!("Unknown Field: `{}`", p),
     }
 }
@@ -12,6 +12,7 @@
 //! pub enum Value {
 //!   Null,
 //!   Object(std::collections::HashMap<std::string::String, Value>),
+//!   Map(std::vec::Vec<(Value, Value)>),
 //!   Array(std::vec::Vec<Value>),
 //!   String(std::string::String),
 //!   Bit(bool),
@@ -29,6 +30,34 @@
 //!
 //! Using `emp::encode::encode` you can pass in an `emp::value::Value` and get an `std::vec::Vec<u8>` back, this will encode the data in the `Value` into the bytes that you can then write to a file.
 //!
+//! If your data repeats the same strings many times you can use `emp::encode::encode_with_string_table` instead, passing in the `Value` and a length threshold. Every string at least that long which appears more than once is stored a single time in a string table at the start of the data and referenced by index everywhere else. `emp::decode::decode` reads the string table automatically.
+//!
+//! To choose which optimizations the encoder uses, pass an `emp::encode::EncodeOptions` to `emp::encode::encode_with` along with a `&Value`. `EncodeOptions::default()` matches `encode`, `EncodeOptions::speed()` skips the optimizations that cost time while encoding and `EncodeOptions::size()` turns everything on, including the string table. Any options the decoder needs to know about are recorded at the start of the data, so `decode` never needs to be configured.
+//!
+//! To budget space before serializing, `emp::encode::encoded_len` returns the number of bytes `encode` would produce for a `&Value` and `emp::encode::encoded_len_with` does the same for a set of `EncodeOptions`. The value is run through the encoder with the bytes counted rather than stored, so the length always matches.
+//!
+//! To keep a `Value` around after encoding it, or to reuse the same buffer for many values, `emp::encode::encode_into` borrows the `&Value` and appends its bytecode to a `&mut Vec<u8>`, and `emp::encode::encode_into_with` does the same with a set of `EncodeOptions`. Every value is written straight into the buffer, so encoding does not allocate anything apart from growing it. `cargo bench` compares this against `encode`, and checks that `encode_into` makes no allocations at all once the buffer is large enough.
+//!
+//! ### Streaming
+//!
+//! To write large data without holding all of the bytecode in memory, `emp::encode::stream::Encoder` writes straight into anything implementing `std::io::Write`, such as a file or a socket. It hands the writer a few bytes at a time, so wrap files and sockets in a `std::io::BufWriter`, which `finish` flushes. Create one with `Encoder::new` or `Encoder::with_options`, then either pass a whole `&Value` to `push`, or build the data up a piece at a time with `begin_array`, `begin_object` and `begin_map`, writing object keys with `key`, pushing children with `push` and closing each container with `end`. `finish` closes anything still open and hands the writer back. Containers opened this way always end with a closing tag as their length is not known ahead of time, and the string table is only used when a whole `Value` is pushed.
+//!
+//! ### Async
+//!
+//! With the `async` feature, `emp::codec::Codec` implements the `Encoder` and `Decoder` traits from `tokio_util::codec`, so `Value`s can be sent over a `TcpStream` or any other async byte stream using `tokio_util::codec::Framed`. Create one with `Codec::new`, passing the longest frame in bytes that should be accepted, or with `Codec::with_options` to also choose the `EncodeOptions` to encode with. Each frame is just the EMP bytecode of a value, decoded with a `PushDecoder` as it arrives, and any frame longer than the maximum is refused with `emp::errors::CodecError::FrameSizeError` as soon as that many bytes of it have come in, so a peer can not make the decoder hold more than that much data.
+//!
+//! ### Metadata
+//!
+//! Setting the `metadata` field of `EncodeOptions` to a `HashMap<String, Value>` stores it as an object before the data, which is useful for things like the author, the application that created the data, a timestamp or any other tags. Using `emp::decode::decode_metadata` you can read it back as an `Option<HashMap<String, Value>>` without decoding the rest of the data, `decode` skips over it.
+//!
+//! ### Schemas
+//!
+//! An `emp::value::schema::Schema` describes what a `Value` should look like, including the width of every number and a description of each field of an object. You can create one by hand or with `Schema::infer`, and check a `Value` against it with `Schema::validate`, which returns an `emp::errors::SchemaError` pointing at the first value that does not match. Setting the `schema` field of `EncodeOptions` stores the schema before the data, and `emp::decode::decode_schema` reads it back without decoding the rest of the data, so other tools can check or display the data without knowing anything about it beforehand.
+//!
+//! ### Patching
+//!
+//! Using `emp::encode::patch::patch` you can pass in a `&mut [u8]`, a path such as `"players[3].score"` and a `Value` to overwrite a Bit, Boolean or number in the bytecode without re-encoding anything else, `emp::encode::patch::patch_file` does the same thing directly on a file. The new value has to be the same type as the old one and fit into the same number of bytes, so data which is going to be patched should be encoded with `EncodeOptions::fixed_width()`, which gives every number of a type the same length. Signed and encrypted data can not be patched, since the signature would no longer match and the encrypted bytes are not EMP bytecode, so these return `PatchError::SignedError` and `PatchError::EncryptedError`.
+//!
 //! ### Decoding
 //!
 //! Using `emp::decode::decode` you can pass in a `&[u8]` and get an `Result<emp::value::Value, emp::errors::DecodeError>` in return.
@@ -37,19 +66,70 @@
 //!
 //! ```
 //! pub enum DecodeError {
-//!   UnexpectedByte(u8, u64),
+//!   UnexpectedByteError(u8, u64),
 //!   EOFError,
-//!   UnmatchedKey(std::string::String),
+//!   UnmatchedKeyError(std::string::String),
 //!   StringDecodeError(std::str::Utf8Error),
+//!   InvalidKeyError(emp::value::Value),
+//!   StringReferenceError(u64),
+//!   UnsupportedFeatureError(u8),
+//!   SchemaError(emp::errors::SchemaError),
+//!   EncryptedError,
+//!   AuthenticationError,
+//!   SignatureError,
+//!   IOError(std::io::Error),
+//!   DepthLimitError(usize),
+//!   ElementLimitError(usize),
+//!   StringLengthLimitError(usize),
+//!   AllocationLimitError(usize),
+//!   LocatedError(Box<DecodeError>, emp::errors::Location),
 //! }
 //! ```
 //!
-//! You can also use `emp::decode::decode_safe` and pass in the same data to decode the data in the same way but if there is an `Err` it instead returns `emp::value::Value::Null`.
+//! Every decoder returns the errors it finds in the data as a `DecodeError::LocatedError`, which holds the error along with an `emp::errors::Location` giving the offset of the byte it is at from the start of the data and the path of the value it is in, such as `$.players[2].name`. Printing one gives both, such as `Unexpected EOF at byte 1432 ($.players[2].name)`, which is enough to find the problem in a bug report. `DecodeError::cause` returns the error without its location for matching on what went wrong, and `DecodeError::location` returns the location. The offset of an `UnexpectedByteError` is the offset of that byte, and the streaming and push decoders count offsets from the start of everything they have read. This changed in 2.0, `CHANGELOG.md` has notes on moving code which matched on these errors over from 1.x.
+//!
+//! You can also use `emp::decode::decode_safe` and pass in the same data to decode the data in the same way but if there is an `Err` it instead returns `emp::value::Value::Null`. None of the decoders panic on data which is malformed or cut short, anything which is not valid EMP bytecode gives a `DecodeError`, so data from untrusted sources can be passed to them directly. The `fuzz` directory holds `cargo fuzz` targets for the decoders, `emp::value::parse::from_str` and `emp::value::path::from_str` along with a seed corpus for each, which can be run with `cargo fuzz run decode`.
+//!
+//! `emp::decode::decode` has no limits, since it keeps the containers it is in on a stack of its own instead of recursing, so data nested however deeply still decodes. For data from untrusted sources `emp::decode::decode_with_limits` takes an `emp::decode::DecodeLimits` to limit how deeply values can be nested, how many values there can be, how long each string can be and roughly how much memory the decoded value can take up, going over any of them gives one of the `Limit` variants of `DecodeError`. The header and string table count towards the same limits as the data. `DecodeLimits::default()` only limits values to being nested 128 levels deep, which is what the streaming and borrowed decoders use as they recurse once for each container, while the parallel decoder has no limits the same as `decode`, and `DecodeLimits::none()` turns every limit off. `emp::decode::stream::Decoder::with_limits`, `emp::decode::push::PushDecoder::with_limits`, `emp::decode::borrowed::decode_borrowed_with_limits` and `emp::decode::parallel::decode_parallel_with_limits` take limits of their own. Dropping or printing a `Value` recurses as well, so values from untrusted sources should still be decoded with a depth limit.
+//!
+//! The decoder goes straight from the identifier of each value to the code for its type, and keeps the containers it is inside of on a stack of its own rather than recursing, so deeply nested data can not overflow the call stack. `cargo bench --bench decode` measures its throughput on a sample save file, which went from 20.7 to 63.6 MiB/s with the default options, 23.2 to 56.5 MiB/s with `EncodeOptions::speed()` and 13.6 to 33.9 MiB/s with `EncodeOptions::size()` compared to the decoder which tried each type in turn.
+//!
+//! To read a single value, `emp::decode::decode_path` takes the bytes and an `emp::value::path::Path` such as `players[3].name` (parsed with `emp::value::path::from_str`) and returns an `Option<Value>`, skipping over everything that is not on the way to it without decoding it.
+//!
+//! To decode without reading all of the data into memory first, `emp::decode::stream::Decoder` reads from anything implementing `std::io::Read`, such as a `BufReader<File>` or a `TcpStream`. `Decoder::decode` returns the next `Value` in the stream, and the decoder can also be used as an `Iterator` over every value written one after another. Errors from the reader are returned as `emp::errors::DecodeError::IOError`.
+//!
+//! When data arrives in chunks, such as from a network connection, `emp::decode::push::PushDecoder` can decode it as it comes in. Pass each chunk to `PushDecoder::feed` and call `PushDecoder::next_value`, which returns `Ok(Some(value))` once a value is complete, `Ok(None)` if it needs more data and a `DecodeError` if the data is invalid. The decoder remembers how far it got, so nothing is decoded twice however the data is split up.
+//!
+//! If you only need a few fields out of a large amount of data, `emp::decode::events::EventReader` reads the bytecode as a series of `emp::decode::events::Event`s, such as `StartObject`, `Key`, `Int32` and `EndArray`, each with the offset of the byte it starts at, without building any `Value`s. Values you do not need can be stepped over with `EventReader::skip_value`.
+//!
+//! `emp::decode::lazy::LazyValue` wraps the bytes of the data and only decodes the parts you use. `LazyValue::get` and `LazyValue::index` return the child at a key or index as another `LazyValue`, finding where each child of a container starts the first time and keeping that around, so `get("player")` followed by `get("inventory")` costs about as much as the data along the way rather than the whole file. `LazyValue::value` decodes a value into a `&Value`, keeping it for next time.
+//!
+//! For read-only code where speed matters, `emp::decode::borrowed::decode_borrowed` decodes into an `emp::decode::borrowed::ValueRef<'a>` which borrows every string and object key as a `&'a str` from the bytes passed in instead of copying them, and keeps the fields of objects in a `Vec` rather than a `HashMap`. `ValueRef::to_value` turns it into an owned `Value` when needed.
+//!
+//! To check data from an untrusted source before doing anything else with it, `emp::decode::validate::validate` walks the whole of it without building any values and returns either the `emp::errors::DecodeError` for the first problem or `emp::decode::validate::Stats` with the `depth` of the deepest container, the number of `elements` and the `length` of the data in bytes. Containers are tracked on a stack of their own instead of by recursing, so even very deeply nested data can be checked without overflowing the stack.
+//!
+//! With the `mmap` feature, `emp::decode::mmap::MappedFile::open` maps a file into memory instead of reading all of it, so only the pages of the file which are actually read are loaded. `MappedFile::lazy` gives a `LazyValue` and `MappedFile::borrowed` a `ValueRef` over the file without copying it, and `MappedFile::bytes` gives the whole file as a `&[u8]` for any of the other decoders. The file should not be changed by anything else while it is mapped. The `emp` command line utility also maps the file it reads with `--read` when this feature is enabled.
+//!
+//! ### Encryption
+//!
+//! With the `encryption` feature you can use `emp::encode::encrypt::encrypt` to encrypt EMP bytecode with ChaCha20-Poly1305 using a 32 byte key, or `emp::encode::encrypt::encode_encrypted` to encode and encrypt a `Value` in one go. `emp::decode::decrypt::decrypt` and `emp::decode::decrypt::decode_encrypted` reverse this, returning `emp::errors::DecodeError::AuthenticationError` if the key is wrong or the data has been tampered with. Passing encrypted data straight to `decode` returns `DecodeError::EncryptedError`. The `emp` command line utility only has its `--encrypt` and `--decrypt` flags when this feature is enabled.
+//!
+//! ### Signatures
+//!
+//! With the `signatures` feature you can sign data with Ed25519 using a 32 byte secret key, `emp::encode::sign::public_key` gives the public key needed to check the signature. `emp::encode::sign::encode_signed` encodes a `Value` with `EncodeOptions::canonical` turned on, so that object keys are always written in the same order, and embeds the signature in front of the data, while `emp::encode::sign::encode_signed_detached` returns the signature separately. `emp::decode::verify::decode_verified` and `emp::decode::verify::decode_verified_detached` check the signature before decoding, returning `emp::errors::DecodeError::SignatureError` if it does not match. `decode` skips over embedded signatures without checking them. The `emp` command line utility only has its `--sign`, `--verify` and `--public_key` flags when this feature is enabled.
+//!
+//! ### Parallel
+//!
+//! With the `parallel` feature you can use `emp::encode::parallel::encode_parallel` and `emp::decode::parallel::decode_parallel` to split the work for large data between the threads of the rayon thread pool. The children of big arrays, objects and maps are encoded or decoded separately and joined back together in order, so `encode_parallel` gives exactly the same bytes as `emp::encode::encode_with` for the same `EncodeOptions`, and `decode_parallel` gives the same `Value` or `DecodeError` as `decode`. Small containers are handled on the thread that reaches them, as splitting them up costs more than it saves.
 //!
 //! ### Parsing
 //!
 //! Using the `emp::value::parse::from_str` function you can pass in a `&str` to convert it into a `Result<emp::value::Value, emp::errors::ParseError>`. Alternatively you can use `emp::value::parse::from_str_safe` and pass in the same thing to get a `emp::value::Value`, if an error is encounted it returns a `emp::value::Value::Null` instead.
 //!
+//! Objects and Maps are written the same way, as keys and values inside of `{}`. Text with any key which is not a string, such as `{1: "a", 2b: "b"}`, parses into a `Map`, and text with only string keys parses into an `Object`. This means that a `Map` with only string keys, or with no keys at all, is displayed as text which parses back into an `Object` holding the same keys and values.
+//!
+//! `from_str` has no limits either, but the parser recurses into each array and object, so strings from untrusted sources should be parsed with `emp::value::parse::from_str_with_limits`. It takes an `emp::value::parse::ParseLimits` with the same limits as `DecodeLimits`, and `ParseLimits::default()` stops with a `ParseError` once arrays and objects are nested more than 128 levels deep. Going over a limit gives one of the `Limit` variants of `ParseError`, and as with decoding, dropping or printing a very deeply nested `Value` recurses too.
+//!
 //! `ParseError` is an enum that is as so:
 //!
 //! ```
@@ -57,8 +137,12 @@
 //!    EOFError,
 //!    UnexpectedCharacterError(char),
 //!    UnexpectedTokenError(std::string::String),
-//!    InvalidKeyError(value::Value),
+//!    InvalidKeyError(emp::value::Value),
 //!    InvalidNumberError(char),
+//!    DepthLimitError(usize),
+//!    ElementLimitError(usize),
+//!    StringLengthLimitError(usize),
+//!    AllocationLimitError(usize),
 //! }
 //! ```
 //!
@@ -68,6 +152,8 @@
 //!
 //! This crate is compatible with `serde_json`, by using `emp::value::json::from_json` you can convert a `serde_json::Value` to an `emp::value::Value`, vice versa for `emp::value::json::to_json`.
 //!
+//! Since JSON only has string keys, `to_json` turns the keys of a `Map` into strings, strings are kept as they are and every other key becomes its JSON representation, so `{1: "a"}` becomes `{"1": "a"}`. Converting that back with `from_json` gives an `Object`, not a `Map`.
+//!
 //! #### Encoding
 //!
 //! Using `emp::encode::json::encode_json` you can encode a `serde_json::Value` directly into `emp` bytecode.
@@ -89,8 +175,11 @@
 //! Null: `null`
 //! Array: Values separated by commas in square brackets
 //! Object: String then a colon then a Value, separated by commas in curly brackets
+//! Map: The same as an Object, but with any scalar Value (not null, an Array, an Object or a Map) as a key, such as `{1: "a", 2: "b"}`
 //!
 
+#[cfg(feature = "async")]
+pub mod codec;
 pub mod constants;
 pub mod decode;
 pub mod encode;
@@ -6,16 +6,36 @@ pub mod errors;
 pub mod value;
 
 use argument::ArgumentOptions;
-use serde_json;
 use std::env;
 use std::fs;
 
 fn main() {
     match argument::resolve_arguments(env::args()) {
-        ArgumentOptions::ReadFromFile(file) => match fs::read(file) {
+        ArgumentOptions::ReadFromFile(file) => match read_file(&file) {
             Ok(data) => {
-                match decode::decode(&data) {
-                    Ok((v, _)) => println!("Extracted data: {}", v),
+                let data = data.as_ref();
+                match decode::decode_metadata(data) {
+                    Ok(Some(m)) => println!("Metadata: {}", value::Value::Object(m)),
+                    Ok(None) => {}
+                    Err(v) => println!("An error occured while decoding the metadata: {}", v),
+                };
+                let schema = match decode::decode_schema(data) {
+                    Ok(s) => s,
+                    Err(v) => {
+                        println!("An error occured while decoding the schema: {}", v);
+                        None
+                    }
+                };
+                if let Some(s) = &schema {
+                    println!("Schema: {}", s);
+                }
+                match decode::decode(data) {
+                    Ok((v, _)) => {
+                        println!("Extracted data: {}", v);
+                        if let Some(Err(e)) = schema.map(|s| s.validate(&v)) {
+                            println!("The data does not match its schema: {}", e);
+                        }
+                    }
                     Err(v) => println!("An error occured while decoding: {}", v),
                 };
             }
@@ -33,7 +53,7 @@ fn main() {
                 }
             };
 
-            fs::write(file, &encode::encode(data))
+            fs::write(file, encode::encode(data))
                 .expect("Unable to write to file (Do you have permission?)");
         }
         ArgumentOptions::FromJSON(json) => {
@@ -53,6 +73,68 @@ fn main() {
                 e
             ),
         },
+        #[cfg(feature = "encryption")]
+        ArgumentOptions::Encrypt(file, key) => {
+            let key = match read_key(&key) {
+                Some(k) => k,
+                None => return,
+            };
+            match fs::read(&file) {
+                Ok(data) => fs::write(file, encode::encrypt::encrypt(&data, &key))
+                    .expect("Unable to write to file (Do you have permission?)"),
+                Err(_) => println!("Unable to read file (Are you sure it exists?)"),
+            }
+        }
+        #[cfg(feature = "encryption")]
+        ArgumentOptions::Decrypt(file, key) => {
+            let key = match read_key(&key) {
+                Some(k) => k,
+                None => return,
+            };
+            match fs::read(&file) {
+                Ok(data) => match decode::decrypt::decrypt(&data, &key) {
+                    Ok(d) => fs::write(file, d)
+                        .expect("Unable to write to file (Do you have permission?)"),
+                    Err(e) => println!("An error occured while decrypting: {}", e),
+                },
+                Err(_) => println!("Unable to read file (Are you sure it exists?)"),
+            }
+        }
+        #[cfg(feature = "signatures")]
+        ArgumentOptions::Sign(file, key) => {
+            let key = match read_key(&key) {
+                Some(k) => k,
+                None => return,
+            };
+            match fs::read(&file) {
+                Ok(data) => fs::write(file, encode::sign::sign(&data, &key))
+                    .expect("Unable to write to file (Do you have permission?)"),
+                Err(_) => println!("Unable to read file (Are you sure it exists?)"),
+            }
+        }
+        #[cfg(feature = "signatures")]
+        ArgumentOptions::Verify(file, key) => {
+            let key = match read_key(&key) {
+                Some(k) => k,
+                None => return,
+            };
+            match fs::read(&file) {
+                Ok(data) => match decode::verify::decode_verified(&data, &key) {
+                    Ok(v) => println!("Verified data: {}", v),
+                    Err(e) => println!("An error occured while verifying: {}", e),
+                },
+                Err(_) => println!("Unable to read file (Are you sure it exists?)"),
+            }
+        }
+        #[cfg(feature = "signatures")]
+        ArgumentOptions::PublicKey(key, file) => {
+            let key = match read_key(&key) {
+                Some(k) => k,
+                None => return,
+            };
+            fs::write(file, encode::sign::public_key(&key))
+                .expect("Unable to write to file (Do you have permission?)");
+        }
         ArgumentOptions::Version => println!("EMP {}", env!("CARGO_PKG_VERSION")),
         ArgumentOptions::Help => {
             println!("┌───────────────────────────────────┐");
@@ -61,7 +143,8 @@ fn main() {
             println!("│                                   │");
             println!("│[-r | --read] <filename>: Reads the│");
             println!("│EMP bytecode and prints it out as a│");
-            println!("│EMP string.                        │");
+            println!("│EMP string, along with its metadata│");
+            println!("│and schema if it has any.          │");
             println!("│                                   │");
             println!("│[-w | --write] <filename> <emp>:   │");
             println!("|Writes the EMP data into the file  │");
@@ -84,9 +167,61 @@ fn main() {
             println!("│NOTE: Make sure your EMP data is in│");
             println!("│quotes.                            │");
             println!("│                                   │");
+            #[cfg(feature = "encryption")]
+            {
+                println!("│[-e | --encrypt] <filename> <key>: │");
+                println!("│Encrypts the EMP file using the 32 │");
+                println!("│byte key stored in the key file.   │");
+                println!("│                                   │");
+                println!("│[-d | --decrypt] <filename> <key>: │");
+                println!("│Decrypts the EMP file using the 32 │");
+                println!("│byte key stored in the key file.   │");
+                println!("│                                   │");
+            }
+            #[cfg(feature = "signatures")]
+            {
+                println!("│[-s | --sign] <filename> <key>:    │");
+                println!("│Signs the EMP file using the 32    │");
+                println!("│byte secret key in the key file.   │");
+                println!("│                                   │");
+                println!("│[-vs | --verify] <filename> <key>: │");
+                println!("│Checks the signature of the EMP    │");
+                println!("│file using the 32 byte public key  │");
+                println!("│in the key file and prints it out. │");
+                println!("│                                   │");
+                println!("│[-pk | --public_key] <key> <file>: │");
+                println!("│Writes the public key for the      │");
+                println!("│secret key in the key file.        │");
+                println!("│                                   │");
+            }
             println!("│[-v | --version]: Prints out the   │");
             println!("│version of EMP you are using       │");
             println!("└───────────────────────────────────┘");
         }
     }
 }
+
+// Large files are mapped into memory rather than read when the `mmap` feature is enabled.
+#[cfg(feature = "mmap")]
+fn read_file(file: &str) -> Result<decode::mmap::MappedFile, errors::DecodeError> {
+    decode::mmap::MappedFile::open(file)
+}
+
+#[cfg(not(feature = "mmap"))]
+fn read_file(file: &str) -> std::io::Result<Vec<u8>> {
+    fs::read(file)
+}
+
+#[cfg(any(feature = "encryption", feature = "signatures"))]
+fn read_key(file: &str) -> Option<[u8; 32]> {
+    use std::convert::TryInto;
+
+    match fs::read(file) {
+        Ok(key) => match key.try_into() {
+            Ok(k) => return Some(k),
+            Err(_) => println!("The key file has to contain exactly 32 bytes"),
+        },
+        Err(_) => println!("Unable to read key file (Are you sure it exists?)"),
+    }
+    None
+}
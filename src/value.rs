@@ -2,6 +2,8 @@
 
 pub mod json;
 pub mod parse;
+pub mod path;
+pub mod schema;
 
 use crate::constants;
 use std::fmt;
@@ -13,6 +15,10 @@ use std::fmt;
 /// Object: A `HashMap` with a `std::string::String` as the key and a `Value`
 /// as the object. You cannot use a `Value` as a key because `HashMap` does
 /// not derive `Hash`.
+/// Map: A `std::vec::Vec` of key and value pairs, where the keys can be any
+/// scalar `Value` (anything other than a Null, Array, Object or Map). A Map
+/// with only String keys is displayed the same way as an Object, so parsing
+/// it back gives an Object.
 /// Array: A `std::vec::Vec` of `Value`s
 /// String: A `std::string::String`
 /// Bit: A boolean where true means 1 and false means 0
@@ -21,6 +27,7 @@ use std::fmt;
 pub enum Value {
     Null,
     Object(std::collections::HashMap<std::string::String, Value>),
+    Map(std::vec::Vec<(Value, Value)>),
     Array(std::vec::Vec<Value>),
     String(std::string::String),
     Bit(bool),
@@ -33,6 +40,17 @@ pub enum Value {
     Int8(i8),
 }
 
+impl Value {
+    /// Returns whether the value is a scalar, meaning that it can be used as
+    /// the key of a `Value::Map`.
+    pub fn is_scalar(&self) -> bool {
+        !matches!(
+            self,
+            Value::Null | Value::Object(_) | Value::Map(_) | Value::Array(_)
+        )
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", display(self.clone()))
@@ -41,22 +59,22 @@ impl fmt::Display for Value {
 
 fn display(val: Value) -> String {
     match val {
-        Value::String(s) => return format!("\"{}\"", s.replace("\"", "\\\"")),
-        Value::Bit(i) => return format!("{}{}", if i { "1" } else { "0" }, constants::EMP_BIT),
-        Value::Boolean(i) => return (if i { "true" } else { "false" }).to_owned(),
-        Value::Int32(i) => return format!("{}", i),
-        Value::Int64(i) => return format!("{}{}", i, constants::EMP_LONG),
-        Value::Int16(i) => return format!("{}{}", i, constants::EMP_SHORT),
-        Value::Int8(i) => return format!("{}{}", i, constants::EMP_BYTE),
-        Value::Float(i) => return format!("{}{}", i, constants::EMP_FLOAT),
-        Value::Double(i) => return format!("{}{}", i, constants::EMP_DOUBLE),
+        Value::String(s) => format!("\"{}\"", s.replace("\"", "\\\"")),
+        Value::Bit(i) => format!("{}{}", if i { "1" } else { "0" }, constants::EMP_BIT),
+        Value::Boolean(i) => (if i { "true" } else { "false" }).to_owned(),
+        Value::Int32(i) => format!("{}", i),
+        Value::Int64(i) => format!("{}{}", i, constants::EMP_LONG),
+        Value::Int16(i) => format!("{}{}", i, constants::EMP_SHORT),
+        Value::Int8(i) => format!("{}{}", i, constants::EMP_BYTE),
+        Value::Float(i) => format!("{}{}", i, constants::EMP_FLOAT),
+        Value::Double(i) => format!("{}{}", i, constants::EMP_DOUBLE),
         Value::Array(a) => {
             let mut arr = vec![];
             for v in a {
                 arr.push(display(v));
             }
 
-            return format!("[{}]", arr.join(", "));
+            format!("[{}]", arr.join(", "))
         }
         Value::Object(o) => {
             let mut out = String::from("");
@@ -75,8 +93,16 @@ fn display(val: Value) -> String {
             if out.len() > 2 {
                 return format!("{{{}}}", out[2..].to_owned());
             }
-            return "{}".to_owned();
+            "{}".to_owned()
+        }
+        Value::Map(m) => {
+            let mut pairs = vec![];
+            for (k, v) in m {
+                pairs.push(format!("{}: {}", display(k), display(v)));
+            }
+
+            format!("{{{}}}", pairs.join(", "))
         }
-        Value::Null => return "null".to_owned(),
+        Value::Null => "null".to_owned(),
     }
 }
@@ -10,8 +10,8 @@ use std::collections::HashMap;
 /// of bits possible, to save space.
 pub fn from_json(val: serde_json::Value) -> value::Value {
     match val {
-        serde_json::Value::Null => return value::Value::Null,
-        serde_json::Value::Bool(b) => return value::Value::Boolean(b),
+        serde_json::Value::Null => value::Value::Null,
+        serde_json::Value::Bool(b) => value::Value::Boolean(b),
         serde_json::Value::Number(n) => match n.as_f64() {
             Some(doub) => {
                 if !(doub - doub.floor() < 0.0000003 && doub - doub.floor() > -0.0000003) {
@@ -34,7 +34,7 @@ pub fn from_json(val: serde_json::Value) -> value::Value {
                     return value::Value::Int32(numb as i32);
                 }
 
-                return value::Value::Int64(numb);
+                value::Value::Int64(numb)
             }
             None => match n.as_i64() {
                 Some(numb) => {
@@ -54,12 +54,12 @@ pub fn from_json(val: serde_json::Value) -> value::Value {
                         return value::Value::Int32(numb as i32);
                     }
 
-                    return value::Value::Int64(numb);
+                    value::Value::Int64(numb)
                 }
-                None => return value::Value::Null,
+                None => value::Value::Null,
             },
         },
-        serde_json::Value::String(s) => return value::Value::String(s),
+        serde_json::Value::String(s) => value::Value::String(s),
         serde_json::Value::Array(a) => {
             let mut arr = vec![];
 
@@ -67,7 +67,7 @@ pub fn from_json(val: serde_json::Value) -> value::Value {
                 arr.push(from_json(c));
             }
 
-            return value::Value::Array(arr);
+            value::Value::Array(arr)
         }
         serde_json::Value::Object(o) => {
             let mut out: HashMap<String, value::Value> = HashMap::new();
@@ -82,15 +82,21 @@ pub fn from_json(val: serde_json::Value) -> value::Value {
                 );
             }
 
-            return value::Value::Object(out);
+            value::Value::Object(out)
         }
     }
 }
 
 /// This converts an `emp::value::Value` to a `serde_json::Value`
+///
+/// As JSON only allows string keys, `emp::value::Value::Map`s are converted
+/// into objects with their keys turned into strings: strings are kept as
+/// they are, while every other key is written out as its JSON value (so
+/// `1` becomes `"1"` and `true` becomes `"true"`). Converting the JSON back
+/// gives an `emp::value::Value::Object`.
 pub fn to_json(val: value::Value) -> serde_json::Value {
     match val {
-        value::Value::Null => return serde_json::Value::Null,
+        value::Value::Null => serde_json::Value::Null,
         value::Value::Object(o) => {
             let mut out = serde_json::Map::new();
 
@@ -104,7 +110,20 @@ pub fn to_json(val: value::Value) -> serde_json::Value {
                 );
             }
 
-            return serde_json::Value::Object(out);
+            serde_json::Value::Object(out)
+        }
+        value::Value::Map(m) => {
+            let mut out = serde_json::Map::new();
+
+            for (k, v) in m {
+                let key = match k {
+                    value::Value::String(s) => s,
+                    _ => to_json(k).to_string(),
+                };
+                out.insert(key, to_json(v));
+            }
+
+            serde_json::Value::Object(out)
         }
         value::Value::Array(a) => {
             let mut arr = vec![];
@@ -113,16 +132,16 @@ pub fn to_json(val: value::Value) -> serde_json::Value {
                 arr.push(to_json(c));
             }
 
-            return serde_json::Value::Array(arr);
+            serde_json::Value::Array(arr)
         }
         value::Value::String(s) => serde_json::json!(s),
-        value::Value::Bit(i) => return serde_json::json!(if i { 1 } else { 0 }),
-        value::Value::Boolean(b) => return serde_json::json!(b),
-        value::Value::Int32(i) => return serde_json::json!(i),
-        value::Value::Float(f) => return serde_json::json!(f),
-        value::Value::Double(d) => return serde_json::json!(d),
-        value::Value::Int64(i) => return serde_json::json!(i),
-        value::Value::Int16(i) => return serde_json::json!(i),
-        value::Value::Int8(i) => return serde_json::json!(i),
+        value::Value::Bit(i) => serde_json::json!(if i { 1 } else { 0 }),
+        value::Value::Boolean(b) => serde_json::json!(b),
+        value::Value::Int32(i) => serde_json::json!(i),
+        value::Value::Float(f) => serde_json::json!(f),
+        value::Value::Double(d) => serde_json::json!(d),
+        value::Value::Int64(i) => serde_json::json!(i),
+        value::Value::Int16(i) => serde_json::json!(i),
+        value::Value::Int8(i) => serde_json::json!(i),
     }
 }
@@ -13,6 +13,107 @@ mod macros;
 
 const DIGITS: [char; 12] = ['1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '.', '-'];
 
+#[derive(Clone, Debug)]
+/// Limits on what parsing a string can use up, for strings from untrusted
+/// sources.
+///
+/// `emp::value::parse::from_str` has no limits. `ParseLimits::default()` only
+/// limits arrays and objects to being nested 128 levels deep, which keeps
+/// the parser from overflowing the stack as it recurses into each of them,
+/// and `ParseLimits::none()` turns every limit off. Going over a limit stops
+/// the parser with one of the `Limit` variants of `emp::errors::ParseError`.
+pub struct ParseLimits {
+    /// How many arrays and objects can be nested inside of each other.
+    pub depth: usize,
+    /// How many values can be parsed, including object keys.
+    pub elements: usize,
+    /// The length of the longest string in bytes.
+    pub string_length: usize,
+    /// Roughly how many bytes the parsed values can take up in memory,
+    /// counting the size of a `Value` for every value along with the bytes
+    /// of every string.
+    pub allocation: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            depth: 128,
+            ..ParseLimits::none()
+        }
+    }
+}
+
+impl ParseLimits {
+    /// Limits which allow anything.
+    pub fn none() -> Self {
+        ParseLimits {
+            depth: usize::MAX,
+            elements: usize::MAX,
+            string_length: usize::MAX,
+            allocation: usize::MAX,
+        }
+    }
+}
+
+// How much of its limits a value has used up so far while it is being parsed.
+struct Usage<'l> {
+    limits: &'l ParseLimits,
+    depth: usize,
+    elements: usize,
+    allocation: usize,
+}
+
+impl Usage<'_> {
+    fn new(limits: &ParseLimits) -> Usage<'_> {
+        Usage {
+            limits,
+            depth: 0,
+            elements: 0,
+            allocation: 0,
+        }
+    }
+
+    // Counts the value about to be parsed.
+    fn value(&mut self) -> Result<(), ParseError> {
+        self.elements += 1;
+        if self.elements > self.limits.elements {
+            return Err(ParseError::ElementLimitError(self.limits.elements));
+        }
+
+        self.allocate(std::mem::size_of::<Value>())
+    }
+
+    // Counts an array or object being opened, `depth` has to be taken back down once it is closed.
+    fn open(&mut self) -> Result<(), ParseError> {
+        if self.depth >= self.limits.depth {
+            return Err(ParseError::DepthLimitError(self.limits.depth));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn string(&mut self, len: usize) -> Result<(), ParseError> {
+        if len > self.limits.string_length {
+            return Err(ParseError::StringLengthLimitError(
+                self.limits.string_length,
+            ));
+        }
+
+        self.allocate(len)
+    }
+
+    fn allocate(&mut self, len: usize) -> Result<(), ParseError> {
+        self.allocation = self.allocation.saturating_add(len);
+        if self.allocation > self.limits.allocation {
+            return Err(ParseError::AllocationLimitError(self.limits.allocation));
+        }
+
+        Ok(())
+    }
+}
+
 fn lex_num(mut string: &str) -> Result<(Option<String>, &str), ParseError> {
     let mut emp_numb = String::from("");
 
@@ -20,32 +121,28 @@ fn lex_num(mut string: &str) -> Result<(Option<String>, &str), ParseError> {
         return Ok((None, string));
     }
 
-    while string.len() != 0 {
+    while !string.is_empty() {
         if !DIGITS.contains(&string.chars().next().unwrap()) {
             if constants::EMP_NUMB_SIGN.contains(&string.chars().next().unwrap()) {
-                emp_numb.push_str(&string.chars().next().unwrap().to_string());
+                emp_numb.push(string.chars().next().unwrap());
                 string = &string[1..];
             }
             return Ok((Some(emp_numb), string));
         }
 
-        emp_numb.push_str(&string.chars().next().unwrap().to_string());
+        emp_numb.push(string.chars().next().unwrap());
         string = &string[1..];
     }
 
-    return Ok((Some(emp_numb), string));
+    Ok((Some(emp_numb), string))
 }
 
 fn lex_null(string: &str) -> (Option<String>, &str) {
-    if string.len() < 4 {
-        return (None, string);
-    }
-
-    if &string[0..4] == "null" {
-        return (Some("null".to_owned()), &string[4..]);
+    if let Some(rest) = string.strip_prefix("null") {
+        return (Some("null".to_owned()), rest);
     }
 
-    return (None, string);
+    (None, string)
 }
 
 fn lex_string(mut string: &str) -> Result<(Option<String>, &str), ParseError> {
@@ -57,43 +154,36 @@ fn lex_string(mut string: &str) -> Result<(Option<String>, &str), ParseError> {
 
     string = &string[1..];
 
-    while string.len() != 0 {
+    while !string.is_empty() {
         if string.chars().next().unwrap() == constants::EMP_QUOTE
             && emp_string.chars().last().unwrap() == constants::EMP_ESCAPE
         {
             emp_string = emp_string[..emp_string.len() - 1].to_owned();
-            emp_string.push_str("\"");
+            emp_string.push('"');
             string = &string[1..];
         } else if string.chars().next().unwrap() == constants::EMP_QUOTE {
-            emp_string.push_str("\"");
+            emp_string.push('"');
             return Ok((Some(emp_string), &string[1..]));
         } else {
-            emp_string.push_str(&string.chars().next().unwrap().to_string());
-            string = &string[1..];
+            let c = string.chars().next().unwrap();
+            emp_string.push(c);
+            string = &string[c.len_utf8()..];
         }
     }
 
-    return Err(ParseError::EOFError);
+    Err(ParseError::EOFError)
 }
 
 fn lex_bool(string: &str) -> (Option<String>, &str) {
-    if string.len() < 4 {
-        return (None, string);
+    if let Some(rest) = string.strip_prefix("true") {
+        return (Some("true".to_owned()), rest);
     }
 
-    if &string[0..4] == "true" {
-        return (Some("true".to_owned()), &string[4..]);
+    if let Some(rest) = string.strip_prefix("false") {
+        return (Some("false".to_owned()), rest);
     }
 
-    if string.len() < 5 {
-        return (None, string);
-    }
-
-    if &string[0..5] == "false" {
-        return (Some("false".to_owned()), &string[5..]);
-    }
-
-    return (None, string);
+    (None, string)
 }
 
 /// Lexs strings into a set of tokens.
@@ -103,7 +193,7 @@ fn lex_bool(string: &str) -> (Option<String>, &str) {
 pub fn lex(mut string: &str) -> Result<Vec<String>, ParseError> {
     let mut tokens: Vec<String> = vec![];
 
-    while string.len() != 0 {
+    while !string.is_empty() {
         match lex_num(string) {
             Ok((t, s)) => {
                 string = s;
@@ -115,22 +205,16 @@ pub fn lex(mut string: &str) -> Result<Vec<String>, ParseError> {
             Err(e) => return Err(e),
         }
 
-        match lex_null(string) {
-            (Some(token), s) => {
-                tokens.push(token);
-                string = s;
-                continue;
-            }
-            (None, _) => {}
+        if let (Some(token), s) = lex_null(string) {
+            tokens.push(token);
+            string = s;
+            continue;
         }
 
-        match lex_bool(string) {
-            (Some(token), s) => {
-                tokens.push(token);
-                string = s;
-                continue;
-            }
-            (None, _) => {}
+        if let (Some(token), s) = lex_bool(string) {
+            tokens.push(token);
+            string = s;
+            continue;
         }
 
         match lex_string(string) {
@@ -159,10 +243,13 @@ pub fn lex(mut string: &str) -> Result<Vec<String>, ParseError> {
         ));
     }
 
-    return Ok(tokens);
+    Ok(tokens)
 }
 
-fn parse_array(mut tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseError> {
+fn parse_array<'a>(
+    mut tokens: &'a [String],
+    usage: &mut Usage,
+) -> Result<(Option<Value>, &'a [String]), ParseError> {
     let mut values: Vec<Value> = vec![];
 
     if tokens[0] != String::from(constants::EMP_OPEN_BRACE) {
@@ -170,13 +257,15 @@ fn parse_array(mut tokens: &[String]) -> Result<(Option<Value>, &[String]), Pars
     }
 
     tokens = &tokens[1..];
+    usage.open()?;
 
-    while tokens.len() != 0 {
+    while !tokens.is_empty() {
         if tokens[0] == String::from(constants::EMP_CLOSE_BRACE) {
+            usage.depth -= 1;
             return Ok((Some(Value::Array(values)), &tokens[1..]));
         }
 
-        match parse(tokens) {
+        match parse_value(tokens, usage) {
             Ok((val, tok)) => {
                 tokens = tok;
                 values.push(val);
@@ -184,6 +273,9 @@ fn parse_array(mut tokens: &[String]) -> Result<(Option<Value>, &[String]), Pars
             Err(e) => return Err(e),
         }
 
+        if tokens.is_empty() {
+            return Err(ParseError::EOFError);
+        }
         if tokens[0] != "," && tokens[0] != constants::EMP_CLOSE_BRACE.to_string() {
             return Err(ParseError::UnexpectedTokenError(tokens[0].clone()));
         }
@@ -192,49 +284,60 @@ fn parse_array(mut tokens: &[String]) -> Result<(Option<Value>, &[String]), Pars
         }
     }
 
-    return Err(ParseError::EOFError);
+    Err(ParseError::EOFError)
 }
 
-fn parse_object(mut tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseError> {
-    let mut values: HashMap<String, Value> = HashMap::new();
+fn parse_object<'a>(
+    mut tokens: &'a [String],
+    usage: &mut Usage,
+) -> Result<(Option<Value>, &'a [String]), ParseError> {
+    let mut values: Vec<(Value, Value)> = vec![];
 
     if tokens[0] != String::from(constants::EMP_OPEN_BRACKET) {
         return Ok((None, tokens));
     }
 
     tokens = &tokens[1..];
+    usage.open()?;
 
-    while tokens.len() != 0 {
+    while !tokens.is_empty() {
         if tokens[0] == String::from(constants::EMP_CLOSE_BRACKET) {
-            return Ok((Some(Value::Object(values)), &tokens[1..]));
+            usage.depth -= 1;
+            return Ok((Some(to_object(values)), &tokens[1..]));
         }
 
         let key;
 
-        match parse(tokens) {
+        match parse_value(tokens, usage) {
             Ok((val, tok)) => {
                 tokens = tok;
-                match val {
-                    Value::String(s) => key = s,
-                    _ => return Err(ParseError::InvalidKeyError(val)),
+                if !val.is_scalar() {
+                    return Err(ParseError::InvalidKeyError(val));
                 }
+                key = val;
             }
             Err(e) => return Err(e),
         }
 
+        if tokens.is_empty() {
+            return Err(ParseError::EOFError);
+        }
         if tokens[0] != ":" {
             return Err(ParseError::UnexpectedTokenError(tokens[0].clone()));
         }
         tokens = &tokens[1..];
 
-        match parse(tokens) {
+        match parse_value(tokens, usage) {
             Ok((val, tok)) => {
                 tokens = tok;
-                values.insert(key, val);
+                values.push((key, val));
             }
             Err(e) => return Err(e),
         }
 
+        if tokens.is_empty() {
+            return Err(ParseError::EOFError);
+        }
         if tokens[0] != "," && tokens[0] != constants::EMP_CLOSE_BRACKET.to_string() {
             return Err(ParseError::UnexpectedTokenError(tokens[0].clone()));
         }
@@ -243,7 +346,23 @@ fn parse_object(mut tokens: &[String]) -> Result<(Option<Value>, &[String]), Par
         }
     }
 
-    return Err(ParseError::EOFError);
+    Err(ParseError::EOFError)
+}
+
+// Objects with only string keys become a `Value::Object`, anything else becomes a `Value::Map`.
+fn to_object(values: Vec<(Value, Value)>) -> Value {
+    if values.iter().any(|(k, _)| !matches!(k, Value::String(_))) {
+        return Value::Map(values);
+    }
+
+    let mut object: HashMap<String, Value> = HashMap::new();
+    for (k, v) in values {
+        if let Value::String(s) = k {
+            object.insert(s, v);
+        }
+    }
+
+    Value::Object(object)
 }
 
 fn parse_null(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseError> {
@@ -251,7 +370,7 @@ fn parse_null(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseErro
         return Ok((Some(Value::Null), &tokens[1..]));
     }
 
-    return Ok((None, tokens));
+    Ok((None, tokens))
 }
 
 fn parse_bool(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseError> {
@@ -263,18 +382,23 @@ fn parse_bool(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseErro
         return Ok((Some(Value::Boolean(false)), &tokens[1..]));
     }
 
-    return Ok((None, tokens));
+    Ok((None, tokens))
 }
 
-fn parse_string(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseError> {
+fn parse_string<'a>(
+    tokens: &'a [String],
+    usage: &mut Usage,
+) -> Result<(Option<Value>, &'a [String]), ParseError> {
     if tokens[0].chars().next().unwrap() != constants::EMP_QUOTE {
         return Ok((None, tokens));
     }
 
-    return Ok((
+    usage.string(tokens[0].len() - 2)?;
+
+    Ok((
         Some(Value::String(tokens[0][1..tokens[0].len() - 1].to_owned())),
         &tokens[1..],
-    ));
+    ))
 }
 
 fn parse_number(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseError> {
@@ -283,59 +407,42 @@ fn parse_number(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseEr
     }
 
     match tokens[0].chars().last().unwrap() {
-        constants::EMP_BIT => {
-            return Ok((
-                Some(Value::Bit(tokens[0].chars().next().unwrap() == '1')),
-                &tokens[1..],
-            ))
-        }
+        constants::EMP_BIT => Ok((Some(Value::Bit(tokens[0].starts_with('1'))), &tokens[1..])),
         constants::EMP_BYTE => match i8::from_str(&tokens[0][..tokens[0].len() - 1]) {
-            Ok(n) => return Ok((Some(Value::Int8(n)), &tokens[1..])),
-            Err(_) => {
-                return Err(ParseError::InvalidNumberError(
-                    tokens[0].chars().last().unwrap(),
-                ))
-            }
+            Ok(n) => Ok((Some(Value::Int8(n)), &tokens[1..])),
+            Err(_) => Err(ParseError::InvalidNumberError(
+                tokens[0].chars().last().unwrap(),
+            )),
         },
         constants::EMP_LONG => match i64::from_str(&tokens[0][..tokens[0].len() - 1]) {
-            Ok(n) => return Ok((Some(Value::Int64(n)), &tokens[1..])),
-            Err(_) => {
-                return Err(ParseError::InvalidNumberError(
-                    tokens[0].chars().last().unwrap(),
-                ))
-            }
+            Ok(n) => Ok((Some(Value::Int64(n)), &tokens[1..])),
+            Err(_) => Err(ParseError::InvalidNumberError(
+                tokens[0].chars().last().unwrap(),
+            )),
         },
         constants::EMP_SHORT => match i16::from_str(&tokens[0][..tokens[0].len() - 1]) {
-            Ok(n) => return Ok((Some(Value::Int16(n)), &tokens[1..])),
-            Err(_) => {
-                return Err(ParseError::InvalidNumberError(
-                    tokens[0].chars().last().unwrap(),
-                ))
-            }
+            Ok(n) => Ok((Some(Value::Int16(n)), &tokens[1..])),
+            Err(_) => Err(ParseError::InvalidNumberError(
+                tokens[0].chars().last().unwrap(),
+            )),
         },
         constants::EMP_FLOAT => match f32::from_str(&tokens[0][..tokens[0].len() - 1]) {
-            Ok(n) => return Ok((Some(Value::Float(n)), &tokens[1..])),
-            Err(_) => {
-                return Err(ParseError::InvalidNumberError(
-                    tokens[0].chars().last().unwrap(),
-                ))
-            }
+            Ok(n) => Ok((Some(Value::Float(n)), &tokens[1..])),
+            Err(_) => Err(ParseError::InvalidNumberError(
+                tokens[0].chars().last().unwrap(),
+            )),
         },
         constants::EMP_DOUBLE => match f64::from_str(&tokens[0][..tokens[0].len() - 1]) {
-            Ok(n) => return Ok((Some(Value::Double(n)), &tokens[1..])),
-            Err(_) => {
-                return Err(ParseError::InvalidNumberError(
-                    tokens[0].chars().last().unwrap(),
-                ))
-            }
+            Ok(n) => Ok((Some(Value::Double(n)), &tokens[1..])),
+            Err(_) => Err(ParseError::InvalidNumberError(
+                tokens[0].chars().last().unwrap(),
+            )),
         },
         _ => match i32::from_str(&tokens[0]) {
-            Ok(n) => return Ok((Some(Value::Int32(n)), &tokens[1..])),
-            Err(_) => {
-                return Err(ParseError::InvalidNumberError(
-                    tokens[0].chars().last().unwrap(),
-                ))
-            }
+            Ok(n) => Ok((Some(Value::Int32(n)), &tokens[1..])),
+            Err(_) => Err(ParseError::InvalidNumberError(
+                tokens[0].chars().last().unwrap(),
+            )),
         },
     }
 }
@@ -347,23 +454,46 @@ fn parse_number(tokens: &[String]) -> Result<(Option<Value>, &[String]), ParseEr
 /// The function will return an `emp::errors::ParseError` if something
 /// unexpected happens.
 pub fn parse(tokens: &[String]) -> Result<(Value, &[String]), ParseError> {
-    try_parse!(parse_array, tokens);
-    try_parse!(parse_object, tokens);
+    parse_value(tokens, &mut Usage::new(&ParseLimits::none()))
+}
+
+fn parse_value<'a>(
+    tokens: &'a [String],
+    usage: &mut Usage,
+) -> Result<(Value, &'a [String]), ParseError> {
+    if tokens.is_empty() {
+        return Err(ParseError::EOFError);
+    }
+
+    usage.value()?;
+
+    try_parse!(parse_array, tokens, usage);
+    try_parse!(parse_object, tokens, usage);
     try_parse!(parse_null, tokens);
     try_parse!(parse_bool, tokens);
-    try_parse!(parse_string, tokens);
+    try_parse!(parse_string, tokens, usage);
     try_parse!(parse_number, tokens);
 
-    return Err(ParseError::UnexpectedTokenError(tokens[0].clone()));
+    Err(ParseError::UnexpectedTokenError(tokens[0].clone()))
 }
 
 /// This will lex and parse the string into a `emp::value::Value`
 ///
 /// If either function returns a `emp::errors::ParseError` it will return that
-/// instead.
+/// instead. Strings from untrusted sources should be parsed with
+/// `from_str_with_limits` instead, as very deeply nested arrays and objects
+/// overflow the stack, both while parsing and when the `emp::value::Value`
+/// is dropped or printed.
 pub fn from_str(string: &str) -> Result<Value, ParseError> {
+    from_str_with_limits(string, &ParseLimits::none())
+}
+
+/// This will lex and parse the string into a `emp::value::Value` the same way
+/// as `from_str`, returning a `emp::errors::ParseError` as soon as the data
+/// goes over any of the `ParseLimits`.
+pub fn from_str_with_limits(string: &str, limits: &ParseLimits) -> Result<Value, ParseError> {
     match lex(string) {
-        Ok(tok) => match parse(&tok) {
+        Ok(tok) => match parse_value(&tok, &mut Usage::new(limits)) {
             Ok((val, _)) => Ok(val),
             Err(e) => Err(e),
         },
@@ -1,6 +1,6 @@
 macro_rules! try_parse {
-    ($decode_fn: ident, $bytes: expr) => {
-        match $decode_fn($bytes) {
+    ($decode_fn: ident, $($args: expr),+) => {
+        match $decode_fn($($args),+) {
             Ok((Some(v), b)) => return Ok((v, b)),
             Err(e) => return Err(e),
             _ => {}
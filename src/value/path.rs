@@ -0,0 +1,178 @@
+//! Paths pointing to a value inside of an EMP Value
+
+use crate::constants;
+use crate::errors::ParseError;
+use crate::value::Value;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A single step in a path.
+///
+/// Key: The key of a value in an object
+/// Index: The index of a value in an array
+pub enum Segment {
+    Key(std::string::String),
+    Index(usize),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// A path to a value, written as `$.players[2].name`.
+///
+/// The leading `$` is optional when parsing, keys containing `.` or `[` can
+/// be written in quotes inside of square brackets: `$["a.b"]`.
+pub struct Path {
+    pub segments: std::vec::Vec<Segment>,
+}
+
+impl Path {
+    /// Returns a new path with `segment` added on to the end.
+    pub fn join(&self, segment: Segment) -> Path {
+        let mut segments = self.segments.clone();
+        segments.push(segment);
+        Path { segments }
+    }
+}
+
+// The path segment for the value of `key` in an object or map, integer keys are indices the same as in paths.
+pub(crate) fn key_segment(key: &Value) -> Segment {
+    let index = match key {
+        Value::String(s) => return Segment::Key(s.to_owned()),
+        Value::Int8(i) => *i as i64,
+        Value::Int16(i) => *i as i64,
+        Value::Int32(i) => *i as i64,
+        Value::Int64(i) => *i,
+        k => return Segment::Key(k.to_string()),
+    };
+
+    if index < 0 {
+        return Segment::Key(key.to_string());
+    }
+    Segment::Index(index as usize)
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.segments {
+            match segment {
+                Segment::Key(k) => {
+                    if k.is_empty()
+                        || k.contains([constants::EMP_PERIOD, constants::EMP_OPEN_BRACE])
+                    {
+                        let k = k.replace('\\', "\\\\").replace('"', "\\\"");
+                        write!(f, "[\"{}\"]", k)?
+                    } else {
+                        write!(f, ".{}", k)?
+                    }
+                }
+                Segment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Path {
+    type Err = ParseError;
+
+    fn from_str(string: &str) -> Result<Path, ParseError> {
+        from_str(string)
+    }
+}
+
+/// Parses a string such as `players[3].name` into an `emp::value::path::Path`
+///
+/// If the string is not a valid path it will return a
+/// `emp::errors::ParseError`
+pub fn from_str(mut string: &str) -> Result<Path, ParseError> {
+    let mut segments = vec![];
+
+    if string.starts_with('$') {
+        string = &string[1..];
+    } else if !string.is_empty() && !string.starts_with(constants::EMP_OPEN_BRACE) {
+        string = &string[lex_key(string, &mut segments)..];
+    }
+
+    while !string.is_empty() {
+        let c = string.chars().next().unwrap();
+        string = &string[c.len_utf8()..];
+
+        if c == constants::EMP_PERIOD {
+            let len = lex_key(string, &mut segments);
+            if len == 0 {
+                return match string.chars().next() {
+                    Some(c) => Err(ParseError::UnexpectedCharacterError(c)),
+                    None => Err(ParseError::EOFError),
+                };
+            }
+            string = &string[len..];
+        } else if c == constants::EMP_OPEN_BRACE {
+            let end = match string.find(constants::EMP_CLOSE_BRACE) {
+                Some(e) => e,
+                None => return Err(ParseError::EOFError),
+            };
+
+            if string.starts_with(constants::EMP_QUOTE) {
+                let (key, len) = match lex_quoted(string) {
+                    Some(k) => k,
+                    None => return Err(ParseError::EOFError),
+                };
+                string = &string[len..];
+                if !string.starts_with(constants::EMP_CLOSE_BRACE) {
+                    return match string.chars().next() {
+                        Some(c) => Err(ParseError::UnexpectedCharacterError(c)),
+                        None => Err(ParseError::EOFError),
+                    };
+                }
+                segments.push(Segment::Key(key));
+                string = &string[1..];
+                continue;
+            }
+
+            match usize::from_str(&string[..end]) {
+                Ok(i) => segments.push(Segment::Index(i)),
+                Err(_) => {
+                    return Err(ParseError::UnexpectedTokenError(string[..end].to_owned()));
+                }
+            }
+            string = &string[end + 1..];
+        } else {
+            return Err(ParseError::UnexpectedCharacterError(c));
+        }
+    }
+
+    Ok(Path { segments })
+}
+
+fn lex_key(string: &str, segments: &mut std::vec::Vec<Segment>) -> usize {
+    let len = string
+        .find([constants::EMP_PERIOD, constants::EMP_OPEN_BRACE])
+        .unwrap_or(string.len());
+
+    if len != 0 {
+        segments.push(Segment::Key(string[..len].to_owned()));
+    }
+
+    len
+}
+
+fn lex_quoted(string: &str) -> Option<(std::string::String, usize)> {
+    let mut key = std::string::String::new();
+    let mut escaped = false;
+
+    for (i, c) in string.char_indices().skip(1) {
+        if escaped {
+            key.push(c);
+            escaped = false;
+        } else if c == constants::EMP_ESCAPE {
+            escaped = true;
+        } else if c == constants::EMP_QUOTE {
+            return Some((key, i + 1));
+        } else {
+            key.push(c);
+        }
+    }
+
+    None
+}
@@ -0,0 +1,305 @@
+//! Schemas describing the shape of EMP Values
+
+use crate::errors::SchemaError;
+use crate::value::path::{key_segment, Path, Segment};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A description of what a `Value` should look like.
+///
+/// Any: Any value at all
+/// Array: An array where every value matches the inner schema
+/// Object: An object with the given fields, and no others
+/// Map: A map where every key matches the first schema and every value
+/// matches the second schema
+/// Every other variant matches the `Value` variant with the same name
+pub enum Schema {
+    Any,
+    Null,
+    Bit,
+    Boolean,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Float,
+    Double,
+    String,
+    Array(Box<Schema>),
+    Object(HashMap<std::string::String, Field>),
+    Map(Box<Schema>, Box<Schema>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A field of an object in a `Schema`.
+pub struct Field {
+    /// The schema the value of the field has to match.
+    pub schema: Schema,
+    /// What the field is for, so other people know what it means.
+    pub description: Option<std::string::String>,
+    /// Whether the field can be left out of the object.
+    pub optional: bool,
+}
+
+impl Field {
+    /// Creates a field which is required and has no description.
+    pub fn new(schema: Schema) -> Field {
+        Field {
+            schema,
+            description: None,
+            optional: false,
+        }
+    }
+}
+
+const SCALARS: [(&str, Schema); 11] = [
+    ("any", Schema::Any),
+    ("null", Schema::Null),
+    ("bit", Schema::Bit),
+    ("boolean", Schema::Boolean),
+    ("int8", Schema::Int8),
+    ("int16", Schema::Int16),
+    ("int32", Schema::Int32),
+    ("int64", Schema::Int64),
+    ("float", Schema::Float),
+    ("double", Schema::Double),
+    ("string", Schema::String),
+];
+
+impl Schema {
+    /// Creates the schema that `val` matches, with every array and map
+    /// holding mixed values using `Schema::Any`.
+    pub fn infer(val: &Value) -> Schema {
+        match val {
+            Value::Null => Schema::Null,
+            Value::Bit(_) => Schema::Bit,
+            Value::Boolean(_) => Schema::Boolean,
+            Value::Int8(_) => Schema::Int8,
+            Value::Int16(_) => Schema::Int16,
+            Value::Int32(_) => Schema::Int32,
+            Value::Int64(_) => Schema::Int64,
+            Value::Float(_) => Schema::Float,
+            Value::Double(_) => Schema::Double,
+            Value::String(_) => Schema::String,
+            Value::Array(a) => Schema::Array(Box::new(infer_all(a.iter()))),
+            Value::Object(o) => {
+                let mut fields = HashMap::new();
+                for (k, v) in o {
+                    fields.insert(k.to_owned(), Field::new(Schema::infer(v)));
+                }
+                Schema::Object(fields)
+            }
+            Value::Map(m) => Schema::Map(
+                Box::new(infer_all(m.iter().map(|(k, _)| k))),
+                Box::new(infer_all(m.iter().map(|(_, v)| v))),
+            ),
+        }
+    }
+
+    /// Checks that `val` matches the schema.
+    ///
+    /// If it does not, the `emp::errors::SchemaError` contains the path to
+    /// the first value which does not match.
+    pub fn validate(&self, val: &Value) -> Result<(), SchemaError> {
+        validate(self, val, &Path::default())
+    }
+
+    /// Converts the schema into a `Value` so that it can be stored.
+    ///
+    /// Scalar schemas become their name as a string (such as `"int32"`),
+    /// arrays become `{"array": schema}`, maps become `{"map": [key, value]}`
+    /// and objects become `{"object": {name: field}}` where each field is
+    /// `{"type": schema}` with an optional `"description"` and `"optional"`.
+    pub fn to_value(&self) -> Value {
+        for (name, schema) in SCALARS.iter() {
+            if schema == self {
+                return Value::String(name.to_string());
+            }
+        }
+
+        let mut out = HashMap::new();
+        match self {
+            Schema::Array(s) => {
+                out.insert("array".to_owned(), s.to_value());
+            }
+            Schema::Map(k, v) => {
+                out.insert(
+                    "map".to_owned(),
+                    Value::Array(vec![k.to_value(), v.to_value()]),
+                );
+            }
+            Schema::Object(fields) => {
+                let mut object = HashMap::new();
+                for (name, field) in fields {
+                    let mut f = HashMap::new();
+                    f.insert("type".to_owned(), field.schema.to_value());
+                    if let Some(d) = &field.description {
+                        f.insert("description".to_owned(), Value::String(d.to_owned()));
+                    }
+                    if field.optional {
+                        f.insert("optional".to_owned(), Value::Boolean(true));
+                    }
+                    object.insert(name.to_owned(), Value::Object(f));
+                }
+                out.insert("object".to_owned(), Value::Object(object));
+            }
+            _ => {}
+        }
+
+        Value::Object(out)
+    }
+
+    /// Converts a `Value` created by `Schema::to_value` back into a schema.
+    ///
+    /// If the value is not a valid schema this returns a
+    /// `emp::errors::SchemaError::InvalidSchemaError`.
+    pub fn from_value(val: &Value) -> Result<Schema, SchemaError> {
+        let invalid = || SchemaError::InvalidSchemaError(val.clone());
+
+        let o = match val {
+            Value::String(s) => {
+                for (name, schema) in SCALARS.iter() {
+                    if name == s {
+                        return Ok(schema.clone());
+                    }
+                }
+                return Err(invalid());
+            }
+            Value::Object(o) if o.len() == 1 => o,
+            _ => return Err(invalid()),
+        };
+
+        if let Some(s) = o.get("array") {
+            return Ok(Schema::Array(Box::new(Schema::from_value(s)?)));
+        }
+
+        if let Some(Value::Array(a)) = o.get("map") {
+            if a.len() != 2 {
+                return Err(invalid());
+            }
+            return Ok(Schema::Map(
+                Box::new(Schema::from_value(&a[0])?),
+                Box::new(Schema::from_value(&a[1])?),
+            ));
+        }
+
+        if let Some(Value::Object(object)) = o.get("object") {
+            let mut fields = HashMap::new();
+            for (name, f) in object {
+                let f = match f {
+                    Value::Object(f) => f,
+                    _ => return Err(invalid()),
+                };
+                let schema = match f.get("type") {
+                    Some(t) => Schema::from_value(t)?,
+                    None => return Err(invalid()),
+                };
+                let description = match f.get("description") {
+                    Some(Value::String(d)) => Some(d.to_owned()),
+                    None => None,
+                    _ => return Err(invalid()),
+                };
+                let optional = match f.get("optional") {
+                    Some(Value::Boolean(b)) => *b,
+                    None => false,
+                    _ => return Err(invalid()),
+                };
+
+                fields.insert(
+                    name.to_owned(),
+                    Field {
+                        schema,
+                        description,
+                        optional,
+                    },
+                );
+            }
+            return Ok(Schema::Object(fields));
+        }
+
+        Err(invalid())
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+fn infer_all<'a, I: Iterator<Item = &'a Value>>(values: I) -> Schema {
+    let mut out: Option<Schema> = None;
+
+    for v in values {
+        let schema = Schema::infer(v);
+        match &out {
+            None => out = Some(schema),
+            Some(s) if *s == schema => {}
+            Some(_) => return Schema::Any,
+        }
+    }
+
+    out.unwrap_or(Schema::Any)
+}
+
+fn validate(schema: &Schema, val: &Value, path: &Path) -> Result<(), SchemaError> {
+    let matches = match (schema, val) {
+        (Schema::Any, _) => true,
+        (Schema::Null, Value::Null) => true,
+        (Schema::Bit, Value::Bit(_)) => true,
+        (Schema::Boolean, Value::Boolean(_)) => true,
+        (Schema::Int8, Value::Int8(_)) => true,
+        (Schema::Int16, Value::Int16(_)) => true,
+        (Schema::Int32, Value::Int32(_)) => true,
+        (Schema::Int64, Value::Int64(_)) => true,
+        (Schema::Float, Value::Float(_)) => true,
+        (Schema::Double, Value::Double(_)) => true,
+        (Schema::String, Value::String(_)) => true,
+        (Schema::Array(s), Value::Array(a)) => {
+            for (i, v) in a.iter().enumerate() {
+                validate(s, v, &path.join(Segment::Index(i)))?;
+            }
+            true
+        }
+        (Schema::Map(k, v), Value::Map(m)) => {
+            for (key, value) in m {
+                let segment = key_segment(key);
+                validate(k, key, &path.join(segment.clone()))?;
+                validate(v, value, &path.join(segment))?;
+            }
+            true
+        }
+        (Schema::Object(fields), Value::Object(o)) => {
+            for (name, field) in fields {
+                match o.get(name) {
+                    Some(v) => {
+                        validate(&field.schema, v, &path.join(Segment::Key(name.to_owned())))?
+                    }
+                    None if field.optional => {}
+                    None => {
+                        return Err(SchemaError::MissingFieldError(
+                            path.join(Segment::Key(name.to_owned())),
+                        ))
+                    }
+                }
+            }
+            for name in o.keys() {
+                if !fields.contains_key(name) {
+                    return Err(SchemaError::UnknownFieldError(
+                        path.join(Segment::Key(name.to_owned())),
+                    ));
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+
+    if !matches {
+        return Err(SchemaError::TypeMismatchError(path.clone(), schema.clone()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::borrowed::{decode_borrowed, ValueRef};
+use emp::decode::decode;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+
+fn borrowed_from(s: &str, bytes: &[u8]) -> bool {
+    bytes.as_ptr_range().contains(&s.as_ptr())
+}
+
+#[test]
+fn to_value() {
+    let value = object(vec![
+        (
+            "map",
+            Value::Map(vec![(Value::Int16(-3), Value::Float(0.5))]),
+        ),
+        (
+            "list",
+            Value::Array(vec![Value::Null, Value::Int64(1 << 40)]),
+        ),
+        (
+            "flags",
+            Value::Array(vec![Value::Bit(true), Value::Boolean(false)]),
+        ),
+    ]);
+    for options in [EncodeOptions::default(), EncodeOptions::speed()] {
+        let bytes = encode_with(&value, &options);
+        assert_same(&decode_borrowed(&bytes).unwrap().to_value(), &value);
+    }
+}
+
+#[test]
+fn strings_are_borrowed() {
+    let short = "short";
+    let long = "a string which is too long for a nibble length";
+    let bytes = encode(object(vec![(short, string(long))]));
+
+    match decode_borrowed(&bytes).unwrap() {
+        ValueRef::Object(fields) => match fields[..] {
+            [(k, ValueRef::String(v))] => {
+                assert_eq!((k, v), (short, long));
+                assert!(borrowed_from(k, &bytes) && borrowed_from(v, &bytes));
+            }
+            _ => panic!("expected one string field"),
+        },
+        v => panic!("expected an object, found {}", v.to_value()),
+    }
+}
+
+#[test]
+fn references_borrow_from_the_table() {
+    let bytes = encode_with(
+        &Value::Array(vec![string("ab"), string("ab")]),
+        &EncodeOptions::size(),
+    );
+
+    match decode_borrowed(&bytes).unwrap() {
+        ValueRef::Array(a) => match a[..] {
+            [ValueRef::String(x), ValueRef::String(y)] => {
+                assert_eq!(x.as_ptr(), y.as_ptr());
+                assert!(borrowed_from(x, &bytes[..7]));
+            }
+            _ => panic!("expected two strings"),
+        },
+        v => panic!("expected an array, found {}", v.to_value()),
+    }
+}
+
+#[test]
+fn fields_keep_their_order() {
+    let bytes = [0x20, 0x14, b'b', 0x05, 0x14, b'a', 0x1e];
+    match decode_borrowed(&bytes).unwrap() {
+        ValueRef::Object(fields) => {
+            let keys: Vec<&str> = fields.iter().map(|(k, _)| *k).collect();
+            assert_eq!(keys, ["b", "a"]);
+        }
+        v => panic!("expected an object, found {}", v.to_value()),
+    }
+}
+
+#[test]
+fn errors_match_decode() {
+    let inputs: [&[u8]; 6] = [
+        &[0x14, 0xff],
+        &[0x34, b'a'],
+        &[0x00, 0x14, b'a', 0x02, 0x05, 0x01],
+        &[0x22, 0x05, 0x03],
+        &[0x2f, 0x10, 0x05, 0x1e],
+        &[0x00, 0x14, b'a', 0x01],
+    ];
+    for bytes in inputs {
+        let a = decode_borrowed(bytes).map(|_| ()).unwrap_err();
+        let b = decode(bytes).unwrap_err();
+        assert_eq!(a.to_string(), b.to_string(), "{:02x?}", bytes);
+    }
+    assert!(matches!(
+        decode_borrowed(&[0x14, 0xff]).unwrap_err().cause(),
+        DecodeError::StringDecodeError(_)
+    ));
+}
@@ -0,0 +1,189 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use common::{assert_same, object, string};
+use emp::codec::Codec;
+use emp::decode::DecodeLimits;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::{CodecError, DecodeError};
+use emp::value::Value;
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Decoder, Framed};
+
+fn save() -> Value {
+    object(vec![
+        ("name", string("a save")),
+        (
+            "scores",
+            Value::Array(vec![Value::Int32(-70000), Value::Int8(3)]),
+        ),
+        (
+            "flags",
+            Value::Map(vec![(Value::Int8(1), Value::Bit(true))]),
+        ),
+    ])
+}
+
+fn values() -> Vec<Value> {
+    vec![
+        save(),
+        Value::Null,
+        Value::String("a string which is too long for a nibble length".to_owned()),
+        Value::Array(vec![Value::Int8(1); 100]),
+    ]
+}
+
+// Accepts one connection and sends back every value it receives, until the connection closes or a frame is bad.
+async fn echo(
+    max_frame_size: usize,
+) -> (
+    std::net::SocketAddr,
+    tokio::task::JoinHandle<Vec<CodecError>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(stream, Codec::new(max_frame_size));
+        let mut errors = vec![];
+
+        while let Some(val) = framed.next().await {
+            match val {
+                Ok(val) => framed.send(val).await.unwrap(),
+                Err(e) => {
+                    errors.push(e);
+                    break;
+                }
+            }
+        }
+        errors
+    });
+
+    (addr, server)
+}
+
+#[tokio::test]
+async fn round_trip() {
+    let (addr, server) = echo(1 << 20).await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, Codec::with_options(1 << 20, EncodeOptions::size()));
+
+    for val in values() {
+        framed.send(&val).await.unwrap();
+    }
+    for val in values() {
+        assert_same(&framed.next().await.unwrap().unwrap(), &val);
+    }
+
+    drop(framed);
+    assert!(server.await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn split_across_reads() {
+    let (addr, server) = echo(1 << 20).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.set_nodelay(true).unwrap();
+
+    let mut bytes = vec![];
+    for val in values() {
+        bytes.extend(encode_with(&val, &EncodeOptions::default()));
+    }
+
+    // Pieces which end part way through values, waiting between each so the server reads them separately.
+    for piece in bytes.chunks(bytes.len() / 7 + 1) {
+        stream.write_all(piece).await.unwrap();
+        stream.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut framed = Framed::new(stream, Codec::new(1 << 20));
+    for val in values() {
+        assert_same(&framed.next().await.unwrap().unwrap(), &val);
+    }
+
+    drop(framed);
+    assert!(server.await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn oversize_frames() {
+    let max = 32;
+    assert!(encode(save()).len() > max);
+
+    // Refused by the receiving end as soon as too many bytes arrive.
+    let (addr, server) = echo(max).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(&encode(save())).await.unwrap();
+
+    let errors = server.await.unwrap();
+    assert!(matches!(errors[..], [CodecError::FrameSizeError(_)]));
+
+    // And by the sending end before anything is written.
+    let (addr, _server) = echo(max).await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, Codec::new(max));
+    assert!(matches!(
+        framed.send(save()).await,
+        Err(CodecError::FrameSizeError(_))
+    ));
+    assert!(framed.write_buffer().is_empty());
+}
+
+#[tokio::test]
+async fn bad_frames() {
+    // Invalid bytes, then a connection closed part way through a frame.
+    for (bytes, eof) in [(&[0x14, 0xff][..], false), (&[0x02, 0x05][..], true)] {
+        let (addr, server) = echo(1 << 20).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(bytes).await.unwrap();
+        drop(stream);
+
+        let errors = server.await.unwrap();
+        match &errors[..] {
+            [CodecError::DecodeError(e)] => match e.cause() {
+                DecodeError::EOFError => assert!(eof),
+                DecodeError::StringDecodeError(_) => assert!(!eof),
+                e => panic!("unexpected error {}", e),
+            },
+            e => panic!("unexpected errors {:?}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn deep_frames() {
+    // Nested deeper than the default depth limit, without going over the frame size.
+    let mut bytes = vec![0x12; 100_000];
+    bytes.push(0x05);
+
+    let (addr, server) = echo(1 << 20).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    // The server may close the connection before everything has been written.
+    let _ = stream.write_all(&bytes).await;
+
+    let errors = server.await.unwrap();
+    match &errors[..] {
+        [CodecError::DecodeError(e)] => {
+            assert!(matches!(e.cause(), DecodeError::DepthLimitError(128)))
+        }
+        e => panic!("unexpected errors {:?}", e),
+    }
+
+    // Other limits can be given to the codec.
+    let limits = DecodeLimits {
+        depth: 2,
+        ..DecodeLimits::default()
+    };
+    let mut codec = Codec::with_limits(1 << 20, EncodeOptions::default(), limits);
+    let mut src = bytes::BytesMut::from(&[0x12, 0x12, 0x12, 0x05][..]);
+    assert!(matches!(
+        codec.decode(&mut src),
+        Err(CodecError::DecodeError(e)) if matches!(e.cause(), DecodeError::DepthLimitError(2))
+    ));
+}
@@ -0,0 +1,48 @@
+#![allow(dead_code)]
+
+use emp::value::Value;
+use std::collections::HashMap;
+
+// Values do not implement `PartialEq`, so they are compared field by field.
+pub fn assert_same(a: &Value, b: &Value) {
+    assert!(same(a, b), "{} != {}", a, b);
+}
+
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Null, Value::Null) => true,
+        (Value::Bit(a), Value::Bit(b)) | (Value::Boolean(a), Value::Boolean(b)) => a == b,
+        (Value::Int8(a), Value::Int8(b)) => a == b,
+        (Value::Int16(a), Value::Int16(b)) => a == b,
+        (Value::Int32(a), Value::Int32(b)) => a == b,
+        (Value::Int64(a), Value::Int64(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+        (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(k, a)| b.get(k).is_some_and(|b| same(a, b)))
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ak, av), (bk, bv))| same(ak, bk) && same(av, bv))
+        }
+        _ => false,
+    }
+}
+
+pub fn object(pairs: Vec<(&str, Value)>) -> Value {
+    let mut object = HashMap::new();
+    for (k, v) in pairs {
+        object.insert(k.to_owned(), v);
+    }
+    Value::Object(object)
+}
+
+pub fn string(s: &str) -> Value {
+    Value::String(s.to_owned())
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::{decode, decode_with_limits, DecodeLimits};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+
+#[test]
+fn edge_values() {
+    let values = [
+        Value::Int8(i8::MAX),
+        Value::Int8(-i8::MAX),
+        Value::Int16(i16::MAX),
+        Value::Int32(-i32::MAX),
+        Value::Int64(i64::MAX),
+        Value::Float(f32::MAX),
+        Value::Double(f64::MIN_POSITIVE),
+        Value::Bit(true),
+        Value::Boolean(false),
+        string(""),
+        string(&"x".repeat(300)),
+    ];
+    for value in &values {
+        for options in [EncodeOptions::default(), EncodeOptions::fixed_width()] {
+            let bytes = encode_with(value, &options);
+            assert_same(&decode(&bytes).unwrap().0, value);
+        }
+    }
+}
+
+#[test]
+fn deep_nesting() {
+    let depth = 20_000;
+    let mut bytes = vec![0x02; depth];
+    bytes.extend(vec![0x03; depth]);
+
+    let (mut value, rest) = decode_with_limits(&bytes, &DecodeLimits::none()).unwrap();
+    assert!(rest.is_empty());
+    for _ in 1..depth {
+        value = match value {
+            Value::Array(mut a) if a.len() == 1 => a.pop().unwrap(),
+            _ => panic!("expected an array holding one value"),
+        };
+    }
+    assert!(matches!(value, Value::Array(a) if a.is_empty()));
+}
+
+#[test]
+fn containers() {
+    let value = object(vec![
+        (
+            "list",
+            Value::Array(vec![Value::Null, Value::Array(vec![])]),
+        ),
+        ("map", Value::Map(vec![(Value::Int8(1), object(vec![]))])),
+    ]);
+    let mut bytes = encode(value.clone());
+    bytes.push(0x05);
+
+    let (decoded, rest) = decode(&bytes).unwrap();
+    assert_same(&decoded, &value);
+    assert_eq!(rest, [0x05]);
+}
+
+#[test]
+fn keys_must_be_strings() {
+    // The same object with a stored length and with a closing tag.
+    for bytes in [[0x10, 0x1e, 0x05, 0x05], [0x00, 0x1e, 0x05, 0x01]] {
+        assert!(matches!(
+            decode(&bytes).unwrap_err().cause(),
+            DecodeError::InvalidKeyError(Value::Int8(1))
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::encode::{encode, encode_into, encode_into_with, encode_with, EncodeOptions};
+use emp::value::Value;
+
+fn player(name: &str, score: i32) -> Value {
+    object(vec![
+        ("name", string(name)),
+        ("score", Value::Int32(score)),
+        (
+            "items",
+            Value::Array(vec![string("sword"), string("sword")]),
+        ),
+    ])
+}
+
+#[test]
+fn appends_to_the_buffer() {
+    let (a, b) = (player("ann", 1), player("bob", -70000));
+
+    let mut out = vec![0x05];
+    encode_into(&a, &mut out);
+    encode_into(&b, &mut out);
+
+    let mut expected = vec![0x05];
+    expected.extend(encode(a.clone()));
+    expected.extend(encode(b.clone()));
+    assert_eq!(out, expected);
+
+    let (first, rest) = decode(&out[1..]).unwrap();
+    let (second, rest) = decode(rest).unwrap();
+    assert_same(&first, &a);
+    assert_same(&second, &b);
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn reused_buffer() {
+    let options = EncodeOptions::size();
+    let mut out = vec![];
+
+    for (i, value) in [player("a long name", 12), player("ann", 0), Value::Null]
+        .iter()
+        .enumerate()
+    {
+        out.clear();
+        let capacity = out.capacity();
+        encode_into_with(value, &options, &mut out);
+
+        assert_eq!(out, encode_with(value, &options));
+        assert_same(&decode(&out).unwrap().0, value);
+        if i > 0 {
+            assert_eq!(out.capacity(), capacity, "the buffer grew for value {}", i);
+        }
+    }
+}
+
+#[test]
+fn string_table_after_other_bytes() {
+    let value = player("ann", 3);
+    let mut out = b"prefix".to_vec();
+    encode_into_with(&value, &EncodeOptions::size(), &mut out);
+
+    assert_eq!(&out[..6], b"prefix");
+    assert_eq!(&out[6..], &encode_with(&value, &EncodeOptions::size())[..]);
+}
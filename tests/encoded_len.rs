@@ -0,0 +1,69 @@
+mod common;
+
+use common::{object, string};
+use emp::encode::{encode, encode_with, encoded_len, encoded_len_with, EncodeOptions};
+use emp::value::Value;
+use std::collections::HashMap;
+
+fn check(value: &Value, options: &EncodeOptions) {
+    let len = encode_with(value, options).len();
+    assert_eq!(encoded_len_with(value, options), len, "{}", value);
+}
+
+#[test]
+fn boundary_lengths() {
+    for n in [0, 1, 15, 16, 255, 256, 70_000] {
+        let s = string(&"x".repeat(n));
+        assert_eq!(encoded_len(&s), encode(s.clone()).len(), "{}", n);
+        check(&s, &EncodeOptions::speed());
+    }
+    for n in [0, 15, 16] {
+        check(
+            &Value::Array(vec![Value::Null; n]),
+            &EncodeOptions::default(),
+        );
+        check(&Value::Array(vec![Value::Null; n]), &EncodeOptions::speed());
+    }
+}
+
+#[test]
+fn number_widths() {
+    let numbers = [
+        Value::Int64(127),
+        Value::Int64(128),
+        Value::Int64(-32769),
+        Value::Int64(1 << 40),
+        Value::Int32(-1),
+        Value::Double(0.5),
+        Value::Double(0.1),
+        Value::Float(1.5),
+    ];
+    for n in &numbers {
+        check(n, &EncodeOptions::default());
+        check(n, &EncodeOptions::fixed_width());
+    }
+}
+
+#[test]
+fn containers_and_options() {
+    let value = object(vec![
+        ("map", Value::Map(vec![(Value::Int8(1), string("one"))])),
+        ("list", Value::Array(vec![string("one"), string("one")])),
+        ("empty", object(vec![])),
+    ]);
+    let mut metadata = HashMap::new();
+    metadata.insert("author".to_owned(), string("someone"));
+
+    for options in [
+        EncodeOptions::default(),
+        EncodeOptions::size(),
+        EncodeOptions {
+            string_table: Some(1),
+            canonical: true,
+            metadata: Some(metadata),
+            ..EncodeOptions::default()
+        },
+    ] {
+        check(&value, &options);
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::encode::stream::Encoder;
+use emp::encode::{encode_with, EncodeOptions};
+use emp::value::Value;
+use std::io::{self, BufWriter, Write};
+
+#[test]
+fn push_matches_encode() {
+    let value = object(vec![
+        ("list", Value::Array(vec![string("ab"), string("ab")])),
+        ("ab", Value::Map(vec![(Value::Int8(1), Value::Null)])),
+    ]);
+    for options in [EncodeOptions::default(), EncodeOptions::size()] {
+        let mut encoder = Encoder::with_options(vec![], options.clone());
+        encoder.push(&value).unwrap();
+        assert_eq!(encoder.finish().unwrap(), encode_with(&value, &options));
+    }
+}
+
+#[test]
+fn pieces() {
+    let mut encoder = Encoder::new(vec![]);
+    encoder.begin_object().unwrap();
+    encoder.key("a").unwrap();
+    encoder.begin_array().unwrap();
+    encoder.push(&Value::Int8(1)).unwrap();
+    encoder.end().unwrap();
+    encoder.end().unwrap();
+
+    // Containers opened a piece at a time always end with a closing tag.
+    let bytes = encoder.finish().unwrap();
+    assert_eq!(bytes, [0x00, 0x14, b'a', 0x02, 0x1e, 0x03, 0x01]);
+}
+
+#[test]
+fn finish_closes_everything() {
+    let mut encoder = Encoder::new(vec![]);
+    encoder.begin_object().unwrap();
+    encoder.key("list").unwrap();
+    encoder.begin_array().unwrap();
+    encoder.push(&string("two")).unwrap();
+    encoder.begin_map().unwrap();
+    encoder.push(&Value::Int8(3)).unwrap();
+    encoder.begin_object().unwrap();
+    let bytes = encoder.finish().unwrap();
+
+    let expected = object(vec![(
+        "list",
+        Value::Array(vec![
+            string("two"),
+            Value::Map(vec![(Value::Int8(3), object(vec![]))]),
+        ]),
+    )]);
+    assert_same(&decode(&bytes).unwrap().0, &expected);
+}
+
+fn is_misuse(result: io::Result<()>) -> bool {
+    matches!(result, Err(e) if e.kind() == io::ErrorKind::InvalidInput)
+}
+
+#[test]
+fn misuse() {
+    let mut encoder = Encoder::new(vec![]);
+    assert!(is_misuse(encoder.key("a")));
+    assert!(is_misuse(encoder.end()));
+
+    encoder.begin_array().unwrap();
+    assert!(is_misuse(encoder.key("a")));
+    encoder.begin_map().unwrap();
+    assert!(is_misuse(encoder.key("a")));
+    encoder.end().unwrap();
+    encoder.end().unwrap();
+
+    // Nothing can follow the data once it is complete.
+    assert!(is_misuse(encoder.push(&Value::Null)));
+    assert!(is_misuse(encoder.begin_array()));
+    assert_eq!(encoder.finish().unwrap(), [0x02, 0x2f, 0x00, 0x01, 0x03]);
+}
+
+#[test]
+fn keys_and_values_take_turns_in_objects() {
+    let mut encoder = Encoder::new(vec![]);
+    encoder.begin_object().unwrap();
+    assert!(is_misuse(encoder.push(&Value::Null)));
+    assert!(is_misuse(encoder.begin_array()));
+    encoder.key("a").unwrap();
+    assert!(is_misuse(encoder.key("b")));
+    assert!(is_misuse(encoder.end()));
+    encoder.push(&Value::Null).unwrap();
+    assert!(is_misuse(encoder.push(&Value::Null)));
+    encoder.end().unwrap();
+    assert_eq!(encoder.finish().unwrap(), [0x00, 0x14, b'a', 0x05, 0x01]);
+}
+
+#[test]
+fn keys_and_values_take_turns_in_maps() {
+    let mut encoder = Encoder::new(vec![]);
+    encoder.begin_map().unwrap();
+    assert!(is_misuse(encoder.begin_array()));
+    assert!(is_misuse(encoder.push(&Value::Null)));
+    encoder.push(&Value::Int32(1)).unwrap();
+    assert!(is_misuse(encoder.end()));
+    encoder.begin_array().unwrap();
+    encoder.end().unwrap();
+    encoder.end().unwrap();
+
+    let expected = Value::Map(vec![(Value::Int32(1), Value::Array(vec![]))]);
+    assert_same(&decode(&encoder.finish().unwrap()).unwrap().0, &expected);
+}
+
+#[test]
+fn unfinished_key_fails_finish() {
+    let mut encoder = Encoder::new(vec![]);
+    encoder.begin_object().unwrap();
+    encoder.key("a").unwrap();
+    assert_eq!(
+        encoder.finish().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
+
+// Counts how many times it is written to.
+#[derive(Default)]
+struct Writes {
+    bytes: Vec<u8>,
+    calls: usize,
+}
+
+impl Write for &mut Writes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.calls += 1;
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn buffered_writer() {
+    let mut writes = Writes::default();
+    let mut encoder = Encoder::new(BufWriter::new(&mut writes));
+    encoder.begin_array().unwrap();
+    for i in 0..1000 {
+        encoder.push(&Value::Int32(i)).unwrap();
+    }
+    drop(encoder.finish().unwrap());
+
+    // Written in one go once the encoder is finished, rather than a few bytes at a time.
+    assert_eq!(writes.calls, 1);
+    let value = Value::Array((0..1000).map(Value::Int32).collect());
+    assert_same(&decode(&writes.bytes).unwrap().0, &value);
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "encryption")]
+
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::decode::decrypt::{decode_encrypted, decrypt};
+use emp::encode::encrypt::{encode_encrypted, encrypt};
+use emp::encode::{encode, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+
+const KEY: [u8; 32] = [7; 32];
+
+fn value() -> Value {
+    object(vec![
+        ("secret", string("hunter2")),
+        ("pin", Value::Int16(1234)),
+    ])
+}
+
+#[test]
+fn round_trip() {
+    let bytes = encode_encrypted(&value(), &EncodeOptions::size(), &KEY);
+    assert_same(&decode_encrypted(&bytes, &KEY).unwrap(), &value());
+
+    let plain = encode(value());
+    assert_eq!(decrypt(&encrypt(&plain, &KEY), &KEY).unwrap(), plain);
+    assert!(decrypt(&encrypt(&[], &KEY), &KEY).unwrap().is_empty());
+}
+
+#[test]
+fn layout() {
+    // The identifier, a 12 byte nonce, the ciphertext and a 16 byte tag.
+    let plain = encode(value());
+    let bytes = encrypt(&plain, &KEY);
+    assert_eq!(bytes[0], 0x5f);
+    assert_eq!(bytes.len(), 1 + 12 + plain.len() + 16);
+
+    // A new nonce is used every time.
+    assert_ne!(bytes, encrypt(&plain, &KEY));
+}
+
+#[test]
+fn wrong_key() {
+    let bytes = encrypt(&encode(value()), &KEY);
+    assert!(matches!(
+        decrypt(&bytes, &[8; 32]).unwrap_err().cause(),
+        DecodeError::AuthenticationError
+    ));
+}
+
+#[test]
+fn tampered() {
+    let bytes = encrypt(&encode(value()), &KEY);
+
+    // The nonce, the ciphertext and the tag are all authenticated.
+    for i in [1, 13, bytes.len() - 17, bytes.len() - 1] {
+        let mut tampered = bytes.clone();
+        tampered[i] ^= 1;
+        assert!(matches!(
+            decrypt(&tampered, &KEY).unwrap_err().cause(),
+            DecodeError::AuthenticationError
+        ));
+    }
+
+    let mut tampered = bytes.clone();
+    tampered[0] = 0x4f;
+    assert!(matches!(
+        decrypt(&tampered, &KEY).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x4f, _)
+    ));
+}
+
+#[test]
+fn truncated() {
+    let bytes = encrypt(&[], &KEY);
+    assert_eq!(bytes.len(), 29);
+    assert!(matches!(
+        decrypt(&bytes[..28], &KEY).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        decrypt(&[], &KEY).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+}
+
+#[test]
+fn decode_refuses_encrypted_data() {
+    let bytes = encrypt(&encode(value()), &KEY);
+    assert!(matches!(
+        decode(&bytes).unwrap_err().cause(),
+        DecodeError::EncryptedError
+    ));
+}
@@ -0,0 +1,153 @@
+use emp::decode::decode;
+use emp::decode::events::{Event, EventReader};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+use std::collections::HashMap;
+
+fn events(bytes: &[u8]) -> Vec<(Event, usize)> {
+    EventReader::new(bytes)
+        .unwrap()
+        .map(|e| e.unwrap())
+        .collect()
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_owned())
+}
+
+#[test]
+fn order_and_offsets() {
+    let mut object = HashMap::new();
+    object.insert(
+        "a".to_owned(),
+        Value::Array(vec![Value::Int8(1), Value::Null]),
+    );
+
+    // Containers with a nibble length end where their last child does.
+    assert_eq!(
+        events(&encode(Value::Object(object))),
+        vec![
+            (Event::StartObject, 0),
+            (Event::Key("a".to_owned()), 1),
+            (Event::StartArray, 3),
+            (Event::Int8(1), 4),
+            (Event::Null, 5),
+            (Event::EndArray, 6),
+            (Event::EndObject, 6),
+        ]
+    );
+
+    let map = Value::Map(vec![(Value::Int16(-2), Value::Array(vec![]))]);
+    assert_eq!(
+        events(&encode_with(&map, &EncodeOptions::speed())),
+        vec![
+            (Event::StartMap, 0),
+            (Event::Int16(-2), 2),
+            (Event::StartArray, 5),
+            (Event::EndArray, 6),
+            (Event::EndMap, 7),
+        ]
+    );
+}
+
+#[test]
+fn header_and_string_table() {
+    let value = Value::Array(vec![string("ab"), string("ab"), Value::Bit(true)]);
+    let bytes = encode_with(&value, &EncodeOptions::size());
+    let table = bytes.len() - 4;
+
+    // The header is read up front, and references give back the string they point to.
+    let reader = EventReader::new(&bytes).unwrap();
+    assert_eq!(reader.offset(), table);
+    assert_eq!(
+        reader.map(|e| e.unwrap()).collect::<Vec<_>>(),
+        vec![
+            (Event::StartArray, table),
+            (Event::String("ab".to_owned()), table + 1),
+            (Event::String("ab".to_owned()), table + 2),
+            (Event::Bit(true), table + 3),
+            (Event::EndArray, table + 4),
+        ]
+    );
+}
+
+#[test]
+fn scalars() {
+    let values = vec![
+        (Value::Boolean(true), Event::Boolean(true)),
+        (Value::Int32(-70_000), Event::Int32(-70_000)),
+        (Value::Int64(1 << 40), Event::Int64(1 << 40)),
+        (Value::Float(-1.5), Event::Float(-1.5)),
+        (Value::Double(0.1), Event::Double(0.1)),
+        (string(""), Event::String(String::new())),
+    ];
+    for (value, event) in values {
+        assert_eq!(events(&encode(value)), vec![(event, 0)]);
+    }
+}
+
+#[test]
+fn skip_value() {
+    let long = Value::Array(vec![Value::Array(vec![Value::Int8(3); 20]); 3]);
+    let bytes = encode(Value::Array(vec![long, Value::Int32(7)]));
+    let mut reader = EventReader::new(&bytes).unwrap();
+
+    assert_eq!(reader.next().unwrap().unwrap().0, Event::StartArray);
+    reader.skip_value().unwrap();
+    assert_eq!(reader.next().unwrap().unwrap().0, Event::Int32(7));
+
+    // There is nothing left to skip inside of the array.
+    reader.skip_value().unwrap();
+    assert_eq!(reader.next().unwrap().unwrap().0, Event::EndArray);
+    assert!(reader.next().is_none());
+    assert_eq!(reader.offset(), bytes.len());
+}
+
+#[test]
+fn stops_after_an_error() {
+    let bytes = [0x22, 0x05];
+    let mut reader = EventReader::new(&bytes).unwrap();
+
+    assert_eq!(reader.next().unwrap().unwrap().0, Event::StartArray);
+    assert_eq!(reader.next().unwrap().unwrap().0, Event::Null);
+    assert!(matches!(
+        reader.next().unwrap().unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(reader.next().is_none());
+
+    assert!(matches!(
+        EventReader::new(&[0x1f, 0x80])
+            .map(|_| ())
+            .unwrap_err()
+            .cause(),
+        DecodeError::UnsupportedFeatureError(0x80)
+    ));
+}
+
+#[test]
+fn keys_match_decode() {
+    // A null map key, an object ending straight after a key, and the same inside of an array.
+    for (bytes, error) in [
+        (
+            &[0x2f, 0x10, 0x05, 0x1e][..],
+            "Invalid Key: `null` at byte 2 ($)",
+        ),
+        (
+            &[0x00, 0x14, b'a', 0x01],
+            "Unmatched Key: `a` at byte 3 ($)",
+        ),
+        (
+            &[0x02, 0x00, 0x14, b'a', 0x01, 0x03],
+            "Unmatched Key: `a` at byte 4 ($[0])",
+        ),
+    ] {
+        let found = EventReader::new(bytes)
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(found.to_string(), error);
+        assert_eq!(decode(bytes).unwrap_err().to_string(), error);
+    }
+}
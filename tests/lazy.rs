@@ -0,0 +1,207 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::decode::lazy::LazyValue;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::path::Segment;
+use emp::value::Value;
+
+fn players() -> Value {
+    object(vec![(
+        "players",
+        Value::Array(vec![
+            object(vec![("name", string("a"))]),
+            object(vec![("name", string("b")), ("score", Value::Int32(3))]),
+        ]),
+    )])
+}
+
+#[test]
+fn lookups() {
+    let bytes = encode(players());
+    let lazy = LazyValue::new(&bytes).unwrap();
+
+    let second = lazy
+        .get("players")
+        .unwrap()
+        .unwrap()
+        .index(1)
+        .unwrap()
+        .unwrap();
+    assert_same(
+        second.get("name").unwrap().unwrap().value().unwrap(),
+        &string("b"),
+    );
+    assert_eq!(second.len().unwrap(), 2);
+
+    let players = lazy
+        .lookup(&Segment::Key("players".to_owned()))
+        .unwrap()
+        .unwrap();
+    assert!(players.index(2).unwrap().is_none());
+    assert!(players.get("0").unwrap().is_none());
+    assert!(lazy.get("missing").unwrap().is_none());
+    assert!(lazy.index(0).unwrap().is_none());
+}
+
+#[test]
+fn map_keys() {
+    let map = Value::Map(vec![
+        (Value::Int8(1), string("int8")),
+        (string("2"), string("string")),
+        (Value::Int64(2), string("int64")),
+        (Value::Bit(true), string("bit")),
+    ]);
+    let bytes = encode(map);
+    let lazy = LazyValue::new(&bytes).unwrap();
+
+    // Integer keys of any size are found by index, string keys by name.
+    assert_same(
+        lazy.index(1).unwrap().unwrap().value().unwrap(),
+        &string("int8"),
+    );
+    assert_same(
+        lazy.index(2).unwrap().unwrap().value().unwrap(),
+        &string("int64"),
+    );
+    assert_same(
+        lazy.get("2").unwrap().unwrap().value().unwrap(),
+        &string("string"),
+    );
+    assert!(lazy.index(0).unwrap().is_none());
+
+    let keys = lazy.keys().unwrap();
+    assert_eq!(keys.len(), 4);
+    assert_same(keys[3], &Value::Bit(true));
+}
+
+#[test]
+fn lengths() {
+    let bytes = encode_with(
+        &Value::Array(vec![Value::Null; 20]),
+        &EncodeOptions::default(),
+    );
+    assert_eq!(LazyValue::new(&bytes).unwrap().len().unwrap(), 20);
+
+    for value in [
+        Value::Array(vec![]),
+        object(vec![]),
+        Value::Int32(5),
+        string("abc"),
+    ] {
+        let bytes = encode(value);
+        let lazy = LazyValue::new(&bytes).unwrap();
+        assert_eq!(lazy.len().unwrap(), 0);
+        assert!(lazy.is_empty().unwrap());
+        assert!(lazy.keys().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn header_and_string_table() {
+    let bytes = encode_with(&players(), &EncodeOptions::size());
+    let lazy = LazyValue::new(&bytes).unwrap();
+    let first = lazy
+        .get("players")
+        .unwrap()
+        .unwrap()
+        .index(0)
+        .unwrap()
+        .unwrap();
+    assert_same(
+        first.get("name").unwrap().unwrap().value().unwrap(),
+        &string("a"),
+    );
+    assert_same(lazy.value().unwrap(), &players());
+}
+
+#[test]
+fn only_used_values_are_decoded() {
+    // An array holding a well formed value and then a string which is not UTF-8.
+    let bytes = [0x22, 0x05, 0x14, 0xff];
+    let lazy = LazyValue::new(&bytes).unwrap();
+
+    assert_same(
+        lazy.index(0).unwrap().unwrap().value().unwrap(),
+        &Value::Null,
+    );
+    assert!(matches!(
+        lazy.index(1).unwrap().unwrap().value().unwrap_err().cause(),
+        DecodeError::StringDecodeError(_)
+    ));
+    assert!(lazy.value().is_err());
+}
+
+#[test]
+fn malformed_containers() {
+    // The array claims three values but only holds two.
+    let bytes = [0x32, 0x05, 0x05];
+    let lazy = LazyValue::new(&bytes).unwrap();
+    assert!(matches!(
+        lazy.len().unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        lazy.index(0).map(|_| ()).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+
+    assert!(matches!(
+        LazyValue::new(&[0x1f, 0x80])
+            .map(|_| ())
+            .unwrap_err()
+            .cause(),
+        DecodeError::UnsupportedFeatureError(0x80)
+    ));
+}
+
+#[test]
+fn keys_match_decode() {
+    // An integer object key, a null map key, an object ending straight after a key, and a bad key one level down.
+    for (bytes, error) in [
+        (&[0x10, 0x1e, 0x05][..], "Invalid Key: `1B` at byte 1 ($)"),
+        (
+            &[0x2f, 0x10, 0x05, 0x1e],
+            "Invalid Key: `null` at byte 2 ($)",
+        ),
+        (
+            &[0x00, 0x14, b'a', 0x01],
+            "Unmatched Key: `a` at byte 3 ($)",
+        ),
+        (
+            &[0x12, 0x10, 0x1e, 0x05],
+            "Invalid Key: `1B` at byte 2 ($[0])",
+        ),
+    ] {
+        let lazy = LazyValue::new(bytes).unwrap();
+        let found = match lazy.index(0) {
+            Ok(Some(child)) => child.len().unwrap_err(),
+            Ok(None) => lazy.len().unwrap_err(),
+            Err(e) => e,
+        };
+        assert_eq!(found.to_string(), error);
+        assert_eq!(decode(bytes).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn duplicate_keys() {
+    // The last value for a key wins in objects and maps, the same as when they are decoded.
+    let bytes = [0x00, 0x14, b'a', 0x1e, 0x14, b'a', 0x2e, 0x01];
+    let lazy = LazyValue::new(&bytes).unwrap();
+    assert_eq!(
+        lazy.get("a").unwrap().unwrap().value().unwrap().to_string(),
+        "2B"
+    );
+    assert_eq!(lazy.len().unwrap(), 1);
+    assert_same(lazy.value().unwrap(), &decode(&bytes).unwrap().0);
+
+    let bytes = [0x2f, 0x00, 0x1e, 0x14, b'a', 0x1e, 0x14, b'b', 0x01];
+    let lazy = LazyValue::new(&bytes).unwrap();
+    assert_eq!(
+        lazy.index(1).unwrap().unwrap().value().unwrap().to_string(),
+        "\"b\""
+    );
+}
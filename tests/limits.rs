@@ -0,0 +1,190 @@
+mod common;
+
+use common::{object, string};
+use emp::decode::borrowed::decode_borrowed_with_limits;
+use emp::decode::push::PushDecoder;
+use emp::decode::stream::Decoder;
+use emp::decode::{decode, decode_with_limits, DecodeLimits};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::{DecodeError, ParseError};
+use emp::value::parse::{self, ParseLimits};
+use emp::value::Value;
+
+fn nested(depth: usize) -> Vec<u8> {
+    let mut bytes = vec![0x12; depth];
+    bytes.push(0x05);
+    bytes
+}
+
+// Decodes `bytes` with every decoder which takes limits, returning each result without its location.
+fn decode_all(bytes: &[u8], limits: &DecodeLimits) -> Vec<Result<(), DecodeError>> {
+    let strip = |r: Result<(), DecodeError>| r.map_err(|e| e.cause().to_string());
+    let mut results = vec![
+        decode_with_limits(bytes, limits).map(|_| ()),
+        decode_borrowed_with_limits(bytes, limits).map(|_| ()),
+        Decoder::with_limits(bytes, limits.clone())
+            .decode()
+            .map(|_| ()),
+    ];
+
+    let mut push = PushDecoder::with_limits(limits.clone());
+    push.feed(bytes);
+    results.push(match push.next_value() {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(DecodeError::EOFError),
+        Err(e) => Err(e),
+    });
+
+    #[cfg(feature = "parallel")]
+    results.push(emp::decode::parallel::decode_parallel_with_limits(bytes, limits).map(|_| ()));
+
+    // Every decoder has to agree on the error, apart from where it is.
+    let first = strip(results[0].as_ref().map(|_| ()).map_err(clone));
+    for r in &results {
+        assert_eq!(strip(r.as_ref().map(|_| ()).map_err(clone)), first);
+    }
+    results
+}
+
+fn clone(e: &DecodeError) -> DecodeError {
+    match e.cause() {
+        DecodeError::DepthLimitError(l) => DecodeError::DepthLimitError(*l),
+        DecodeError::ElementLimitError(l) => DecodeError::ElementLimitError(*l),
+        DecodeError::StringLengthLimitError(l) => DecodeError::StringLengthLimitError(*l),
+        DecodeError::AllocationLimitError(l) => DecodeError::AllocationLimitError(*l),
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn decode_has_no_depth_limit() {
+    assert!(decode(&nested(1000)).is_ok());
+    assert!(parse::from_str(&("[".repeat(200) + &"]".repeat(200))).is_ok());
+}
+
+#[test]
+fn default_depth() {
+    assert!(decode_all(&nested(128), &DecodeLimits::default())[0].is_ok());
+    let results = decode_all(&nested(129), &DecodeLimits::default());
+    assert!(matches!(
+        results[0].as_ref().unwrap_err().cause(),
+        DecodeError::DepthLimitError(128)
+    ));
+
+    let text = "[".repeat(129) + &"]".repeat(129);
+    assert!(matches!(
+        parse::from_str_with_limits(&text, &ParseLimits::default()),
+        Err(ParseError::DepthLimitError(128))
+    ));
+}
+
+#[test]
+fn untrusted_input() {
+    // Nested far too deeply for the returned value to be dropped or printed, which the default limits refuse
+    // before getting anywhere near that deep.
+    for result in decode_all(&nested(1_000_000), &DecodeLimits::default()) {
+        assert!(matches!(
+            result.unwrap_err().cause(),
+            DecodeError::DepthLimitError(128)
+        ));
+    }
+
+    let text = "[".repeat(1_000_000) + &"]".repeat(1_000_000);
+    assert!(matches!(
+        parse::from_str_with_limits(&text, &ParseLimits::default()),
+        Err(ParseError::DepthLimitError(128))
+    ));
+}
+
+#[test]
+fn elements() {
+    // An array of three values is four elements.
+    let bytes = encode(Value::Array(vec![Value::Null; 3]));
+    let limits = |elements| DecodeLimits {
+        elements,
+        ..DecodeLimits::none()
+    };
+
+    assert!(decode_all(&bytes, &limits(4))[0].is_ok());
+    assert!(matches!(
+        decode_all(&bytes, &limits(3))[0]
+            .as_ref()
+            .unwrap_err()
+            .cause(),
+        DecodeError::ElementLimitError(3)
+    ));
+}
+
+#[test]
+fn string_length() {
+    let limits = |string_length| DecodeLimits {
+        string_length,
+        ..DecodeLimits::none()
+    };
+    // Lengths stored in the tag, in a following integer and ended by a closing tag.
+    for bytes in [
+        encode(string("a")),
+        encode(string(&"a".repeat(100))),
+        encode_with(&string(&"a".repeat(100)), &EncodeOptions::fixed_width()),
+    ] {
+        let len = bytes.iter().filter(|b| **b == b'a').count();
+        assert!(decode_all(&bytes, &limits(len))[0].is_ok());
+        assert!(matches!(
+            decode_all(&bytes, &limits(len - 1))[0]
+                .as_ref()
+                .unwrap_err()
+                .cause(),
+            DecodeError::StringLengthLimitError(l) if *l == len - 1
+        ));
+    }
+}
+
+#[test]
+fn allocation() {
+    let value = object(vec![
+        ("name", string(&"a".repeat(600))),
+        ("list", Value::Array(vec![Value::Int32(-70000); 50])),
+    ]);
+    let bytes = encode(value);
+    let limits = |allocation| DecodeLimits {
+        allocation,
+        ..DecodeLimits::none()
+    };
+
+    assert!(decode_all(&bytes, &limits(1 << 20))[0].is_ok());
+    assert!(matches!(
+        decode_all(&bytes, &limits(1000))[0]
+            .as_ref()
+            .unwrap_err()
+            .cause(),
+        DecodeError::AllocationLimitError(1000)
+    ));
+}
+
+#[test]
+fn string_table_counts_towards_the_limits() {
+    let strings: Vec<Value> = (0..10)
+        .map(|i| Value::String(format!("repeated string {}", i)))
+        .collect();
+    let mut value = strings.clone();
+    value.extend(strings);
+    let value = Value::Array(value);
+
+    let plain = encode(value.clone());
+    let table = encode_with(
+        &value,
+        &EncodeOptions {
+            string_table: Some(2),
+            ..EncodeOptions::default()
+        },
+    );
+
+    // The array and its 20 strings, plus the string table and its 10 strings.
+    let limits = |elements| DecodeLimits {
+        elements,
+        ..DecodeLimits::none()
+    };
+    assert!(decode_all(&plain, &limits(21))[0].is_ok());
+    assert!(decode_all(&table, &limits(21))[0].is_err());
+    assert!(decode_all(&table, &limits(32))[0].is_ok());
+}
@@ -0,0 +1,176 @@
+use emp::decode::borrowed::decode_borrowed;
+use emp::decode::decode;
+use emp::decode::push::PushDecoder;
+use emp::decode::stream::Decoder;
+use emp::decode::validate::validate;
+use emp::encode::encode;
+use emp::errors::DecodeError;
+use emp::value::parse;
+use emp::value::path;
+
+// `{"players": [1, 2, {"name": "abc"}]}`, where the string "abc" starts at byte 20.
+fn players() -> Vec<u8> {
+    let value = parse::from_str(r#"{"players": [1, 2, {"name": "abc"}]}"#).unwrap();
+    let bytes = encode(value);
+    assert_eq!(&bytes[20..], [0x34, b'a', b'b', b'c']);
+    bytes
+}
+
+// Decodes `bytes` with every decoder, checking they all fail with the same error, which is returned.
+fn decode_all(bytes: &[u8], finished: bool) -> DecodeError {
+    let error = decode(bytes).unwrap_err();
+    let errors = [
+        decode_borrowed(bytes).map(|_| ()).unwrap_err(),
+        validate(bytes).map(|_| ()).unwrap_err(),
+        Decoder::new(bytes).decode().map(|_| ()).unwrap_err(),
+    ];
+    for e in errors.iter() {
+        assert_eq!(e.to_string(), error.to_string(), "{:02x?}", bytes);
+        assert_eq!(e.location(), error.location());
+    }
+
+    // The push decoder waits for more data instead of running out of it.
+    let mut push = PushDecoder::new();
+    push.feed(bytes);
+    match push.next_value() {
+        Err(e) => assert_eq!(e.to_string(), error.to_string(), "{:02x?}", bytes),
+        Ok(None) => assert!(!finished, "{:02x?}", bytes),
+        Ok(Some(v)) => panic!("decoded {} from {:02x?}", v, bytes),
+    }
+
+    error
+}
+
+fn check(bytes: &[u8], finished: bool, offset: u64, at: &str, display: &str) {
+    let error = decode_all(bytes, finished);
+    let location = error.location().unwrap();
+    assert_eq!(location.offset, offset);
+    assert_eq!(location.path, path::from_str(at).unwrap());
+    assert_eq!(error.to_string(), display);
+}
+
+#[test]
+fn truncated() {
+    let bytes = players();
+    check(
+        &bytes[..21],
+        false,
+        21,
+        "$.players[2].name",
+        "Unexpected EOF at byte 21 ($.players[2].name)",
+    );
+    check(
+        &bytes[..23],
+        false,
+        23,
+        "$.players[2].name",
+        "Unexpected EOF at byte 23 ($.players[2].name)",
+    );
+    check(
+        &[0x32, 0x05, 0x0a, 0x05],
+        false,
+        4,
+        "$[1]",
+        "Unexpected EOF at byte 4 ($[1])",
+    );
+}
+
+#[test]
+fn invalid_string() {
+    let mut bytes = players();
+    bytes[22] = 0xff;
+    let error = decode_all(&bytes, true);
+
+    assert!(matches!(error.cause(), DecodeError::StringDecodeError(_)));
+    assert_eq!(error.location().unwrap().offset, 20);
+    assert_eq!(
+        error.location().unwrap().path.to_string(),
+        "$.players[2].name"
+    );
+}
+
+#[test]
+fn unexpected_byte() {
+    // The error is at the byte, which is also in the error itself.
+    let bytes = [0x00, 0x14, b'a', 0x02, 0x05, 0x01];
+    check(
+        &bytes,
+        true,
+        5,
+        "$.a[1]",
+        "Unexpected Byte: 0x01 at byte 5 ($.a[1])",
+    );
+    assert!(matches!(
+        decode(&bytes).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x01, 5)
+    ));
+}
+
+#[test]
+fn keys() {
+    // A bad key is at its first byte, inside of the container it is in.
+    check(
+        &[0x2f, 0x10, 0x05, 0x1e],
+        true,
+        2,
+        "$",
+        "Invalid Key: `null` at byte 2 ($)",
+    );
+    check(
+        &[0x12, 0x00, 0x14, b'a', 0x1e, 0x1e, 0x05, 0x01],
+        true,
+        5,
+        "$[0]",
+        "Invalid Key: `1B` at byte 5 ($[0])",
+    );
+
+    // An object ends at its closing tag even after a key, while a map reads the tag as the value of the key.
+    check(
+        &[0x00, 0x14, b'a', 0x01],
+        true,
+        3,
+        "$",
+        "Unmatched Key: `a` at byte 3 ($)",
+    );
+    check(
+        &[0x2f, 0x00, 0x1e, 0x01],
+        true,
+        3,
+        "$[1]",
+        "Unexpected Byte: 0x01 at byte 3 ($[1])",
+    );
+}
+
+#[test]
+fn unsupported_features() {
+    check(
+        &[0x1f, 0x80, 0x05],
+        true,
+        0,
+        "$",
+        "Unsupported features: 0b10000000 at byte 0 ($)",
+    );
+}
+
+#[test]
+fn eof_after_a_key() {
+    let bytes = players();
+    check(
+        &bytes[..20],
+        false,
+        20,
+        "$.players[2].name",
+        "Unexpected EOF at byte 20 ($.players[2].name)",
+    );
+}
+
+#[test]
+fn errors_which_are_not_in_the_data() {
+    let error = Decoder::new(&[][..]).decode().unwrap_err();
+    assert_eq!(error.location().unwrap().offset, 0);
+    assert!(DecodeError::EOFError.location().is_none());
+    assert!(matches!(
+        DecodeError::EOFError.cause(),
+        DecodeError::EOFError
+    ));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::{DecodeError, ParseError};
+use emp::value::{json, parse, Value};
+
+fn map() -> Value {
+    Value::Map(vec![
+        (Value::Int32(1), string("a")),
+        (
+            Value::Int8(-2),
+            Value::Map(vec![(Value::Bit(true), Value::Null)]),
+        ),
+        (string("c"), Value::Double(0.5)),
+        (Value::Boolean(false), Value::Array(vec![])),
+    ])
+}
+
+#[test]
+fn round_trip() {
+    assert_same(&decode(&encode(map())).unwrap().0, &map());
+
+    // More pairs than fit in a nibble end with a closing tag.
+    let long = Value::Map((0..16).map(|i| (Value::Int16(i), Value::Null)).collect());
+    let bytes = encode(long.clone());
+    assert_eq!(bytes[..2], [0x2f, 0x00]);
+    assert_eq!(bytes[bytes.len() - 1], 0x01);
+    assert_same(&decode(&bytes).unwrap().0, &long);
+
+    let empty = encode_with(&Value::Map(vec![]), &EncodeOptions::speed());
+    assert_eq!(empty, [0x2f, 0x00, 0x01]);
+    assert_same(&decode(&empty).unwrap().0, &Value::Map(vec![]));
+}
+
+#[test]
+fn layout() {
+    let bytes = encode(Value::Map(vec![(Value::Int8(1), Value::Null)]));
+    assert_eq!(bytes, [0x2f, 0x10, 0x1e, 0x05]);
+}
+
+#[test]
+fn keys_keep_their_order() {
+    let value = Value::Map(vec![
+        (Value::Int32(3), Value::Null),
+        (Value::Int32(1), Value::Null),
+        (Value::Int32(3), Value::Bit(true)),
+    ]);
+    assert_same(&decode(&encode(value.clone())).unwrap().0, &value);
+}
+
+#[test]
+fn text_round_trip() {
+    assert_same(&parse::from_str(&map().to_string()).unwrap(), &map());
+
+    let parsed = parse::from_str(r#"{1: "a", 2b: "b"}"#).unwrap();
+    let expected = Value::Map(vec![
+        (Value::Int32(1), string("a")),
+        (Value::Bit(false), string("b")),
+    ]);
+    assert_same(&parsed, &expected);
+}
+
+#[test]
+fn string_keys_display_as_an_object() {
+    let value = Value::Map(vec![(string("a"), Value::Int32(1))]);
+    assert_eq!(value.to_string(), r#"{"a": 1}"#);
+    let parsed = parse::from_str(&value.to_string()).unwrap();
+    assert_same(&parsed, &object(vec![("a", Value::Int32(1))]));
+
+    let parsed = parse::from_str(&Value::Map(vec![]).to_string()).unwrap();
+    assert!(matches!(parsed, Value::Object(o) if o.is_empty()));
+
+    // One key which is not a string is enough to keep it a Map.
+    let value = Value::Map(vec![
+        (string("a"), Value::Null),
+        (Value::Int8(1), Value::Null),
+    ]);
+    assert!(matches!(parse::from_str(&value.to_string()).unwrap(), Value::Map(m) if m.len() == 2));
+}
+
+#[test]
+fn json_keys() {
+    let value = Value::Map(vec![
+        (Value::Int32(1), string("a")),
+        (Value::Boolean(true), Value::Null),
+        (string("s"), Value::Int8(2)),
+    ]);
+    assert_eq!(
+        json::to_json(value),
+        serde_json::json!({"1": "a", "true": null, "s": 2})
+    );
+}
+
+#[test]
+fn invalid_keys() {
+    assert!(matches!(
+        parse::from_str("{[1]: 2}"),
+        Err(ParseError::InvalidKeyError(Value::Array(_)))
+    ));
+    assert!(matches!(
+        parse::from_str("{null: 2}"),
+        Err(ParseError::InvalidKeyError(Value::Null))
+    ));
+    assert!(matches!(
+        parse::from_str("{1: 2"),
+        Err(ParseError::EOFError)
+    ));
+
+    assert!(matches!(
+        decode(&[0x2f, 0x10, 0x05, 0x05]).unwrap_err().cause(),
+        DecodeError::InvalidKeyError(Value::Null)
+    ));
+    assert!(matches!(
+        decode(&[0x2f, 0x10, 0x02, 0x03, 0x05]).unwrap_err().cause(),
+        DecodeError::InvalidKeyError(Value::Array(_))
+    ));
+}
+
+#[test]
+fn malformed() {
+    assert!(matches!(
+        decode(&[0x2f]).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        decode(&[0x2f, 0x12]).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x12, 1)
+    ));
+    assert!(matches!(
+        decode(&[0x2f, 0x20, 0x1e, 0x05]).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        decode(&[0x2f, 0x00, 0x1e, 0x05]).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+}
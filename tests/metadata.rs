@@ -0,0 +1,100 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::{decode, decode_metadata};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+use std::collections::HashMap;
+
+fn with_metadata(value: &Value, metadata: HashMap<String, Value>) -> Vec<u8> {
+    encode_with(
+        value,
+        &EncodeOptions {
+            metadata: Some(metadata),
+            ..EncodeOptions::default()
+        },
+    )
+}
+
+#[test]
+fn round_trip() {
+    let mut metadata = HashMap::new();
+    metadata.insert("author".to_owned(), string("someone"));
+    metadata.insert("version".to_owned(), Value::Int32(3));
+
+    // The metadata is stored before the string table, so it never refers to it.
+    let value = Value::Array(vec![string("someone"), string("someone")]);
+    let bytes = encode_with(
+        &value,
+        &EncodeOptions {
+            metadata: Some(metadata.clone()),
+            string_table: Some(1),
+            ..EncodeOptions::default()
+        },
+    );
+
+    let read = decode_metadata(&bytes).unwrap().unwrap();
+    assert_same(&Value::Object(read), &Value::Object(metadata));
+    assert_same(&decode(&bytes).unwrap().0, &value);
+}
+
+#[test]
+fn layout() {
+    let mut metadata = HashMap::new();
+    metadata.insert("a".to_owned(), Value::Null);
+    assert_eq!(
+        with_metadata(&Value::Null, metadata),
+        [0x1f, 0x02, 0x3f, 0x10, 0x14, b'a', 0x05, 0x05]
+    );
+
+    let bytes = with_metadata(&Value::Null, HashMap::new());
+    assert_eq!(bytes, [0x1f, 0x02, 0x3f, 0x00, 0x01, 0x05]);
+    assert!(decode_metadata(&bytes).unwrap().unwrap().is_empty());
+}
+
+#[test]
+fn missing() {
+    assert!(decode_metadata(&encode(Value::Null)).unwrap().is_none());
+    assert!(decode_metadata(&[]).unwrap().is_none());
+}
+
+#[test]
+fn data_is_not_decoded() {
+    let mut metadata = HashMap::new();
+    metadata.insert("a".to_owned(), Value::Int8(1));
+    let mut bytes = with_metadata(&Value::Null, metadata);
+    bytes.pop();
+    bytes.push(0x02);
+
+    assert_same(
+        &Value::Object(decode_metadata(&bytes).unwrap().unwrap()),
+        &object(vec![("a", Value::Int8(1))]),
+    );
+    assert!(decode(&bytes).is_err());
+}
+
+#[test]
+fn errors() {
+    // The header says there is metadata but it is missing.
+    assert!(matches!(
+        decode_metadata(&[0x1f, 0x02, 0x05]).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x05, 2)
+    ));
+    assert!(matches!(
+        decode(&[0x1f, 0x02, 0x05]).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x05, 2)
+    ));
+    assert!(matches!(
+        decode_metadata(&[0x1f, 0x02]).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+
+    // Metadata which is not an object.
+    assert!(matches!(
+        decode_metadata(&[0x1f, 0x02, 0x3f, 0x05, 0x05])
+            .unwrap_err()
+            .cause(),
+        DecodeError::UnexpectedByteError(0x05, 3)
+    ));
+}
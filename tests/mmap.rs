@@ -0,0 +1,88 @@
+#![cfg(feature = "mmap")]
+
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::decode::mmap::MappedFile;
+use emp::encode::{encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+use std::fs;
+use std::path::PathBuf;
+
+// A file in the temporary directory which is removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, bytes: &[u8]) -> TempFile {
+        let path = std::env::temp_dir().join(format!("{}-{}.emp", name, std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        TempFile(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn save() -> Value {
+    object(vec![
+        ("name", string("a save with a long enough name")),
+        (
+            "players",
+            Value::Array(vec![string("ann"), string("bob"), string("ann")]),
+        ),
+        ("score", Value::Int32(-70000)),
+    ])
+}
+
+#[test]
+fn matches_decode() {
+    for (name, options) in [
+        ("default", EncodeOptions::default()),
+        ("size", EncodeOptions::size()),
+    ] {
+        let file = TempFile::new(
+            &format!("emp-mmap-{}", name),
+            &encode_with(&save(), &options),
+        );
+        let expected = decode(&fs::read(&file.0).unwrap()).unwrap().0;
+
+        let mapped = MappedFile::open(&file.0).unwrap();
+        assert_eq!(mapped.bytes(), &fs::read(&file.0).unwrap()[..]);
+        assert_same(&mapped.borrowed().unwrap().to_value(), &expected);
+
+        let lazy = mapped.lazy().unwrap();
+        assert_same(lazy.value().unwrap(), &expected);
+        assert_same(
+            lazy.get("players")
+                .unwrap()
+                .unwrap()
+                .index(2)
+                .unwrap()
+                .unwrap()
+                .value()
+                .unwrap(),
+            &string("ann"),
+        );
+    }
+}
+
+#[test]
+fn errors() {
+    let missing = std::env::temp_dir().join("emp-mmap-missing.emp");
+    assert!(matches!(
+        MappedFile::open(&missing),
+        Err(DecodeError::IOError(_))
+    ));
+
+    let file = TempFile::new("emp-mmap-truncated", &[0x34, b'a']);
+    let mapped = MappedFile::open(&file.0).unwrap();
+    assert!(matches!(
+        mapped.borrowed().unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+}
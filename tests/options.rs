@@ -0,0 +1,126 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+
+// Values on either side of every packed or trimmed encoding.
+fn edges() -> Vec<Value> {
+    vec![
+        Value::Int8(0),
+        Value::Int8(1),
+        Value::Int8(15),
+        Value::Int8(16),
+        Value::Int8(-1),
+        Value::Int8(i8::MAX),
+        Value::Int16(0),
+        Value::Int16(255),
+        Value::Int16(-256),
+        Value::Int16(i16::MAX),
+        Value::Int32(0),
+        Value::Int32(-1),
+        Value::Int32(1 << 24),
+        Value::Int32(i32::MAX),
+        Value::Int64(-(1 << 40)),
+        Value::Int64(i64::MAX),
+        Value::Float(0.0),
+        Value::Float(-1.5),
+        Value::Double(3.25e100),
+        string(""),
+        string("fifteen bytes.."),
+        string("sixteen bytes..."),
+        Value::Array(vec![]),
+        Value::Array(vec![Value::Null; 15]),
+        Value::Array(vec![Value::Bit(true); 16]),
+        object(vec![("a", Value::Boolean(false)), ("b", string("a"))]),
+    ]
+}
+
+#[test]
+fn default_matches_encode() {
+    for value in edges() {
+        assert_eq!(
+            encode_with(&value, &EncodeOptions::default()),
+            encode(value)
+        );
+    }
+}
+
+#[test]
+fn speed_decodes_the_same_as_default() {
+    for value in edges() {
+        let speed = encode_with(&value, &EncodeOptions::speed());
+        let default = encode_with(&value, &EncodeOptions::default());
+        assert_same(&decode(&speed).unwrap().0, &decode(&default).unwrap().0);
+        assert_same(&decode(&speed).unwrap().0, &value);
+    }
+}
+
+#[test]
+fn size_decodes_the_same_as_default() {
+    let value = Value::Array(edges().into_iter().chain(edges()).collect());
+    let size = encode_with(&value, &EncodeOptions::size());
+    assert!(size.len() < encode(value.clone()).len());
+    assert_same(&decode(&size).unwrap().0, &value);
+}
+
+#[test]
+fn speed_skips_packing_and_trimming() {
+    let speed = EncodeOptions::speed();
+    assert_eq!(encode_with(&Value::Int8(5), &speed), [0x0e, 0x05]);
+    assert_eq!(encode(Value::Int8(5)), [0x5e]);
+    assert_eq!(encode_with(&Value::Int32(1), &speed), [0x09, 0, 0, 0, 1]);
+    assert_eq!(encode(Value::Int32(1)), [0x39, 1]);
+    assert_eq!(encode_with(&Value::Int16(-1), &speed), [0x8d, 0, 1]);
+    assert_eq!(encode(Value::Int16(-1)), [0x9d, 1]);
+}
+
+#[test]
+fn fixed_width_uses_closing_tags() {
+    let fixed = EncodeOptions::fixed_width();
+    let value = Value::Array(vec![string("ab"), Value::Int8(5)]);
+    assert_eq!(
+        encode_with(&value, &fixed),
+        [0x02, 0x04, b'a', b'b', 0x04, 0x0e, 0x05, 0x03]
+    );
+    assert_eq!(
+        encode_with(&value, &EncodeOptions::speed()),
+        [0x22, 0x24, b'a', b'b', 0x0e, 0x05]
+    );
+
+    for value in edges() {
+        assert_same(&decode(&encode_with(&value, &fixed)).unwrap().0, &value);
+    }
+}
+
+#[test]
+fn size_stores_a_string_table() {
+    let value = Value::Array(vec![string("a"), string("a"), string("bc"), string("bc")]);
+    assert_eq!(
+        encode_with(&value, &EncodeOptions::size()),
+        [0x1f, 0x01, 0x0f, 0x12, 0x24, b'b', b'c', 0x42, 0x14, b'a', 0x14, b'a', 0x16, 0x16]
+    );
+}
+
+#[test]
+fn header_errors() {
+    assert!(matches!(
+        decode(&[0x1f, 0x80, 0x05]).unwrap_err().cause(),
+        DecodeError::UnsupportedFeatureError(0x80)
+    ));
+    // The header promises a string table which is not there.
+    assert!(matches!(
+        decode(&[0x1f, 0x01, 0x05]).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x05, 2)
+    ));
+    assert!(matches!(
+        decode(&[0x1f]).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        decode(&[0x1f, 0x01]).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+}
@@ -0,0 +1,139 @@
+#![cfg(feature = "parallel")]
+
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::parallel::{decode_parallel, decode_parallel_with_limits};
+use emp::decode::{decode, decode_with_limits, DecodeLimits};
+use emp::encode::parallel::encode_parallel;
+use emp::encode::{encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+use std::mem::discriminant;
+
+// Enough rows and bytes for the containers to be split between threads.
+fn rows(n: i32) -> Value {
+    Value::Array(
+        (0..n)
+            .map(|i| {
+                object(vec![
+                    ("id", Value::Int32(i)),
+                    ("name", Value::String(format!("row {}", i % 50))),
+                    ("values", Value::Array(vec![Value::Double(0.5); 20])),
+                ])
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn encode_matches_sequential() {
+    let value = object(vec![
+        ("rows", rows(300)),
+        ("map", Value::Map(vec![(Value::Int8(1), rows(100))])),
+    ]);
+    for options in [
+        EncodeOptions::default(),
+        EncodeOptions::fixed_width(),
+        EncodeOptions::size(),
+    ] {
+        assert_eq!(
+            encode_parallel(&value, &options),
+            encode_with(&value, &options)
+        );
+    }
+    for value in [Value::Null, Value::Array(vec![]), rows(63), rows(64)] {
+        assert_eq!(
+            encode_parallel(&value, &EncodeOptions::default()),
+            encode_with(&value, &EncodeOptions::default())
+        );
+    }
+}
+
+#[test]
+fn decode_matches_sequential() {
+    let value = Value::Array(vec![rows(300), string("end")]);
+    for options in [EncodeOptions::default(), EncodeOptions::size()] {
+        let mut bytes = encode_with(&value, &options);
+        bytes.push(0x05);
+
+        let (decoded, rest) = decode_parallel(&bytes).unwrap();
+        assert_same(&decoded, &value);
+        assert_eq!(rest, &[0x05]);
+    }
+}
+
+#[test]
+fn errors_match_sequential() {
+    let bytes = encode_with(&rows(300), &EncodeOptions::default());
+
+    // Truncated, and a name which is not UTF-8 in the middle of the rows.
+    let mut invalid = bytes.clone();
+    let name = invalid.windows(6).rposition(|w| w == b"row 25").unwrap();
+    invalid[name] = 0xff;
+
+    for input in [&bytes[..bytes.len() - 1], &invalid[..]] {
+        assert_eq!(
+            decode_parallel(input).unwrap_err().to_string(),
+            decode(input).unwrap_err().to_string()
+        );
+    }
+}
+
+#[test]
+fn changed_bytes_match_sequential() {
+    let value = object(vec![
+        ("rows", rows(200)),
+        ("map", Value::Map(vec![(Value::Int8(1), rows(100))])),
+    ]);
+    let bytes = encode_with(&value, &EncodeOptions::default());
+
+    for i in (0..bytes.len()).step_by(bytes.len() / 40) {
+        for b in [0x01, 0x03, 0x14, 0xff] {
+            let mut changed = bytes.clone();
+            changed[i] = b;
+            match (decode(&changed), decode_parallel(&changed)) {
+                (Ok((a, _)), Ok((b, _))) => assert_same(&a, &b),
+                (Err(a), Err(b)) => {
+                    assert_eq!(a.location(), b.location());
+                    assert_eq!(discriminant(a.cause()), discriminant(b.cause()));
+                }
+                (a, b) => panic!("{:?} != {:?}", a.map(|_| ()), b.map(|_| ())),
+            }
+        }
+    }
+}
+
+#[test]
+fn deep_nesting() {
+    // Only containers near the top are split, so data nested far too deeply for the stack is still refused by the
+    // depth limit, and just as deep data within the limit decodes the same as it does in order.
+    let mut bytes = vec![0x12; 1_000_000];
+    bytes.push(0x05);
+    assert!(matches!(
+        decode_parallel_with_limits(&bytes, &DecodeLimits::default())
+            .unwrap_err()
+            .cause(),
+        DecodeError::DepthLimitError(128)
+    ));
+
+    // Without limits, the same as `decode`.
+    let mut bytes = vec![0x12; 1000];
+    bytes.push(0x05);
+    assert_same(
+        &decode_parallel(&bytes).unwrap().0,
+        &decode(&bytes).unwrap().0,
+    );
+
+    let limits = DecodeLimits {
+        depth: 2000,
+        ..DecodeLimits::none()
+    };
+    let mut bytes = vec![0x02; 1000];
+    bytes.extend_from_slice(&encode_with(&rows(2000), &EncodeOptions::default()));
+    bytes.extend(vec![0x03; 1000]);
+    assert_same(
+        &decode_parallel_with_limits(&bytes, &limits).unwrap().0,
+        &decode_with_limits(&bytes, &limits).unwrap().0,
+    );
+}
@@ -0,0 +1,179 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::encode::patch::{patch, patch_file};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::PatchError;
+use emp::value::Value;
+
+fn fixed(val: &Value) -> Vec<u8> {
+    encode_with(val, &EncodeOptions::fixed_width())
+}
+
+// Patches `old` to `new` and checks that only the bytes of the value changed.
+fn check(old: Value, new: Value) {
+    let value = Value::Array(vec![Value::Null, old, string("end")]);
+    let mut bytes = fixed(&value);
+    let before = bytes.clone();
+
+    patch(&mut bytes, "[1]", new.clone()).unwrap();
+    assert_eq!(bytes.len(), before.len());
+    assert_eq!(bytes[..2], before[..2]);
+    assert_eq!(bytes[bytes.len() - 4..], before[before.len() - 4..]);
+    assert_same(
+        &decode(&bytes).unwrap().0,
+        &Value::Array(vec![Value::Null, new, string("end")]),
+    );
+}
+
+#[test]
+fn fixed_width() {
+    check(Value::Bit(true), Value::Bit(false));
+    check(Value::Boolean(false), Value::Boolean(true));
+    check(Value::Int8(1), Value::Int8(i8::MIN));
+    check(Value::Int8(0), Value::Int8(15));
+    check(Value::Int16(0), Value::Int16(i16::MAX));
+    check(Value::Int32(7), Value::Int32(-i32::MAX));
+    check(Value::Int64(-1), Value::Int64(i64::MAX));
+    check(Value::Float(0.0), Value::Float(-0.25));
+    check(Value::Double(1.0), Value::Double(-1e-100));
+}
+
+#[test]
+fn bytes() {
+    let mut bytes = fixed(&Value::Int32(1));
+    assert_eq!(bytes, [0x09, 0, 0, 0, 1]);
+    patch(&mut bytes, "", Value::Int32(-2)).unwrap();
+    assert_eq!(bytes, [0x89, 0, 0, 0, 2]);
+
+    let mut bytes = fixed(&Value::Int8(5));
+    patch(&mut bytes, "$", Value::Int8(-128)).unwrap();
+    assert_eq!(bytes, [0x0e, 0x80]);
+}
+
+#[test]
+fn nested_paths() {
+    let value = object(vec![(
+        "a",
+        Value::Array(vec![
+            object(vec![("b", Value::Int16(1))]),
+            object(vec![("b", Value::Int16(2))]),
+        ]),
+    )]);
+    let mut bytes = fixed(&value);
+
+    patch(&mut bytes, "a[1].b", Value::Int16(300)).unwrap();
+    let expected = object(vec![(
+        "a",
+        Value::Array(vec![
+            object(vec![("b", Value::Int16(1))]),
+            object(vec![("b", Value::Int16(300))]),
+        ]),
+    )]);
+    assert_same(&decode(&bytes).unwrap().0, &expected);
+}
+
+#[test]
+fn trimmed_values_keep_their_length() {
+    // Without fixed_width a value can only be patched by one with at least as many leading zeros.
+    let mut bytes = encode(Value::Int32(1));
+    assert_eq!(bytes, [0x39, 1]);
+    patch(&mut bytes, "", Value::Int32(255)).unwrap();
+    assert_eq!(bytes, [0x39, 0xff]);
+    assert!(matches!(
+        patch(&mut bytes, "", Value::Int32(256)),
+        Err(PatchError::SizeError(Value::Int32(256)))
+    ));
+
+    // A packed Int8 has no byte to hold anything outside of 1 to 15.
+    let mut bytes = encode(Value::Int8(5));
+    patch(&mut bytes, "", Value::Int8(15)).unwrap();
+    assert_eq!(bytes, [0xfe]);
+    assert!(matches!(
+        patch(&mut bytes, "", Value::Int8(16)),
+        Err(PatchError::SizeError(_))
+    ));
+    assert!(matches!(
+        patch(&mut bytes, "", Value::Int8(0)),
+        Err(PatchError::SizeError(_))
+    ));
+
+    // The smallest number is stored as its absolute value, the same as encode does.
+    let mut bytes = fixed(&Value::Int64(0));
+    patch(&mut bytes, "", Value::Int64(i64::MIN)).unwrap();
+    assert_eq!(bytes, fixed(&Value::Int64(i64::MIN)));
+}
+
+#[test]
+fn errors() {
+    let mut bytes = fixed(&object(vec![
+        ("number", Value::Int32(1)),
+        ("name", string("a")),
+    ]));
+
+    assert!(matches!(
+        patch(&mut bytes, "missing", Value::Int8(1)),
+        Err(PatchError::PathNotFoundError(p)) if p == "missing"
+    ));
+    assert!(matches!(
+        patch(&mut bytes, "number[0]", Value::Int8(1)),
+        Err(PatchError::PathNotFoundError(_))
+    ));
+    assert!(matches!(
+        patch(&mut bytes, "number", Value::Int64(1)),
+        Err(PatchError::TypeMismatchError(Value::Int64(1)))
+    ));
+    assert!(matches!(
+        patch(&mut bytes, "name", string("b")),
+        Err(PatchError::TypeMismatchError(_))
+    ));
+    assert!(matches!(
+        patch(&mut bytes, "[", Value::Int8(1)),
+        Err(PatchError::ParseError(_))
+    ));
+    assert!(matches!(
+        patch(&mut [0x09, 0], "", Value::Int32(1)),
+        Err(PatchError::DecodeError(_))
+    ));
+}
+
+#[test]
+fn signed_and_encrypted() {
+    // A signature in front of the data, which is otherwise left as it is.
+    let mut signed = vec![0x6f];
+    signed.extend([0; 64]);
+    signed.extend(fixed(&Value::Int32(1)));
+    let before = signed.clone();
+    assert!(matches!(
+        patch(&mut signed, "", Value::Int32(2)),
+        Err(PatchError::SignedError)
+    ));
+    assert_eq!(signed, before);
+
+    let mut encrypted = vec![0x5f, 0x01, 0x02, 0x03];
+    assert!(matches!(
+        patch(&mut encrypted, "", Value::Int32(2)),
+        Err(PatchError::EncryptedError)
+    ));
+    assert_eq!(encrypted, [0x5f, 0x01, 0x02, 0x03]);
+}
+
+#[test]
+fn file() {
+    let file = std::env::temp_dir().join(format!("emp-patch-{}.emp", std::process::id()));
+    std::fs::write(&file, fixed(&object(vec![("score", Value::Int64(0))]))).unwrap();
+
+    patch_file(&file, "score", Value::Int64(42)).unwrap();
+    let bytes = std::fs::read(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    assert_same(
+        &decode(&bytes).unwrap().0,
+        &object(vec![("score", Value::Int64(42))]),
+    );
+    assert!(matches!(
+        patch_file(&file, "score", Value::Int64(1)),
+        Err(PatchError::IOError(_))
+    ));
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::{object, string};
+use emp::decode::{decode, decode_path};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::path::{self, Path, Segment};
+use emp::value::Value;
+
+fn at(bytes: &[u8], p: &str) -> Result<Option<Value>, DecodeError> {
+    decode_path(bytes, &path::from_str(p).unwrap())
+}
+
+fn found(bytes: &[u8], p: &str) -> Option<String> {
+    at(bytes, p).unwrap().map(|v| v.to_string())
+}
+
+fn players() -> Value {
+    object(vec![
+        ("count", Value::Int8(2)),
+        (
+            "players",
+            Value::Array(vec![
+                object(vec![("name", string("ann"))]),
+                object(vec![
+                    ("name", string("bob")),
+                    ("score", Value::Int32(-70000)),
+                ]),
+            ]),
+        ),
+    ])
+}
+
+#[test]
+fn finds_values() {
+    for options in [EncodeOptions::default(), EncodeOptions::size()] {
+        let bytes = encode_with(&players(), &options);
+        assert_eq!(found(&bytes, "count"), Some(Value::Int8(2).to_string()));
+        assert_eq!(
+            found(&bytes, "players[1].name"),
+            Some(string("bob").to_string())
+        );
+        assert_eq!(
+            found(&bytes, "players[1].score"),
+            Some(Value::Int32(-70000).to_string())
+        );
+        common::assert_same(&at(&bytes, "$").unwrap().unwrap(), &players());
+    }
+}
+
+#[test]
+fn missing_values() {
+    let bytes = encode(players());
+    for p in [
+        "score",
+        "players[2]",
+        "players[0].score",
+        "count.x",
+        "count[0]",
+        "players.name",
+    ] {
+        assert_eq!(found(&bytes, p), None, "{}", p);
+    }
+    assert_eq!(found(&encode(Value::Array(vec![])), "[0]"), None);
+}
+
+#[test]
+fn map_keys() {
+    let map = Value::Map(vec![
+        (Value::Int16(300), string("int")),
+        (string("s"), Value::Null),
+    ]);
+    let bytes = encode(map);
+    assert_eq!(found(&bytes, "[300]"), Some(string("int").to_string()));
+    assert_eq!(found(&bytes, "s"), Some(Value::Null.to_string()));
+    assert_eq!(found(&bytes, "[1]"), None);
+}
+
+#[test]
+fn only_the_path_is_decoded() {
+    // The first element is not valid UTF-8 but is only skipped over.
+    let bytes = [0x32, 0x14, 0xff, 0x1e, 0x03];
+    assert_eq!(found(&bytes, "[1]"), Some(Value::Int8(1).to_string()));
+    assert!(matches!(
+        at(&bytes, "[0]").unwrap_err().cause(),
+        DecodeError::StringDecodeError(_)
+    ));
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(
+        at(&[0x34, b'a'], "$").unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+}
+
+#[test]
+fn keys_match_decode() {
+    // An integer object key, a null map key, an object ending straight after a key, and a bad key one level down.
+    for (bytes, p, error) in [
+        (
+            &[0x10, 0x1e, 0x05][..],
+            "a",
+            "Invalid Key: `1B` at byte 1 ($)",
+        ),
+        (
+            &[0x2f, 0x10, 0x05, 0x1e],
+            "[1]",
+            "Invalid Key: `null` at byte 2 ($)",
+        ),
+        (
+            &[0x00, 0x14, b'a', 0x01],
+            "b",
+            "Unmatched Key: `a` at byte 3 ($)",
+        ),
+        (
+            &[0x12, 0x10, 0x1e, 0x05],
+            "[0].a",
+            "Invalid Key: `1B` at byte 2 ($[0])",
+        ),
+    ] {
+        assert_eq!(at(bytes, p).unwrap_err().to_string(), error);
+        assert_eq!(decode(bytes).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn duplicate_keys() {
+    // The last value for a key wins in objects and maps, the same as when they are decoded.
+    let bytes = [0x00, 0x14, b'a', 0x1e, 0x14, b'a', 0x2e, 0x01];
+    assert_eq!(found(&bytes, "a"), Some("2B".to_owned()));
+    assert_eq!(decode(&bytes).unwrap().0.to_string(), "{\"a\": 2B}");
+
+    let bytes = [0x2f, 0x00, 0x1e, 0x14, b'a', 0x1e, 0x14, b'b', 0x01];
+    assert_eq!(found(&bytes, "[1]"), Some("\"b\"".to_owned()));
+}
+
+fn round_trip(segments: Vec<Segment>) {
+    let path = Path { segments };
+    assert_eq!(path::from_str(&path.to_string()).unwrap(), path);
+}
+
+#[test]
+fn display_parses_back() {
+    round_trip(vec![Segment::Key("players".to_owned()), Segment::Index(2)]);
+    round_trip(vec![Segment::Key("a\\b".to_owned())]);
+    round_trip(vec![Segment::Key("a.\\b".to_owned())]);
+    round_trip(vec![Segment::Key("[\"\\\"]".to_owned())]);
+    round_trip(vec![
+        Segment::Key("a".to_owned()),
+        Segment::Key(String::new()),
+    ]);
+}
+
+#[test]
+fn quoted_keys() {
+    let path = path::from_str(r#"$["a.\\b"].c"#).unwrap();
+    assert_eq!(
+        path.segments,
+        vec![
+            Segment::Key("a.\\b".to_owned()),
+            Segment::Key("c".to_owned())
+        ]
+    );
+    assert_eq!(path.to_string(), r#"$["a.\\b"].c"#);
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::push::PushDecoder;
+use emp::decode::DecodeLimits;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+
+fn values() -> Vec<Value> {
+    vec![
+        object(vec![("a", Value::Array(vec![string("ab"), string("ab")]))]),
+        Value::Null,
+        string("a string which is too long for a nibble length"),
+        Value::Map(vec![(Value::Int8(1), Value::Array(vec![]))]),
+        Value::Array(vec![Value::Int64(-1); 16]),
+    ]
+}
+
+#[test]
+fn fed_in_pieces() {
+    let mut bytes = vec![];
+    for (value, options) in values().iter().zip([
+        EncodeOptions::size(),
+        EncodeOptions::default(),
+        EncodeOptions::fixed_width(),
+        EncodeOptions::speed(),
+        EncodeOptions::default(),
+    ]) {
+        bytes.extend(encode_with(value, &options));
+    }
+
+    for size in [1, 2, 3, 7, bytes.len()] {
+        let mut decoder = PushDecoder::new();
+        let mut decoded = vec![];
+        for piece in bytes.chunks(size) {
+            decoder.feed(piece);
+            while let Some(value) = decoder.next_value().unwrap() {
+                decoded.push(value);
+            }
+        }
+
+        assert_eq!(decoded.len(), values().len(), "pieces of {}", size);
+        for (a, b) in decoded.iter().zip(values().iter()) {
+            assert_same(a, b);
+        }
+        assert_eq!(decoder.buffered(), 0);
+    }
+}
+
+#[test]
+fn unfinished_value() {
+    let bytes = encode(string("abc"));
+
+    let mut decoder = PushDecoder::new();
+    assert!(decoder.next_value().unwrap().is_none());
+    decoder.feed(&bytes[..3]);
+    assert!(decoder.next_value().unwrap().is_none());
+    decoder.feed(&bytes[3..]);
+    assert_same(&decoder.next_value().unwrap().unwrap(), &string("abc"));
+    assert!(decoder.next_value().unwrap().is_none());
+}
+
+#[test]
+fn buffered() {
+    let mut bytes = encode(Value::Int8(1));
+    bytes.extend(encode(Value::Int32(1000)));
+
+    let mut decoder = PushDecoder::new();
+    decoder.feed(&bytes);
+    assert_eq!(decoder.buffered(), 4);
+    assert_same(&decoder.next_value().unwrap().unwrap(), &Value::Int8(1));
+    assert_eq!(decoder.buffered(), 3);
+    assert_same(&decoder.next_value().unwrap().unwrap(), &Value::Int32(1000));
+    assert_eq!(decoder.buffered(), 0);
+}
+
+#[test]
+fn errors() {
+    let mut decoder = PushDecoder::new();
+    decoder.feed(&[0x02, 0x03, 0x03]);
+    assert!(decoder.next_value().unwrap().is_some());
+    assert!(matches!(
+        decoder.next_value().unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x03, _)
+    ));
+
+    let mut decoder = PushDecoder::new();
+    decoder.feed(&[0x5f]);
+    assert!(matches!(
+        decoder.next_value().unwrap_err().cause(),
+        DecodeError::EncryptedError
+    ));
+
+    let mut decoder = PushDecoder::new();
+    decoder.feed(&[0x2f, 0x10, 0x05]);
+    assert!(matches!(
+        decoder.next_value().unwrap_err().cause(),
+        DecodeError::InvalidKeyError(Value::Null)
+    ));
+}
+
+#[test]
+fn depth_limit() {
+    let limits = DecodeLimits {
+        depth: 3,
+        ..DecodeLimits::none()
+    };
+
+    // Containers which have been closed, or filled up, no longer count towards the depth.
+    let mut bytes = vec![];
+    for _ in 0..100 {
+        bytes.extend([0x02, 0x02, 0x12, 0x05, 0x02, 0x03, 0x03, 0x03]);
+    }
+    let mut decoder = PushDecoder::with_limits(limits.clone());
+    for byte in &bytes {
+        decoder.feed(&[*byte]);
+        while decoder.next_value().unwrap().is_some() {}
+    }
+    assert_eq!(decoder.buffered(), 0);
+
+    let mut decoder = PushDecoder::with_limits(limits);
+    decoder.feed(&[0x02, 0x12, 0x02, 0x03, 0x02, 0x02, 0x02]);
+    let error = decoder.next_value().unwrap_err();
+    assert!(matches!(error.cause(), DecodeError::DepthLimitError(3)));
+    assert_eq!(error.location().unwrap().offset, 6);
+}
+
+#[test]
+fn unterminated_string() {
+    let limits = DecodeLimits {
+        string_length: 100,
+        ..DecodeLimits::none()
+    };
+
+    // A string which is never closed can't be buffered any further than the limit.
+    let mut decoder = PushDecoder::with_limits(limits.clone());
+    decoder.feed(&[0x04]);
+    let mut fed = 1;
+    let error = loop {
+        match decoder.next_value() {
+            Ok(value) => assert!(value.is_none()),
+            Err(e) => break e,
+        }
+        decoder.feed(b"aaaaaaaaaa");
+        fed += 10;
+    };
+    assert!(matches!(
+        error.cause(),
+        DecodeError::StringLengthLimitError(100)
+    ));
+    assert!(fed <= 111);
+
+    // A string of exactly the limit is fine.
+    let mut bytes = vec![0x04];
+    bytes.extend_from_slice(&[b'a'; 100]);
+    bytes.push(0x04);
+    let mut decoder = PushDecoder::with_limits(limits);
+    for byte in &bytes {
+        decoder.feed(&[*byte]);
+        if let Some(value) = decoder.next_value().unwrap() {
+            assert_same(&value, &string(&"a".repeat(100)));
+        }
+    }
+    assert_eq!(decoder.position(), 102);
+}
@@ -0,0 +1,123 @@
+use emp::decode::borrowed::decode_borrowed;
+use emp::decode::decode;
+use emp::decode::events::EventReader;
+use emp::decode::lazy::LazyValue;
+use emp::decode::push::PushDecoder;
+use emp::decode::stream::Decoder;
+use emp::decode::validate::validate;
+use emp::errors::DecodeError;
+use emp::value::parse;
+use emp::value::path::{self, Segment};
+
+// Runs `bytes` through every decoder, none of which may panic, returning what `decode` did.
+fn decode_all(bytes: &[u8]) -> Result<(), DecodeError> {
+    let result = decode(bytes).map(|_| ());
+    assert_eq!(validate(bytes).is_ok(), result.is_ok(), "{:02x?}", bytes);
+    #[cfg(feature = "parallel")]
+    assert_eq!(
+        emp::decode::parallel::decode_parallel(bytes).is_ok(),
+        result.is_ok()
+    );
+
+    let _ = decode_borrowed(bytes);
+    let _ = Decoder::new(bytes).decode();
+    if let Ok(lazy) = LazyValue::new(bytes) {
+        let _ = lazy.value();
+    }
+    if let Ok(events) = EventReader::new(bytes) {
+        events.take(bytes.len() + 1).for_each(drop);
+    }
+
+    let mut push = PushDecoder::new();
+    push.feed(bytes);
+    let _ = push.next_value();
+
+    result
+}
+
+fn is_eof(result: Result<(), DecodeError>) -> bool {
+    matches!(result.map_err(|e| e.cause().to_string()), Err(e) if e == DecodeError::EOFError.to_string())
+}
+
+#[test]
+fn empty() {
+    assert!(is_eof(decode_all(&[])));
+}
+
+#[test]
+fn truncated_numbers() {
+    // An Int8 missing its byte, an Int64 with seven leading zeros missing its last byte, and a Double missing half
+    // of its bytes.
+    for bytes in [
+        &[0x0e][..],
+        &[0x7c],
+        &[0x0c, 0x01, 0x02],
+        &[0x0b, 0x01, 0x02],
+    ] {
+        assert!(is_eof(decode_all(bytes)), "{:02x?}", bytes);
+    }
+}
+
+#[test]
+fn bad_map() {
+    // A map has to be followed by the start of a dictionary.
+    assert!(matches!(
+        decode_all(&[0x2f, 0x05]).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x05, _)
+    ));
+    assert!(is_eof(decode_all(&[0x2f])));
+}
+
+#[test]
+fn many_signatures() {
+    // Every decoder has to skip over any number of signatures without recursing for each one.
+    let mut bytes = vec![];
+    for _ in 0..400_000 {
+        bytes.push(0x6f);
+        bytes.extend_from_slice(&[0; 64]);
+    }
+    bytes.push(0x05);
+    assert!(decode_all(&bytes).is_ok());
+}
+
+#[test]
+fn every_two_bytes() {
+    for a in 0..=255 {
+        for b in 0..=255 {
+            let _ = decode_all(&[a, b]);
+        }
+    }
+}
+
+#[test]
+fn non_ascii_text() {
+    for text in [
+        "é",
+        "[é]",
+        "[1, é]",
+        "\"é",
+        "{\"a\": é}",
+        "日本",
+        "[\"日本\"]",
+    ] {
+        let _ = parse::from_str(text);
+    }
+    assert!(parse::from_str("é").is_err());
+    assert!(parse::from_str("[\"日本\"]").is_ok());
+
+    for text in ["é", "a.é", "[0]é", "\"é\"", "\"é"] {
+        let _ = path::from_str(text);
+    }
+}
+
+#[test]
+fn lone_quote_path() {
+    // Outside of square brackets a quote is part of a key like any other character.
+    let quote = path::from_str("\"").unwrap();
+    assert_eq!(quote.segments, vec![Segment::Key("\"".to_owned())]);
+    assert_eq!(path::from_str(&quote.to_string()).unwrap(), quote);
+
+    for text in ["[\"", "[\"]", "[\"\\\"]", "$[\"a\"", "a[\"b\"c]"] {
+        assert!(path::from_str(text).is_err(), "{}", text);
+    }
+}
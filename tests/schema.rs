@@ -0,0 +1,215 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::{decode, decode_metadata, decode_schema};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::{DecodeError, SchemaError};
+use emp::value::path::{Path, Segment};
+use emp::value::schema::{Field, Schema};
+use emp::value::Value;
+use std::collections::HashMap;
+
+fn fields(pairs: Vec<(&str, Field)>) -> Schema {
+    let mut fields = HashMap::new();
+    for (k, v) in pairs {
+        fields.insert(k.to_owned(), v);
+    }
+    Schema::Object(fields)
+}
+
+fn path(segments: Vec<Segment>) -> Path {
+    Path { segments }
+}
+
+#[test]
+fn infer() {
+    let value = object(vec![
+        ("same", Value::Array(vec![Value::Int8(1), Value::Int8(2)])),
+        ("mixed", Value::Array(vec![Value::Int8(1), Value::Null])),
+        ("empty", Value::Array(vec![])),
+        ("map", Value::Map(vec![(Value::Int32(1), string("a"))])),
+    ]);
+    let expected = fields(vec![
+        ("same", Field::new(Schema::Array(Box::new(Schema::Int8)))),
+        ("mixed", Field::new(Schema::Array(Box::new(Schema::Any)))),
+        ("empty", Field::new(Schema::Array(Box::new(Schema::Any)))),
+        (
+            "map",
+            Field::new(Schema::Map(
+                Box::new(Schema::Int32),
+                Box::new(Schema::String),
+            )),
+        ),
+    ]);
+
+    assert_eq!(Schema::infer(&value), expected);
+    assert!(expected.validate(&value).is_ok());
+}
+
+#[test]
+fn mismatch_paths() {
+    let schema = fields(vec![
+        ("a", Field::new(Schema::Int32)),
+        ("list", Field::new(Schema::Array(Box::new(Schema::Bit)))),
+    ]);
+    let list = Value::Array(vec![Value::Bit(true), Value::Bit(false), Value::Null]);
+
+    match schema.validate(&object(vec![
+        ("a", string("b")),
+        ("list", Value::Array(vec![])),
+    ])) {
+        Err(SchemaError::TypeMismatchError(p, Schema::Int32)) => {
+            assert_eq!(p, path(vec![Segment::Key("a".to_owned())]))
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match schema.validate(&object(vec![("a", Value::Int32(1)), ("list", list)])) {
+        Err(SchemaError::TypeMismatchError(p, Schema::Bit)) => {
+            assert_eq!(p.to_string(), "$.list[2]")
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match Schema::Int8.validate(&Value::Int16(1)) {
+        Err(SchemaError::TypeMismatchError(p, _)) => assert_eq!(p.to_string(), "$"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn map_errors_use_the_key() {
+    let schema = Schema::Map(Box::new(Schema::Any), Box::new(Schema::String));
+    let mismatch = |key: Value| match schema.validate(&Value::Map(vec![
+        (Value::Int8(7), string("a")),
+        (key, Value::Null),
+    ])) {
+        Err(SchemaError::TypeMismatchError(p, Schema::String)) => p,
+        r => panic!("unexpected result: {:?}", r),
+    };
+
+    assert_eq!(mismatch(Value::Int32(40)), path(vec![Segment::Index(40)]));
+    assert_eq!(
+        mismatch(string("b")),
+        path(vec![Segment::Key("b".to_owned())])
+    );
+    assert_eq!(
+        mismatch(Value::Int8(-3)),
+        path(vec![Segment::Key("-3B".to_owned())])
+    );
+
+    // The same path as the decoders give an error in the value.
+    let bytes = [0x2f, 0x10, 0x39, 0x28, 0x14, 0xff];
+    let error = decode(&bytes).unwrap_err();
+    assert_eq!(error.location().unwrap().path, mismatch(Value::Int32(40)));
+}
+
+#[test]
+fn fields_and_any() {
+    let schema = fields(vec![
+        ("a", Field::new(Schema::Any)),
+        (
+            "b",
+            Field {
+                schema: Schema::Null,
+                description: Some("left out".to_owned()),
+                optional: true,
+            },
+        ),
+    ]);
+
+    assert!(schema.validate(&object(vec![("a", Value::Null)])).is_ok());
+    assert!(schema
+        .validate(&object(vec![("a", string("a")), ("b", Value::Null)]))
+        .is_ok());
+    assert!(matches!(
+        schema.validate(&object(vec![("b", Value::Null)])),
+        Err(SchemaError::MissingFieldError(p)) if p.to_string() == "$.a"
+    ));
+    assert!(matches!(
+        schema.validate(&object(vec![("a", Value::Null), ("c", Value::Null)])),
+        Err(SchemaError::UnknownFieldError(p)) if p.to_string() == "$.c"
+    ));
+}
+
+#[test]
+fn value_round_trip() {
+    let schema = fields(vec![
+        (
+            "a",
+            Field {
+                schema: Schema::Map(Box::new(Schema::Bit), Box::new(Schema::Double)),
+                description: Some("pairs".to_owned()),
+                optional: true,
+            },
+        ),
+        ("b", Field::new(Schema::Array(Box::new(Schema::Any)))),
+    ]);
+    assert_eq!(Schema::from_value(&schema.to_value()).unwrap(), schema);
+
+    assert_same(&Schema::Int64.to_value(), &string("int64"));
+    assert_same(
+        &Schema::Array(Box::new(Schema::String)).to_value(),
+        &object(vec![("array", string("string"))]),
+    );
+    assert_eq!(
+        Schema::Array(Box::new(Schema::Float)).to_string(),
+        r#"{"array": "float"}"#
+    );
+}
+
+#[test]
+fn invalid_schemas() {
+    let invalid = vec![
+        Value::Int8(1),
+        string("int128"),
+        object(vec![]),
+        object(vec![("map", Value::Array(vec![string("int8")]))]),
+        object(vec![("object", object(vec![("a", object(vec![]))]))]),
+        object(vec![("array", string("any")), ("map", Value::Null)]),
+    ];
+    for val in invalid {
+        assert!(
+            matches!(
+                Schema::from_value(&val),
+                Err(SchemaError::InvalidSchemaError(_))
+            ),
+            "{}",
+            val
+        );
+    }
+}
+
+#[test]
+fn stored_with_data() {
+    let value = object(vec![("a", Value::Int32(1))]);
+    let schema = Schema::infer(&value);
+    let mut metadata = HashMap::new();
+    metadata.insert("b".to_owned(), Value::Null);
+
+    let bytes = encode_with(
+        &value,
+        &EncodeOptions {
+            schema: Some(schema.clone()),
+            metadata: Some(metadata),
+            ..EncodeOptions::default()
+        },
+    );
+
+    assert_eq!(decode_schema(&bytes).unwrap(), Some(schema));
+    assert_eq!(decode_metadata(&bytes).unwrap().unwrap().len(), 1);
+    assert_same(&decode(&bytes).unwrap().0, &value);
+    assert_eq!(decode_schema(&encode(value)).unwrap(), None);
+}
+
+#[test]
+fn stored_schema_errors() {
+    // The header says there is a schema but it is a number.
+    let bytes = [0x1f, 0x04, 0x4f, 0x1e, 0x05];
+    assert!(matches!(
+        decode_schema(&bytes).unwrap_err().cause(),
+        DecodeError::SchemaError(SchemaError::InvalidSchemaError(Value::Int8(1)))
+    ));
+    assert!(matches!(
+        decode_schema(&[0x1f, 0x04, 0x05]).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x05, 2)
+    ));
+}
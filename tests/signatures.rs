@@ -0,0 +1,129 @@
+#![cfg(feature = "signatures")]
+
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::decode;
+use emp::decode::verify::{decode_verified, decode_verified_detached, verify, verify_detached};
+use emp::encode::sign::{encode_signed, encode_signed_detached, public_key, sign, sign_detached};
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+use std::collections::HashMap;
+
+const SECRET: [u8; 32] = [3; 32];
+
+fn value() -> Value {
+    object(vec![("name", string("a")), ("score", Value::Int32(10))])
+}
+
+#[test]
+fn embedded() {
+    let public = public_key(&SECRET);
+    let bytes = encode_signed(&value(), &EncodeOptions::default(), &SECRET);
+    assert_eq!(bytes[0], 0x6f);
+    assert_eq!(bytes.len(), 1 + 64 + encode(value()).len());
+
+    assert_same(&decode_verified(&bytes, &public).unwrap(), &value());
+    // Decoding without checking skips over the signature.
+    assert_same(&decode(&bytes).unwrap().0, &value());
+
+    let plain = encode(value());
+    assert_eq!(verify(&sign(&plain, &SECRET), &public).unwrap(), &plain[..]);
+}
+
+#[test]
+fn detached() {
+    let public = public_key(&SECRET);
+    let (bytes, signature) = encode_signed_detached(&value(), &EncodeOptions::size(), &SECRET);
+
+    assert_same(
+        &decode_verified_detached(&bytes, &signature, &public).unwrap(),
+        &value(),
+    );
+    assert_eq!(signature, sign_detached(&bytes, &SECRET));
+    assert!(verify_detached(&bytes, &signature, &public).is_ok());
+}
+
+#[test]
+fn canonical_keys() {
+    // However the keys were inserted, the signed bytes are the same.
+    let mut forwards = HashMap::new();
+    let mut backwards = HashMap::new();
+    for i in 0..20 {
+        forwards.insert(format!("key {}", i), Value::Int8(i));
+        backwards.insert(format!("key {}", 19 - i), Value::Int8(19 - i));
+    }
+    let options = EncodeOptions::default();
+    assert_eq!(
+        encode_signed(&Value::Object(forwards), &options, &SECRET),
+        encode_signed(&Value::Object(backwards), &options, &SECRET)
+    );
+
+    let canonical = EncodeOptions {
+        canonical: true,
+        ..EncodeOptions::default()
+    };
+    let pairs = object(vec![("b", Value::Null), ("a", Value::Null)]);
+    assert_eq!(
+        encode_with(&pairs, &canonical),
+        [0x20, 0x14, b'a', 0x05, 0x14, b'b', 0x05]
+    );
+}
+
+#[test]
+fn wrong_key() {
+    let other = public_key(&[4; 32]);
+    let bytes = encode_signed(&value(), &EncodeOptions::default(), &SECRET);
+    assert!(matches!(
+        verify(&bytes, &other).unwrap_err().cause(),
+        DecodeError::SignatureError
+    ));
+
+    let plain = encode(value());
+    let signature = sign_detached(&plain, &SECRET);
+    assert!(matches!(
+        verify_detached(&plain, &signature, &other)
+            .unwrap_err()
+            .cause(),
+        DecodeError::SignatureError
+    ));
+}
+
+#[test]
+fn tampered() {
+    let public = public_key(&SECRET);
+    let bytes = encode_signed(&value(), &EncodeOptions::default(), &SECRET);
+
+    // Changing either the signature or the data after it breaks the signature.
+    for i in [1, 64, 65, bytes.len() - 1] {
+        let mut tampered = bytes.clone();
+        tampered[i] ^= 1;
+        assert!(matches!(
+            verify(&tampered, &public).unwrap_err().cause(),
+            DecodeError::SignatureError
+        ));
+    }
+
+    // An embedded signature also covers its identifier, so it cannot be used as a detached one.
+    let mut embedded = [0; 64];
+    embedded.copy_from_slice(&bytes[1..65]);
+    assert!(verify_detached(&bytes[65..], &embedded, &public).is_err());
+}
+
+#[test]
+fn truncated() {
+    let public = public_key(&SECRET);
+    let bytes = sign(&[], &SECRET);
+    assert_eq!(bytes.len(), 65);
+    assert!(verify(&bytes, &public).unwrap().is_empty());
+
+    assert!(matches!(
+        verify(&bytes[..64], &public).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        verify(&encode(value()), &public).unwrap_err().cause(),
+        DecodeError::UnexpectedByteError(0x20, _)
+    ));
+}
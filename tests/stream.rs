@@ -0,0 +1,145 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::stream::Decoder;
+use emp::decode::DecodeLimits;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::Value;
+use std::io::{self, Read};
+
+// A reader which hands out a single byte at a time.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+struct Broken;
+
+impl Read for Broken {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+    }
+}
+
+fn values() -> Vec<Value> {
+    vec![
+        Value::Null,
+        string("a string which is too long for a nibble length"),
+        object(vec![("a", Value::Array(vec![string("ab"), string("ab")]))]),
+        Value::Map(vec![(Value::Int16(-1), Value::Double(0.5))]),
+    ]
+}
+
+#[test]
+fn one_after_another() {
+    let mut bytes = vec![];
+    let mut ends = vec![];
+    for (value, options) in values().iter().zip([
+        EncodeOptions::default(),
+        EncodeOptions::fixed_width(),
+        EncodeOptions::size(),
+        EncodeOptions::speed(),
+    ]) {
+        bytes.extend(encode_with(value, &options));
+        ends.push(bytes.len() as u64);
+    }
+
+    let mut decoder = Decoder::new(Trickle(&bytes));
+    for (value, end) in values().iter().zip(ends) {
+        assert_same(&decoder.decode().unwrap(), value);
+        assert_eq!(decoder.position(), end);
+    }
+    assert!(decoder.next().is_none());
+}
+
+#[test]
+fn iterator() {
+    let bytes: Vec<u8> = values().into_iter().flat_map(encode).collect();
+    let decoded: Vec<Value> = Decoder::new(&bytes[..]).map(|v| v.unwrap()).collect();
+    assert_eq!(decoded.len(), values().len());
+    for (a, b) in decoded.iter().zip(values().iter()) {
+        assert_same(a, b);
+    }
+    assert!(Decoder::new(&[][..]).next().is_none());
+}
+
+#[test]
+fn errors() {
+    let bytes = encode(string("abc"));
+    for end in 1..bytes.len() {
+        assert!(matches!(
+            Decoder::new(&bytes[..end]).decode().unwrap_err().cause(),
+            DecodeError::EOFError
+        ));
+    }
+    assert!(matches!(
+        Decoder::new(&[][..]).decode().unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+    assert!(matches!(
+        Decoder::new(Broken).decode().unwrap_err().cause(),
+        DecodeError::IOError(e) if e.kind() == io::ErrorKind::ConnectionReset
+    ));
+    assert!(matches!(
+        Decoder::new(&[0x5f, 0x00][..])
+            .decode()
+            .unwrap_err()
+            .cause(),
+        DecodeError::EncryptedError
+    ));
+}
+
+#[test]
+fn unterminated_string() {
+    // A string which is never closed must not be read any further than the limit.
+    let mut bytes = vec![0x04];
+    bytes.resize(1 << 20, b'a');
+    let limits = DecodeLimits {
+        string_length: 100,
+        ..DecodeLimits::none()
+    };
+
+    let mut decoder = Decoder::with_limits(&bytes[..], limits.clone());
+    assert!(matches!(
+        decoder.decode().unwrap_err().cause(),
+        DecodeError::StringLengthLimitError(100)
+    ));
+    assert!(decoder.position() <= 102);
+
+    // A string of exactly the limit is fine, as is running out of data before it.
+    let mut bytes = vec![0x04];
+    bytes.extend_from_slice(&[b'a'; 100]);
+    bytes.push(0x04);
+    assert!(Decoder::with_limits(&bytes[..], limits.clone())
+        .decode()
+        .is_ok());
+    assert!(matches!(
+        Decoder::with_limits(&bytes[..50], limits)
+            .decode()
+            .unwrap_err()
+            .cause(),
+        DecodeError::EOFError
+    ));
+}
+
+#[test]
+fn stops_after_an_error() {
+    let mut bytes = encode(Value::Int8(1));
+    bytes.push(0xff);
+    bytes.extend(encode(Value::Int8(2)));
+
+    let mut decoder = Decoder::new(&bytes[..]);
+    assert!(decoder.next().unwrap().is_ok());
+    assert!(decoder.next().unwrap().is_err());
+    assert!(decoder.next().is_none());
+    assert!(decoder.next().is_none());
+}
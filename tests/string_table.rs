@@ -0,0 +1,131 @@
+mod common;
+
+use common::{assert_same, object, string};
+use emp::decode::borrowed::decode_borrowed;
+use emp::decode::events::EventReader;
+use emp::decode::lazy::LazyValue;
+use emp::decode::push::PushDecoder;
+use emp::decode::stream::Decoder;
+use emp::decode::validate::validate;
+use emp::decode::{decode, decode_path};
+use emp::encode::{encode, encode_with_string_table};
+use emp::errors::DecodeError;
+use emp::value::path::Path;
+use emp::value::Value;
+
+// `count` different strings, each of which appears twice.
+fn repeated(count: usize) -> Value {
+    let strings: Vec<Value> = (0..count).map(|i| string(&format!("s{:02}", i))).collect();
+    Value::Array([strings.clone(), strings].concat())
+}
+
+#[test]
+fn round_trip() {
+    let value = object(vec![
+        ("team", string("red")),
+        ("red", Value::Array(vec![string("team"), string("red")])),
+        ("once", string("only here")),
+        ("empty", Value::Array(vec![string(""), string("")])),
+    ]);
+
+    let bytes = encode_with_string_table(value.clone(), 1);
+    assert_eq!(bytes[..3], [0x1f, 0x01, 0x0f]);
+    assert_same(&decode(&bytes).unwrap().0, &value);
+}
+
+#[test]
+fn layout() {
+    let bytes = encode_with_string_table(Value::Array(vec![string("ab"), string("ab")]), 1);
+    assert_eq!(
+        bytes,
+        [0x1f, 0x01, 0x0f, 0x12, 0x24, b'a', b'b', 0x22, 0x16, 0x16],
+        "{:02x?}",
+        bytes
+    );
+}
+
+#[test]
+fn threshold() {
+    let value = Value::Array(vec![string("ab"), string("ab"), string("c"), string("c")]);
+
+    // Strings shorter than the threshold stay inline, the rest are referenced.
+    let bytes = encode_with_string_table(value.clone(), 2);
+    assert_eq!(bytes[2..8], [0x0f, 0x12, 0x24, b'a', b'b', 0x42]);
+    assert_same(&decode(&bytes).unwrap().0, &value);
+
+    // With nothing worth storing there is no table at all.
+    assert_eq!(encode_with_string_table(value.clone(), 3), encode(value));
+    let once = Value::Array(vec![string("ab"), string("cd")]);
+    assert_eq!(encode_with_string_table(once.clone(), 1), encode(once));
+}
+
+#[test]
+fn int32_references() {
+    // Indices up to 14 fit in the top 4 bits of the reference, 15 onwards follow it as an Int32.
+    let bytes = encode_with_string_table(repeated(16), 1);
+    let references = &bytes[bytes.len() - 19..];
+    assert_eq!(references[0], 0x16);
+    assert_eq!(references[14..], [0xf6, 0x06, 0x39, 0x0f, 0x03]);
+
+    assert_same(&decode(&bytes).unwrap().0, &repeated(16));
+}
+
+#[test]
+fn errors() {
+    // A table holding one string, and references to the second.
+    assert!(matches!(
+        decode(&[0x0f, 0x12, 0x14, b'a', 0x26]).unwrap_err().cause(),
+        DecodeError::StringReferenceError(1)
+    ));
+    assert!(matches!(
+        decode(&[0x0f, 0x12, 0x14, b'a', 0x06, 0x39, 0x0f])
+            .unwrap_err()
+            .cause(),
+        DecodeError::StringReferenceError(15)
+    ));
+
+    // The Int32 index is cut off.
+    assert!(decode(&[0x0f, 0x12, 0x14, b'a', 0x06]).is_err());
+    assert!(decode(&[0x0f, 0x12, 0x14, b'a', 0x06, 0x39]).is_err());
+}
+
+#[test]
+fn table_which_is_not_an_array() {
+    // A table holding a Null, and an array holding a Null, each followed by the data.
+    for (bytes, tag) in [
+        (&[0x1f, 0x01, 0x0f, 0x05, 0x05][..], 0x05),
+        (&[0x1f, 0x01, 0x0f, 0x12, 0x05, 0x05][..], 0x12),
+    ] {
+        let mut push = PushDecoder::new();
+        push.feed(bytes);
+        let errors = vec![
+            decode(bytes).unwrap_err(),
+            decode_path(bytes, &Path::default()).unwrap_err(),
+            decode_borrowed(bytes).unwrap_err(),
+            validate(bytes).unwrap_err(),
+            Decoder::new(bytes).decode().unwrap_err(),
+            push.next_value().unwrap_err(),
+            LazyValue::new(bytes)
+                .and_then(|l| l.value().map(|_| ()))
+                .unwrap_err(),
+            EventReader::new(bytes)
+                .and_then(|mut r| r.try_for_each(|e| e.map(|_| ())))
+                .unwrap_err(),
+        ];
+        #[cfg(feature = "parallel")]
+        let errors: Vec<_> = errors
+            .into_iter()
+            .chain(emp::decode::parallel::decode_parallel(bytes).err())
+            .collect();
+
+        for e in errors {
+            assert!(
+                matches!(e.cause(), DecodeError::UnexpectedByteError(b, 3) if *b == tag),
+                "{:02x?}: {}",
+                bytes,
+                e
+            );
+            assert_eq!(e.location().unwrap().path, Path::default());
+        }
+    }
+}
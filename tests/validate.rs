@@ -0,0 +1,121 @@
+mod common;
+
+use common::{object, string};
+use emp::decode::decode;
+use emp::decode::validate::validate;
+use emp::encode::{encode, encode_with, EncodeOptions};
+use emp::errors::DecodeError;
+use emp::value::schema::Schema;
+use emp::value::Value;
+use std::collections::HashMap;
+
+#[test]
+fn stats() {
+    let stats = validate(&encode(Value::Array(vec![Value::Array(vec![Value::Null])]))).unwrap();
+    assert_eq!(stats.depth, 2);
+    assert_eq!(stats.elements, 3);
+    assert_eq!(stats.length, 3);
+
+    let stats = validate(&encode(Value::Int32(7))).unwrap();
+    assert_eq!((stats.depth, stats.elements), (0, 1));
+}
+
+#[test]
+fn keys_and_header() {
+    let value = object(vec![("a", string("x")), ("b", string("x"))]);
+    let bytes = encode_with(&value, &EncodeOptions::size());
+    let stats = validate(&bytes).unwrap();
+    assert_eq!(stats.depth, 1);
+    assert_eq!(stats.elements, 5);
+    assert_eq!(stats.length, bytes.len());
+}
+
+#[test]
+fn trailing_data() {
+    let bytes = encode(Value::Array(vec![Value::Int8(1)]));
+    let mut trailing = bytes.clone();
+    trailing.extend([0x05, 0x05]);
+    assert_eq!(validate(&trailing).unwrap().length, bytes.len());
+}
+
+#[test]
+fn deep_nesting() {
+    let depth = 100_000;
+    let mut bytes = vec![0x02; depth];
+    bytes.extend(vec![0x03; depth]);
+    assert_eq!(validate(&bytes).unwrap().depth, depth);
+
+    bytes.pop();
+    assert!(matches!(
+        validate(&bytes).unwrap_err().cause(),
+        DecodeError::EOFError
+    ));
+}
+
+#[test]
+fn errors_match_decode() {
+    let inputs: [&[u8]; 4] = [&[0x14, 0xff], &[0x34, b'a'], &[0x22, 0x05, 0x01], &[0x0a]];
+    for bytes in inputs {
+        let a = validate(bytes).unwrap_err();
+        let b = decode(bytes).unwrap_err();
+        assert_eq!(a.to_string(), b.to_string(), "{:02x?}", bytes);
+    }
+}
+
+// Data with metadata, a schema and a string table in front of it.
+fn with_header() -> Vec<u8> {
+    let value = object(vec![
+        ("names", Value::Array(vec![string("ann"), string("ann")])),
+        (
+            "map",
+            Value::Map(vec![(Value::Int8(1), Value::Double(0.5))]),
+        ),
+    ]);
+    let mut metadata = HashMap::new();
+    metadata.insert("author".to_owned(), string("someone"));
+
+    encode_with(
+        &value,
+        &EncodeOptions {
+            metadata: Some(metadata),
+            schema: Some(Schema::infer(&value)),
+            ..EncodeOptions::size()
+        },
+    )
+}
+
+#[test]
+fn malformed_metadata() {
+    // Metadata holding a string which is not UTF-8, followed by well formed data.
+    let bytes = [
+        0x1f, 0x02, 0x3f, 0x10, 0x64, 0x61, 0x75, 0x74, 0x68, 0xca, 0x72, 0x14, 0x78, 0x05, 0x05,
+    ];
+
+    assert_eq!(
+        validate(&bytes).unwrap_err().to_string(),
+        decode(&bytes).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn agrees_with_decode() {
+    // Every prefix of the data and every change to a single byte of it.
+    let bytes = with_header();
+    let mut inputs: Vec<Vec<u8>> = (0..=bytes.len()).map(|i| bytes[..i].to_vec()).collect();
+    for i in 0..bytes.len() {
+        for b in [0x00, 0x04, 0x0f, 0x12, 0x80, 0xff] {
+            let mut changed = bytes.clone();
+            changed[i] = b;
+            inputs.push(changed);
+        }
+    }
+
+    for input in inputs {
+        assert_eq!(
+            validate(&input).is_ok(),
+            decode(&input).is_ok(),
+            "{:02x?}",
+            input
+        );
+    }
+}